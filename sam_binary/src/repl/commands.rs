@@ -0,0 +1,41 @@
+use nu_ansi_term::Color::Green;
+use sam_library::{errors::ErrorWithMessage, parser, AngleMode, SamError, SamVM};
+
+/// Handles REPL commands, which are lines starting with `:`.
+pub fn run_command(vm: &mut SamVM, line: &str) -> Result<(), SamError> {
+    let line = line.trim().trim_start_matches(':');
+    let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+    match command {
+        "simplify" => {
+            let expr = parser::parse_input(argument.trim())?;
+            println!("{}", Green.paint(vm.optimize(&expr).to_string()));
+        }
+        "angle" => match argument.trim() {
+            "" => println!("{}", Green.paint(vm.angle_mode().to_string())),
+            mode => vm.set_angle_mode(mode.parse::<AngleMode>()?),
+        },
+        "const" => print_constants(vm, argument.trim())?,
+        _ => return Err(ErrorWithMessage::new_box(&format!("unknown command :{}", command))),
+    }
+    Ok(())
+}
+
+/// Prints one constant with its unit and description, or every constant
+/// whose name starts with `name.`, or all of them.
+fn print_constants(vm: &SamVM, name: &str) -> Result<(), SamError> {
+    if let Some(constant) = vm.constant(name) {
+        println!("{}", Green.paint(constant.to_string()));
+        return Ok(());
+    }
+    let prefix = format!("{}.", name);
+    let matching: Vec<String> = vm
+        .constants()
+        .filter(|constant| name.is_empty() || constant.name.starts_with(&prefix))
+        .map(|constant| constant.to_string())
+        .collect();
+    if matching.is_empty() {
+        return Err(ErrorWithMessage::new_box(&format!("unknown constant {}", name)));
+    }
+    println!("{}", Green.paint(matching.join("\n")));
+    Ok(())
+}
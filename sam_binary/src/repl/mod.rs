@@ -1,3 +1,5 @@
+mod commands;
+
 use sam_library::*;
 use nu_ansi_term::Color::{Green, Red};
 use rustyline::error::ReadlineError;
@@ -28,6 +30,14 @@ pub fn run_repl() -> Result<(), SamError> {
                 }
                 rl.add_history_entry(line.as_str());
 
+                if line.trim_start().starts_with(':') {
+                    if let Err(e) = commands::run_command(&mut vm, &line) {
+                        eprintln!("Error: {}", Red.paint(e.to_string()));
+                    }
+                    history.push(line);
+                    continue;
+                }
+
                 let output = parser::parse_input(line.as_str()).and_then(|output| {
                     vm.interpret(output).and_then(|real| {
                         println!("{}", Green.paint(real.to_string()));
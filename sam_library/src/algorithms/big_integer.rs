@@ -0,0 +1,428 @@
+use std::{
+    cmp::Ordering,
+    fmt::{Display, Formatter, Result},
+    ops::{Add, BitAnd, BitOr, BitXor, Mul, Neg, Shl, Shr, Sub},
+};
+
+/// An arbitrarily large integer, stored as its sign and its magnitude in
+/// base 2^32 with the least significant digit first and no leading zeros.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn zero() -> BigInt {
+        BigInt { negative: false, magnitude: vec![] }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.magnitude.len() > 2 {
+            return None;
+        }
+        let magnitude = self.magnitude.iter().rev().fold(0_u64, |n, digit| n << 32 | *digit as u64);
+        match self.negative {
+            true if magnitude <= i64::MIN.unsigned_abs() => Some(0_i64.wrapping_sub_unsigned(magnitude)),
+            false => i64::try_from(magnitude).ok(),
+            _ => None,
+        }
+    }
+
+    /// The nearest float, or an infinity when it is out of range.
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self.magnitude.iter().rev().fold(0.0, |x, digit| x * 4294967296.0 + *digit as f64);
+        if self.negative {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    /// The number of bits in the magnitude.
+    pub fn bits(&self) -> u64 {
+        match self.magnitude.last() {
+            Some(top) => 32 * self.magnitude.len() as u64 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    pub fn pow(&self, mut exponent: u32) -> BigInt {
+        let mut result = BigInt::from(1_u64);
+        let mut base = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = &result * &base;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = &base * &base;
+            }
+        }
+        result
+    }
+
+    /// The quotient and remainder of dividing the magnitude by `divisor`,
+    /// which must not be 0. The quotient keeps the sign.
+    pub fn div_rem_u64(&self, divisor: u64) -> (BigInt, u64) {
+        let mut remainder: u128 = 0;
+        let mut quotient = vec![0; self.magnitude.len()];
+        for (i, digit) in self.magnitude.iter().enumerate().rev() {
+            let current = remainder << 32 | *digit as u128;
+            quotient[i] = (current / divisor as u128) as u32;
+            remainder = current % divisor as u128;
+        }
+        (BigInt::new(self.negative, quotient), remainder as u64)
+    }
+
+    /// The quotient rounded toward zero and the remainder, which has the
+    /// sign of `self`, as for `i64`. `divisor` must not be 0.
+    pub fn div_rem(&self, divisor: &BigInt) -> (BigInt, BigInt) {
+        let negative = self.negative != divisor.negative;
+        if let Some(small) = divisor.to_i64().map(i64::unsigned_abs) {
+            let (quotient, remainder) = self.div_rem_u64(small);
+            let remainder = BigInt::new(self.negative, BigInt::from(remainder).magnitude);
+            return (BigInt::new(negative, quotient.magnitude), remainder);
+        }
+        // Long division one bit at a time.
+        let mut quotient = vec![0_u32; self.magnitude.len()];
+        let mut remainder: Vec<u32> = vec![];
+        for bit in (0..self.bits()).rev() {
+            remainder = shift_magnitude_left(&remainder, 1);
+            if self.magnitude[(bit / 32) as usize] >> (bit % 32) & 1 == 1 {
+                match remainder.first_mut() {
+                    Some(digit) => *digit |= 1,
+                    None => remainder.push(1),
+                }
+            }
+            if compare_magnitudes(&remainder, &divisor.magnitude) != Ordering::Less {
+                remainder = trim(sub_magnitudes(&remainder, &divisor.magnitude));
+                quotient[(bit / 32) as usize] |= 1 << (bit % 32);
+            }
+        }
+        (BigInt::new(negative, quotient), BigInt::new(self.negative, remainder))
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt::new(false, self.magnitude.clone())
+    }
+
+    /// The greatest common divisor, which is never negative.
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let (mut a, mut b) = (self.abs(), other.abs());
+        while !b.is_zero() {
+            (a, b) = (b.clone(), a.div_rem(&b).1);
+        }
+        a
+    }
+
+    pub fn lcm(&self, other: &BigInt) -> BigInt {
+        if self.is_zero() || other.is_zero() {
+            return BigInt::zero();
+        }
+        (&self.div_rem(&self.gcd(other)).0 * other).abs()
+    }
+
+    /// `⌊√n⌋` for `n` that is not negative, by Newton's method from above.
+    pub fn isqrt(&self) -> BigInt {
+        if self.is_zero() {
+            return BigInt::zero();
+        }
+        let mut root = &BigInt::from(1_u64) << (self.bits() / 2 + 1) as u32;
+        loop {
+            let next = &(&root + &self.div_rem(&root).0) >> 1;
+            if next >= root {
+                return root;
+            }
+            root = next;
+        }
+    }
+
+    /// The nearest float to `self / divisor`, even when both are too large
+    /// for a float.
+    pub fn div_f64(&self, divisor: &BigInt) -> f64 {
+        // Keep at least 64 bits of the quotient.
+        let shift = (divisor.bits() + 64).saturating_sub(self.bits());
+        let (quotient, _) = (self << shift as u32).div_rem(divisor);
+        quotient.to_f64() * 2_f64.powi(-(shift as i32))
+    }
+
+    /// The digits of `self` in two's complement, sign extended to `length`.
+    fn twos_complement(&self, length: usize) -> Vec<u32> {
+        let mut digits = self.magnitude.clone();
+        digits.resize(length, 0);
+        if self.negative {
+            negate_digits(&mut digits);
+        }
+        digits
+    }
+
+    fn from_twos_complement(mut digits: Vec<u32>) -> BigInt {
+        let negative = digits.last().is_some_and(|top| top >> 31 == 1);
+        if negative {
+            negate_digits(&mut digits);
+        }
+        BigInt::new(negative, digits)
+    }
+
+    fn bitwise(&self, other: &BigInt, op: fn(u32, u32) -> u32) -> BigInt {
+        let length = self.magnitude.len().max(other.magnitude.len()) + 1;
+        let (a, b) = (self.twos_complement(length), other.twos_complement(length));
+        BigInt::from_twos_complement(a.iter().zip(&b).map(|(a, b)| op(*a, *b)).collect())
+    }
+
+    fn new(negative: bool, magnitude: Vec<u32>) -> BigInt {
+        let magnitude = trim(magnitude);
+        let negative = negative && !magnitude.is_empty();
+        BigInt { negative, magnitude }
+    }
+}
+
+impl From<u64> for BigInt {
+    fn from(n: u64) -> Self {
+        BigInt::new(false, vec![n as u32, (n >> 32) as u32])
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(n: i64) -> Self {
+        let magnitude = BigInt::from(n.unsigned_abs());
+        BigInt::new(n < 0, magnitude.magnitude)
+    }
+}
+
+fn trim(mut magnitude: Vec<u32>) -> Vec<u32> {
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+    magnitude
+}
+
+/// Two's complement negation in place: invert and add one.
+fn negate_digits(digits: &mut [u32]) {
+    let mut carry = true;
+    for digit in digits {
+        let (sum, overflow) = (!*digit).overflowing_add(carry as u32);
+        *digit = sum;
+        carry = overflow;
+    }
+}
+
+fn shift_magnitude_left(magnitude: &[u32], bits: u32) -> Vec<u32> {
+    if magnitude.is_empty() {
+        return vec![];
+    }
+    let mut shifted = vec![0; (bits / 32) as usize];
+    let mut carry = 0_u32;
+    for digit in magnitude {
+        let wide = (*digit as u64) << (bits % 32);
+        shifted.push(wide as u32 | carry);
+        carry = (wide >> 32) as u32;
+    }
+    shifted.push(carry);
+    trim(shifted)
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut sum = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0_u64;
+    for (i, digit) in long.iter().enumerate() {
+        let total = *digit as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        sum.push(total as u32);
+        carry = total >> 32;
+    }
+    sum.push(carry as u32);
+    sum
+}
+
+/// `a - b` for magnitudes with `a >= b`.
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(a.len());
+    let mut borrow = 0_i64;
+    for (i, digit) in a.iter().enumerate() {
+        let mut total = *digit as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = (total < 0) as i64;
+        total += borrow << 32;
+        difference.push(total as u32);
+    }
+    difference
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+    fn add(self, other: Self) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_magnitudes(&self.magnitude, &other.magnitude));
+        }
+        match compare_magnitudes(&self.magnitude, &other.magnitude) {
+            Ordering::Less => BigInt::new(other.negative, sub_magnitudes(&other.magnitude, &self.magnitude)),
+            _ => BigInt::new(self.negative, sub_magnitudes(&self.magnitude, &other.magnitude)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+    fn sub(self, other: Self) -> BigInt {
+        self + &-other
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+    fn mul(self, other: Self) -> BigInt {
+        let mut product = vec![0_u32; self.magnitude.len() + other.magnitude.len()];
+        for (i, a) in self.magnitude.iter().enumerate() {
+            let mut carry = 0_u64;
+            for (j, b) in other.magnitude.iter().enumerate() {
+                let total = *a as u64 * *b as u64 + product[i + j] as u64 + carry;
+                product[i + j] = total as u32;
+                carry = total >> 32;
+            }
+            product[i + other.magnitude.len()] = carry as u32;
+        }
+        BigInt::new(self.negative != other.negative, product)
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.magnitude.clone())
+    }
+}
+
+impl Shl<u32> for &BigInt {
+    type Output = BigInt;
+    fn shl(self, bits: u32) -> BigInt {
+        BigInt::new(self.negative, shift_magnitude_left(&self.magnitude, bits))
+    }
+}
+
+/// Rounds toward negative infinity, as `>>` does for `i64`.
+impl Shr<u32> for &BigInt {
+    type Output = BigInt;
+    fn shr(self, bits: u32) -> BigInt {
+        if self.negative {
+            let one = BigInt::from(1_u64);
+            return &-&(&(&-self - &one) >> bits) - &one;
+        }
+        let digits = (bits / 32) as usize;
+        let mut shifted = vec![0; self.magnitude.len().saturating_sub(digits)];
+        for (i, digit) in shifted.iter_mut().enumerate() {
+            let wide = self.magnitude[i + digits] as u64 | (*self.magnitude.get(i + digits + 1).unwrap_or(&0) as u64) << 32;
+            *digit = (wide >> (bits % 32)) as u32;
+        }
+        BigInt::new(false, shifted)
+    }
+}
+
+impl BitAnd for &BigInt {
+    type Output = BigInt;
+    fn bitand(self, other: Self) -> BigInt {
+        self.bitwise(other, |a, b| a & b)
+    }
+}
+
+impl BitOr for &BigInt {
+    type Output = BigInt;
+    fn bitor(self, other: Self) -> BigInt {
+        self.bitwise(other, |a, b| a | b)
+    }
+}
+
+impl BitXor for &BigInt {
+    type Output = BigInt;
+    fn bitxor(self, other: Self) -> BigInt {
+        self.bitwise(other, |a, b| a ^ b)
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitudes(&self.magnitude, &other.magnitude),
+            (true, true) => compare_magnitudes(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        // Peel off nine decimal digits at a time.
+        let mut chunks = vec![];
+        let mut rest = self.clone();
+        while !rest.is_zero() {
+            let (quotient, remainder) = rest.div_rem_u64(1_000_000_000);
+            chunks.push(remainder);
+            rest = quotient;
+        }
+        let mut digits = match chunks.pop() {
+            Some(top) => top.to_string(),
+            None => return write!(f, "0"),
+        };
+        for chunk in chunks.iter().rev() {
+            digits.push_str(&format!("{:09}", chunk));
+        }
+        if self.negative {
+            write!(f, "-{}", digits)
+        } else {
+            write!(f, "{}", digits)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BigInt;
+
+    #[test]
+    fn test_big_integer_arithmetic() {
+        let big = BigInt::from(u64::MAX);
+        let square = &big * &big;
+        assert!(square.to_string() == "340282366920938463426481119284349108225");
+        assert!((&square - &square).is_zero());
+        assert!((&BigInt::from(-5_i64) + &BigInt::from(3_i64)).to_i64() == Some(-2));
+        assert!(BigInt::from(i64::MIN).to_i64() == Some(i64::MIN));
+        assert!((&BigInt::from(i64::MAX) + &BigInt::from(1_i64)).to_i64().is_none());
+        assert!(BigInt::from(2_u64).pow(100).to_string() == "1267650600228229401496703205376");
+        assert!(BigInt::from(-7_i64) < BigInt::from(-3_i64) && -&square < BigInt::zero());
+        let (quotient, remainder) = square.div_rem_u64(10);
+        assert!(remainder == 5 && quotient.to_string() == "34028236692093846342648111928434910822");
+        assert!((square.to_f64() - 3.402823669209385e38).abs() < 1e24);
+
+        let (quotient, remainder) = (&square + &BigInt::from(7_u64)).div_rem(&-&big);
+        assert!(quotient == -&big && remainder == BigInt::from(7_u64));
+        let (quotient, remainder) = (-&square).div_rem(&BigInt::from(10_u64));
+        assert!(quotient.to_string() == "-34028236692093846342648111928434910822" && remainder.to_i64() == Some(-5));
+        assert!((&BigInt::from(1_u64) << 100).to_string() == "1267650600228229401496703205376");
+        assert!((&square >> 64) == BigInt::from(u64::MAX - 1) && (&BigInt::from(-5_i64) >> 1).to_i64() == Some(-3));
+        assert!((&(&big << 10) & &BigInt::from(-1024_i64)) == &big << 10);
+        assert!((&BigInt::from(-6_i64) ^ &(&BigInt::from(1_u64) << 70)).to_string() == "-1180591620717411303430");
+        assert!(square.div_f64(&big) == u64::MAX as f64);
+        assert!(square.isqrt() == big && (&square - &BigInt::from(1_u64)).isqrt() == &big - &BigInt::from(1_u64));
+        assert!(square.gcd(&-&(&big * &BigInt::from(6_u64))) == &big * &BigInt::from(3_u64));
+        assert!(big.lcm(&BigInt::from(-2_i64)) == &big * &BigInt::from(2_u64));
+    }
+}
@@ -0,0 +1,138 @@
+use std::f64::consts::LN_2;
+
+use crate::errors::{ErrorWithMessage, SamError};
+
+use super::{big_integer::BigInt, special::ln_gamma};
+
+/// Results are refused beyond this many bits, about 315,000 digits, rather
+/// than tying the machine up for minutes.
+const MAX_BITS: f64 = (1 << 20) as f64;
+
+pub fn factorial(n: u64) -> Result<BigInt, SamError> {
+    check_size(ln_factorial(n))?;
+    Ok(product(1..=n))
+}
+
+/// The number of ways to choose `k` of `n` items, `n! / (k! (n - k)!)`.
+pub fn choose(n: u64, k: u64) -> Result<BigInt, SamError> {
+    if k > n {
+        return Ok(BigInt::zero());
+    }
+    check_size(ln_factorial(n) - ln_factorial(k) - ln_factorial(n - k))?;
+    let k = k.min(n - k);
+    let mut result = BigInt::from(1_u64);
+    for i in 1..=k {
+        // Each partial product is itself a binomial coefficient, so the
+        // division is exact.
+        result = (&result * &BigInt::from(n - k + i)).div_rem_u64(i).0;
+    }
+    Ok(result)
+}
+
+/// The number of ordered arrangements of `k` of `n` items, `n! / (n - k)!`.
+pub fn permutations(n: u64, k: u64) -> Result<BigInt, SamError> {
+    if k > n {
+        return Ok(BigInt::zero());
+    }
+    check_size(ln_factorial(n) - ln_factorial(n - k))?;
+    Ok(product(n - k + 1..=n))
+}
+
+/// The number of ways to split `k1 + k2 + ...` items into groups of sizes
+/// `k1, k2, ...`.
+pub fn multinomial(groups: &[u64]) -> Result<BigInt, SamError> {
+    let total = groups.iter().try_fold(0_u64, |total, k| total.checked_add(*k)).ok_or_else(too_large)?;
+    check_size(ln_factorial(total) - groups.iter().map(|k| ln_factorial(*k)).sum::<f64>())?;
+    let mut result = BigInt::from(1_u64);
+    let mut seen = 0;
+    for k in groups {
+        seen += k;
+        result = &result * &choose(seen, *k)?;
+    }
+    Ok(result)
+}
+
+/// The `n`th Catalan number, `C(2n, n) / (n + 1)`.
+pub fn catalan(n: u64) -> Result<BigInt, SamError> {
+    let central = choose(n.checked_mul(2).ok_or_else(too_large)?, n)?;
+    Ok(central.div_rem_u64(n + 1).0)
+}
+
+/// The `n`th Fibonacci number by fast doubling, extended to negative `n`
+/// with `F(-n) = (-1)^(n + 1) F(n)`.
+pub fn fibonacci(n: i64) -> Result<BigInt, SamError> {
+    let m = n.unsigned_abs();
+    // F(m) grows like φ^m, which is about 0.694 bits per step.
+    check_size(m as f64 * 0.4812118250596034)?;
+    let (mut a, mut b) = (BigInt::zero(), BigInt::from(1_u64));
+    for bit in (0..64 - m.leading_zeros()).rev() {
+        // From (F(k), F(k + 1)) to (F(2k), F(2k + 1)).
+        let two_b = &b + &b;
+        let doubled = &a * &(&two_b - &a);
+        let next = &(&a * &a) + &(&b * &b);
+        (a, b) = (doubled, next);
+        if m >> bit & 1 == 1 {
+            (a, b) = (b.clone(), &a + &b);
+        }
+    }
+    match n < 0 && m.is_multiple_of(2) {
+        true => Ok(-&a),
+        false => Ok(a),
+    }
+}
+
+fn product(factors: impl Iterator<Item = u64>) -> BigInt {
+    // Multiply small factors together natively until they would overflow.
+    let mut result = BigInt::from(1_u64);
+    let mut chunk = 1_u64;
+    for factor in factors {
+        match chunk.checked_mul(factor) {
+            Some(next) => chunk = next,
+            None => {
+                result = &result * &BigInt::from(chunk);
+                chunk = factor;
+            }
+        }
+    }
+    &result * &BigInt::from(chunk)
+}
+
+fn ln_factorial(n: u64) -> f64 {
+    ln_gamma(n as f64 + 1.0)
+}
+
+fn check_size(ln_result: f64) -> Result<(), SamError> {
+    match ln_result / LN_2 > MAX_BITS {
+        true => Err(too_large()),
+        false => Ok(()),
+    }
+}
+
+fn too_large() -> SamError {
+    ErrorWithMessage::new_box("the result is too large")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{catalan, choose, factorial, fibonacci, multinomial, permutations};
+
+    #[test]
+    fn test_combinatorics() {
+        assert!(factorial(0).unwrap().to_i64() == Some(1));
+        assert!(factorial(20).unwrap().to_i64() == Some(2432902008176640000));
+        assert!(factorial(30).unwrap().to_string() == "265252859812191058636308480000000");
+        assert!(factorial(u64::MAX).is_err());
+
+        assert!(choose(5, 2).unwrap().to_i64() == Some(10));
+        assert!(choose(3, 5).unwrap().is_zero());
+        assert!(choose(100, 50).unwrap().to_string() == "100891344545564193334812497256");
+        assert!(permutations(10, 3).unwrap().to_i64() == Some(720));
+        assert!(multinomial(&[2, 3, 4]).unwrap().to_i64() == Some(1260));
+        assert!(catalan(10).unwrap().to_i64() == Some(16796));
+
+        assert!(fibonacci(0).unwrap().is_zero());
+        assert!(fibonacci(10).unwrap().to_i64() == Some(55));
+        assert!(fibonacci(-8).unwrap().to_i64() == Some(-21));
+        assert!(fibonacci(100).unwrap().to_string() == "354224848179261915075");
+    }
+}
@@ -0,0 +1,54 @@
+use crate::errors::{ErrorWithMessage, SamError};
+
+/// How much the step shrinks between rows of the extrapolation table.
+const SHRINK: f64 = 1.4;
+
+const MAX_ROWS: usize = 10;
+
+/// The derivative of `f` at `x` and an estimate of its error, by Richardson
+/// extrapolation of central differences with shrinking steps (Ridders'
+/// method). Stops early once higher orders stop improving the estimate.
+pub fn derivative(f: &mut dyn FnMut(f64) -> Result<f64, SamError>, x: f64) -> Result<(f64, f64), SamError> {
+    let mut central = |h: f64| -> Result<f64, SamError> { Ok((f(x + h)? - f(x - h)?) / (2.0 * h)) };
+
+    let mut h = 0.1 * x.abs().max(1.0);
+    let mut previous = vec![central(h)?];
+    let mut best = (previous[0], f64::INFINITY);
+    for _ in 1..MAX_ROWS {
+        h /= SHRINK;
+        let mut row = vec![central(h)?];
+        let mut factor = SHRINK * SHRINK;
+        for j in 1..=previous.len() {
+            row.push((row[j - 1] * factor - previous[j - 1]) / (factor - 1.0));
+            factor *= SHRINK * SHRINK;
+            let error = (row[j] - row[j - 1]).abs().max((row[j] - previous[j - 1]).abs());
+            if error <= best.1 {
+                best = (row[j], error);
+            }
+        }
+        let last = row.len() - 1;
+        if (row[last] - previous[last - 1]).abs() >= 2.0 * best.1 {
+            break;
+        }
+        previous = row;
+    }
+
+    match best.0.is_finite() {
+        true => Ok(best),
+        false => Err(ErrorWithMessage::new_box(&format!("deriv: the function is not differentiable at {}", x))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::derivative;
+
+    #[test]
+    fn test_derivative() {
+        let (value, error) = derivative(&mut |x| Ok(x.exp()), 1.0).unwrap();
+        assert!((value - std::f64::consts::E).abs() < 1e-10 && error < 1e-8);
+        let (value, _) = derivative(&mut |x| Ok(x.powi(3)), -2.0).unwrap();
+        assert!((value - 12.0).abs() < 1e-9);
+        assert!(derivative(&mut |x| Ok(1.0 / (x - x)), 0.0).is_err());
+    }
+}
@@ -0,0 +1,264 @@
+use std::f64::consts::{PI, SQRT_2};
+
+use crate::errors::{ErrorWithMessage, SamError};
+
+use super::{
+    roots::brent,
+    special::{beta_inc, erfc, gamma_p, gamma_q, ln_beta, ln_gamma},
+};
+
+/// A probability distribution with its parameters. Discrete distributions
+/// have a probability mass function in place of a density.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Distribution {
+    Normal { mean: f64, std_dev: f64 },
+    Binomial { trials: f64, p: f64 },
+    Poisson { rate: f64 },
+    Exponential { rate: f64 },
+    StudentT { dof: f64 },
+    ChiSquared { dof: f64 },
+    F { dof1: f64, dof2: f64 },
+}
+
+impl Distribution {
+    /// The density at `x`, or the probability of `x` for a discrete
+    /// distribution.
+    pub fn pdf(&self, x: f64) -> Result<f64, SamError> {
+        self.check()?;
+        let density = match *self {
+            Distribution::Normal { mean, std_dev } => {
+                let z = (x - mean) / std_dev;
+                (-0.5 * z * z).exp() / (std_dev * (2.0 * PI).sqrt())
+            }
+            Distribution::Binomial { trials, p } => {
+                if x < 0.0 || x > trials || x.fract() != 0.0 {
+                    return Ok(0.0);
+                }
+                match p {
+                    _ if p == 0.0 => (x == 0.0) as u8 as f64,
+                    _ if p == 1.0 => (x == trials) as u8 as f64,
+                    _ => (ln_choose(trials, x) + x * p.ln() + (trials - x) * (1.0 - p).ln()).exp(),
+                }
+            }
+            Distribution::Poisson { rate } => match x < 0.0 || x.fract() != 0.0 {
+                true => 0.0,
+                false => (x * rate.ln() - rate - ln_gamma(x + 1.0)).exp(),
+            },
+            Distribution::Exponential { rate } => match x < 0.0 {
+                true => 0.0,
+                false => rate * (-rate * x).exp(),
+            },
+            Distribution::StudentT { dof } => {
+                let log_scale = ln_gamma((dof + 1.0) / 2.0) - ln_gamma(dof / 2.0) - 0.5 * (dof * PI).ln();
+                (log_scale - (dof + 1.0) / 2.0 * (x * x / dof).ln_1p()).exp()
+            }
+            Distribution::ChiSquared { dof } => {
+                let k = dof / 2.0;
+                match x {
+                    _ if x < 0.0 => 0.0,
+                    _ if x == 0.0 => density_at_zero(k, 0.5),
+                    _ => ((k - 1.0) * x.ln() - x / 2.0 - k * 2_f64.ln() - ln_gamma(k)).exp(),
+                }
+            }
+            Distribution::F { dof1, dof2 } => {
+                let (a, b) = (dof1 / 2.0, dof2 / 2.0);
+                match x {
+                    _ if x < 0.0 => 0.0,
+                    _ if x == 0.0 => density_at_zero(a, (-ln_beta(a, b) + a * (dof1 / dof2).ln()).exp()),
+                    _ => (a * (dof1 * x).ln() + b * dof2.ln() - (a + b) * (dof1 * x + dof2).ln()
+                        - x.ln()
+                        - ln_beta(a, b))
+                    .exp(),
+                }
+            }
+        };
+        Ok(density)
+    }
+
+    /// The probability of a value at most `x`.
+    pub fn cdf(&self, x: f64) -> Result<f64, SamError> {
+        self.check()?;
+        let probability = match *self {
+            Distribution::Normal { mean, std_dev } => 0.5 * erfc(-(x - mean) / (std_dev * SQRT_2)),
+            Distribution::Binomial { trials, p } => match x.floor() {
+                k if k < 0.0 => 0.0,
+                k if k >= trials => 1.0,
+                k => beta_inc(trials - k, k + 1.0, 1.0 - p)?,
+            },
+            Distribution::Poisson { rate } => match x.floor() {
+                k if k < 0.0 => 0.0,
+                k => gamma_q(k + 1.0, rate)?,
+            },
+            Distribution::Exponential { rate } => match x < 0.0 {
+                true => 0.0,
+                false => -(-rate * x).exp_m1(),
+            },
+            Distribution::StudentT { dof } => {
+                if x.is_infinite() {
+                    return Ok((x > 0.0) as u8 as f64);
+                }
+                let tail = 0.5 * beta_inc(dof / 2.0, 0.5, dof / (dof + x * x))?;
+                match x > 0.0 {
+                    true => 1.0 - tail,
+                    false => tail,
+                }
+            }
+            Distribution::ChiSquared { dof } => match x <= 0.0 {
+                true => 0.0,
+                false => gamma_p(dof / 2.0, x.min(f64::MAX) / 2.0)?,
+            },
+            Distribution::F { dof1, dof2 } => match x <= 0.0 {
+                true => 0.0,
+                false if x.is_infinite() => 1.0,
+                false => beta_inc(dof1 / 2.0, dof2 / 2.0, dof1 * x / (dof1 * x + dof2))?,
+            },
+        };
+        Ok(probability)
+    }
+
+    /// The smallest `x` whose cdf reaches `q`, for `q` between 0 and 1.
+    pub fn inverse_cdf(&self, q: f64) -> Result<f64, SamError> {
+        self.check()?;
+        if !(0.0..=1.0).contains(&q) {
+            return Err(ErrorWithMessage::new_box(&format!("probability {} is not between 0 and 1", q)));
+        }
+        match *self {
+            Distribution::Normal { mean, std_dev } => Ok(mean + std_dev * invert_symmetric(self.standardized(), q)?),
+            Distribution::StudentT { .. } => invert_symmetric(*self, q),
+            Distribution::Exponential { rate } => Ok(-(-q).ln_1p() / rate),
+            Distribution::ChiSquared { .. } | Distribution::F { .. } => invert_positive(*self, q),
+            Distribution::Binomial { trials, .. } => invert_discrete(*self, q, trials),
+            Distribution::Poisson { .. } => invert_discrete(*self, q, f64::INFINITY),
+        }
+    }
+
+    fn standardized(&self) -> Distribution {
+        match self {
+            Distribution::Normal { .. } => Distribution::Normal { mean: 0.0, std_dev: 1.0 },
+            _ => *self,
+        }
+    }
+
+    fn check(&self) -> Result<(), SamError> {
+        let (valid, requirement) = match *self {
+            Distribution::Normal { std_dev, .. } => (std_dev > 0.0, "the standard deviation must be positive"),
+            Distribution::Binomial { trials, p } => (
+                trials >= 0.0 && trials.fract() == 0.0 && (0.0..=1.0).contains(&p),
+                "binomial needs a whole number of trials and p between 0 and 1",
+            ),
+            Distribution::Poisson { rate } | Distribution::Exponential { rate } => (rate > 0.0, "the rate must be positive"),
+            Distribution::StudentT { dof } | Distribution::ChiSquared { dof } => {
+                (dof > 0.0, "the degrees of freedom must be positive")
+            }
+            Distribution::F { dof1, dof2 } => (dof1 > 0.0 && dof2 > 0.0, "the degrees of freedom must be positive"),
+        };
+        match valid {
+            true => Ok(()),
+            false => Err(ErrorWithMessage::new_box(requirement)),
+        }
+    }
+}
+
+fn ln_choose(n: f64, k: f64) -> f64 {
+    ln_gamma(n + 1.0) - ln_gamma(k + 1.0) - ln_gamma(n - k + 1.0)
+}
+
+/// The limit at zero of a density that behaves like `scale * x^(shape - 1)`.
+fn density_at_zero(shape: f64, scale: f64) -> f64 {
+    match shape {
+        _ if shape < 1.0 => f64::INFINITY,
+        _ if shape == 1.0 => scale,
+        _ => 0.0,
+    }
+}
+
+/// Inverts a cdf that is symmetric about zero, solving in the lower tail
+/// where the probabilities are most precise.
+fn invert_symmetric(distribution: Distribution, q: f64) -> Result<f64, SamError> {
+    if q > 0.5 {
+        return Ok(-invert_symmetric(distribution, 1.0 - q)?);
+    }
+    if q == 0.0 {
+        return Ok(f64::NEG_INFINITY);
+    }
+    let mut lower = -1.0;
+    while distribution.cdf(lower)? > q {
+        lower *= 2.0;
+    }
+    brent(&mut |x| Ok(distribution.cdf(x)? - q), lower, 0.0, 0.0)
+}
+
+fn invert_positive(distribution: Distribution, q: f64) -> Result<f64, SamError> {
+    if q == 0.0 {
+        return Ok(0.0);
+    }
+    if q == 1.0 {
+        return Ok(f64::INFINITY);
+    }
+    let mut upper = 1.0;
+    while distribution.cdf(upper)? < q {
+        upper *= 2.0;
+    }
+    brent(&mut |x| Ok(distribution.cdf(x)? - q), 0.0, upper, 0.0)
+}
+
+/// The smallest whole number up to `max` whose cdf reaches `q`, found by
+/// doubling and then bisecting. The cdf rounds to 1 before `max`, so `q = 1`
+/// is `max` itself.
+fn invert_discrete(distribution: Distribution, q: f64, max: f64) -> Result<f64, SamError> {
+    if q == 1.0 {
+        return Ok(max);
+    }
+    let mut upper = 1_f64.min(max);
+    while distribution.cdf(upper)? < q {
+        if upper >= max || upper.is_infinite() {
+            return Ok(max);
+        }
+        upper = (2.0 * upper).min(max);
+    }
+    let mut lower = -1.0;
+    while upper - lower > 1.0 {
+        let middle = ((lower + upper) / 2.0).floor();
+        match distribution.cdf(middle)? < q {
+            true => lower = middle,
+            false => upper = middle,
+        }
+    }
+    Ok(upper)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Distribution;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-9 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn test_distributions() {
+        let normal = Distribution::Normal { mean: 0.0, std_dev: 1.0 };
+        assert!(close(normal.cdf(1.96).unwrap(), 0.9750021048517795));
+        assert!(close(normal.inverse_cdf(0.975).unwrap(), 1.959963984540054));
+        assert!(close(normal.inverse_cdf(1e-10).unwrap(), -6.361340902404056));
+
+        let t = Distribution::StudentT { dof: 10.0 };
+        assert!(close(t.inverse_cdf(0.975).unwrap(), 2.228138851986274));
+        let chi = Distribution::ChiSquared { dof: 3.0 };
+        assert!(close(chi.inverse_cdf(0.95).unwrap(), 7.814727903251178));
+        let f = Distribution::F { dof1: 5.0, dof2: 10.0 };
+        assert!(close(f.cdf(f.inverse_cdf(0.95).unwrap()).unwrap(), 0.95));
+
+        let binomial = Distribution::Binomial { trials: 10.0, p: 0.3 };
+        assert!(close(binomial.pdf(3.0).unwrap(), 0.2668279320));
+        assert!(close(binomial.cdf(3.0).unwrap(), 0.6496107184));
+        assert!(binomial.inverse_cdf(0.6).unwrap() == 3.0);
+        let poisson = Distribution::Poisson { rate: 4.0 };
+        assert!(close(poisson.cdf(2.0).unwrap(), 13.0 * (-4_f64).exp()));
+        assert!(poisson.inverse_cdf(0.5).unwrap() == 4.0);
+        assert!(poisson.inverse_cdf(1.0).unwrap() == f64::INFINITY);
+
+        assert!(Distribution::Exponential { rate: -1.0 }.pdf(1.0).is_err());
+        assert!(normal.inverse_cdf(1.5).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use crate::errors::{ErrorWithMessage, SamError};
+
+const MAX_ITERATIONS: usize = 1000;
+
+/// Gives the residuals at the parameters `p` and their Jacobian, one row
+/// per residual.
+pub type Residuals<'a> = dyn FnMut(&[f64]) -> Result<(Vec<f64>, Vec<Vec<f64>>), SamError> + 'a;
+
+/// Finds the parameters minimising the sum of squared residuals, starting
+/// from `p0`, by Levenberg–Marquardt: Gauss–Newton steps damped towards
+/// gradient descent whenever they fail to reduce the sum.
+pub fn levenberg_marquardt(residuals: &mut Residuals, p0: &[f64], tolerance: f64) -> Result<Vec<f64>, SamError> {
+    let n = p0.len();
+    let mut p = p0.to_vec();
+    let (mut r, mut jacobian) = residuals(&p)?;
+    let mut cost = sum_of_squares(&r);
+    let mut damping = 1e-3;
+
+    for _ in 0..MAX_ITERATIONS {
+        // The normal equations (JᵀJ + λ diag(JᵀJ)) δ = -Jᵀr.
+        let normal: Vec<Vec<f64>> = (0..n)
+            .map(|i| (0..n).map(|j| jacobian.iter().map(|row| row[i] * row[j]).sum()).collect())
+            .collect();
+        let gradient: Vec<f64> = (0..n).map(|i| jacobian.iter().zip(&r).map(|(row, r)| row[i] * r).sum()).collect();
+        if gradient.iter().all(|g| g.abs() <= tolerance * cost.max(tolerance)) {
+            return Ok(p);
+        }
+
+        let mut damped = normal.clone();
+        for (i, row) in damped.iter_mut().enumerate() {
+            row[i] += damping * normal[i][i].max(f64::EPSILON);
+        }
+        let step = match solve(damped, gradient.iter().map(|g| -g).collect()) {
+            Some(step) => step,
+            None => {
+                damping *= 10.0;
+                continue;
+            }
+        };
+        let next: Vec<f64> = p.iter().zip(&step).map(|(p, d)| p + d).collect();
+        let (next_r, next_jacobian) = residuals(&next)?;
+        let next_cost = sum_of_squares(&next_r);
+
+        // Near the minimum the sum only changes by rounding, so it cannot
+        // tell whether a step helps; the step is still the best estimate.
+        let predicted: f64 = -step.iter().zip(&gradient).map(|(d, g)| d * g).sum::<f64>();
+        if next_cost.is_finite() && predicted <= 16.0 * f64::EPSILON * cost {
+            return Ok(next);
+        }
+        if next_cost.is_finite() && next_cost <= cost {
+            let converged = step.iter().zip(&next).all(|(d, p)| d.abs() <= tolerance * p.abs().max(tolerance));
+            (p, r, jacobian, cost) = (next, next_r, next_jacobian, next_cost);
+            if converged {
+                return Ok(p);
+            }
+            damping = (damping / 10.0).max(1e-12);
+        } else {
+            damping *= 10.0;
+            if damping > 1e16 {
+                // Even tiny steps no longer help, so this is as close as we get.
+                return Ok(p);
+            }
+        }
+    }
+    Err(ErrorWithMessage::new_box("fit did not converge"))
+}
+
+fn sum_of_squares(r: &[f64]) -> f64 {
+    r.iter().map(|r| r * r).sum()
+}
+
+/// Solves `a x = b` by Gaussian elimination with partial pivoting, or gives
+/// `None` if `a` is singular.
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for column in 0..n {
+        let pivot = (column..n).max_by(|i, j| a[*i][column].abs().total_cmp(&a[*j][column].abs()))?;
+        if a[pivot][column] == 0.0 || !a[pivot][column].is_finite() {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+        let (pivot_row, b_pivot) = (a[column].clone(), b[column]);
+        for (row, b_row) in a.iter_mut().zip(b.iter_mut()).skip(column + 1) {
+            let factor = row[column] / pivot_row[column];
+            for (entry, pivot_entry) in row.iter_mut().zip(&pivot_row).skip(column) {
+                *entry -= factor * pivot_entry;
+            }
+            *b_row -= factor * b_pivot;
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let known: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - known) / a[row][row];
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod test {
+    use super::levenberg_marquardt;
+
+    #[test]
+    fn test_exponential_fit() {
+        let xs = [0.0, 1.0, 2.0, 3.0, 4.0];
+        let ys: Vec<f64> = xs.iter().map(|x: &f64| 2.5 * (-0.7 * x).exp()).collect();
+        let mut residuals = |p: &[f64]| {
+            let r = xs.iter().zip(&ys).map(|(x, y)| p[0] * (p[1] * x).exp() - y).collect();
+            let jacobian = xs.iter().map(|x| vec![(p[1] * x).exp(), p[0] * x * (p[1] * x).exp()]).collect();
+            Ok((r, jacobian))
+        };
+        let p = levenberg_marquardt(&mut residuals, &[1.0, -0.1], 1e-12).unwrap();
+        assert!((p[0] - 2.5).abs() < 1e-8 && (p[1] + 0.7).abs() < 1e-8);
+    }
+}
@@ -0,0 +1,400 @@
+use crate::errors::{ErrorWithMessage, SamError};
+
+/// A dense matrix as a list of rows.
+pub type Matrix = Vec<Vec<f64>>;
+
+const MAX_ITERATIONS: usize = 10_000;
+
+/// The factors of `P A = L U`, with `L` unit lower triangular and `P` a
+/// permutation.
+pub struct Lu {
+    pub lower: Matrix,
+    pub upper: Matrix,
+    pub permutation: Matrix,
+    /// The determinant of `P`.
+    sign: f64,
+}
+
+pub fn identity(n: usize) -> Matrix {
+    (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect()
+}
+
+pub fn transpose(a: &Matrix) -> Matrix {
+    let columns = a.first().map_or(0, Vec::len);
+    (0..columns).map(|j| a.iter().map(|row| row[j]).collect()).collect()
+}
+
+pub fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let b = transpose(b);
+    a.iter().map(|row| b.iter().map(|column| dot(row, column)).collect()).collect()
+}
+
+pub fn trace(a: &Matrix) -> Result<f64, SamError> {
+    check_square(a)?;
+    Ok((0..a.len()).map(|i| a[i][i]).sum())
+}
+
+/// LU decomposition with partial pivoting. Singular matrices still factor,
+/// with a zero on the diagonal of `U`.
+pub fn lu(a: &Matrix) -> Result<Lu, SamError> {
+    let n = check_square(a)?;
+    let mut upper = a.clone();
+    let mut lower = identity(n);
+    let mut order: Vec<usize> = (0..n).collect();
+    let mut sign = 1.0;
+
+    for column in 0..n {
+        let pivot = (column..n).max_by(|i, j| upper[*i][column].abs().total_cmp(&upper[*j][column].abs())).unwrap();
+        if pivot != column {
+            upper.swap(column, pivot);
+            order.swap(column, pivot);
+            let (top, bottom) = lower.split_at_mut(pivot);
+            top[column][..column].swap_with_slice(&mut bottom[0][..column]);
+            sign = -sign;
+        }
+        if upper[column][column] == 0.0 {
+            continue;
+        }
+        let pivot_row = upper[column].clone();
+        for row in column + 1..n {
+            let factor = upper[row][column] / pivot_row[column];
+            lower[row][column] = factor;
+            for (entry, pivot_entry) in upper[row].iter_mut().zip(&pivot_row).skip(column) {
+                *entry -= factor * pivot_entry;
+            }
+        }
+    }
+
+    let permutation = order.iter().map(|i| (0..n).map(|j| if j == *i { 1.0 } else { 0.0 }).collect()).collect();
+    Ok(Lu { lower, upper, permutation, sign })
+}
+
+pub fn det(a: &Matrix) -> Result<f64, SamError> {
+    let lu = lu(a)?;
+    Ok(lu.sign * (0..a.len()).map(|i| lu.upper[i][i]).product::<f64>())
+}
+
+/// Solves `A X = B`. Square systems are solved exactly and overdetermined
+/// ones in the least squares sense.
+pub fn solve(a: &Matrix, b: &Matrix) -> Result<Matrix, SamError> {
+    let (m, n) = shape(a)?;
+    if b.len() != m {
+        return Err(ErrorWithMessage::new_box(&format!(
+            "cannot solve a system with {} equations and {} right-hand sides",
+            m,
+            b.len()
+        )));
+    }
+    if m < n {
+        return Err(ErrorWithMessage::new_box("the system is underdetermined"));
+    }
+    if m > n {
+        return least_squares(a, b);
+    }
+
+    let lu = lu(a)?;
+    let tolerance = n as f64 * f64::EPSILON * max_abs(a);
+    if (0..n).any(|i| lu.upper[i][i].abs() <= tolerance) {
+        return Err(ErrorWithMessage::new_box("matrix is singular"));
+    }
+    let b = multiply(&lu.permutation, b);
+    let columns = b[0].len();
+    let mut x = vec![vec![0.0; columns]; n];
+    for column in 0..columns {
+        // Forward substitution with L, then back substitution with U.
+        let mut y = vec![0.0; n];
+        for i in 0..n {
+            y[i] = b[i][column] - (0..i).map(|k| lu.lower[i][k] * y[k]).sum::<f64>();
+        }
+        for i in (0..n).rev() {
+            let known: f64 = (i + 1..n).map(|k| lu.upper[i][k] * x[k][column]).sum();
+            x[i][column] = (y[i] - known) / lu.upper[i][i];
+        }
+    }
+    Ok(x)
+}
+
+pub fn inverse(a: &Matrix) -> Result<Matrix, SamError> {
+    let n = check_square(a)?;
+    solve(a, &identity(n))
+}
+
+/// QR decomposition by Householder reflections, with `Q` square.
+pub fn qr(a: &Matrix) -> Result<(Matrix, Matrix), SamError> {
+    let (m, n) = shape(a)?;
+    let mut r = a.clone();
+    let mut q = identity(m);
+    for k in 0..n.min(m.saturating_sub(1)) {
+        let x: Vec<f64> = (k..m).map(|i| r[i][k]).collect();
+        let norm = dot(&x, &x).sqrt();
+        if norm == 0.0 {
+            continue;
+        }
+        let mut v = x;
+        v[0] += norm.copysign(v[0]);
+        let length = dot(&v, &v);
+
+        // R = H R and Q = Q H, with H = I - 2 v vᵀ / vᵀv.
+        let projections: Vec<f64> = (0..n).map(|j| 2.0 * (k..m).map(|i| v[i - k] * r[i][j]).sum::<f64>() / length).collect();
+        for (i, row) in r.iter_mut().enumerate().skip(k) {
+            for (entry, projection) in row.iter_mut().zip(&projections) {
+                *entry -= projection * v[i - k];
+            }
+        }
+        for row in q.iter_mut() {
+            let projection = 2.0 * (k..m).map(|i| row[i] * v[i - k]).sum::<f64>() / length;
+            for i in k..m {
+                row[i] -= projection * v[i - k];
+            }
+        }
+        for row in r.iter_mut().skip(k + 1) {
+            row[k] = 0.0;
+        }
+    }
+    Ok((q, r))
+}
+
+/// The lower triangular `L` with `A = L Lᵀ`, for symmetric positive definite
+/// `A`.
+pub fn cholesky(a: &Matrix) -> Result<Matrix, SamError> {
+    let n = check_square(a)?;
+    if !is_symmetric(a) {
+        return Err(ErrorWithMessage::new_box("matrix is not symmetric"));
+    }
+    let mut lower = vec![vec![0.0; n]; n];
+    for j in 0..n {
+        let diagonal = a[j][j] - (0..j).map(|k| lower[j][k] * lower[j][k]).sum::<f64>();
+        if diagonal <= 0.0 {
+            return Err(ErrorWithMessage::new_box("matrix is not positive definite"));
+        }
+        lower[j][j] = diagonal.sqrt();
+        for i in j + 1..n {
+            lower[i][j] = (a[i][j] - (0..j).map(|k| lower[i][k] * lower[j][k]).sum::<f64>()) / lower[j][j];
+        }
+    }
+    Ok(lower)
+}
+
+/// The eigenvalues `(re, im)` of a square matrix by the shifted QR
+/// algorithm, sorted by real part.
+pub fn eigenvalues(a: &Matrix) -> Result<Vec<(f64, f64)>, SamError> {
+    let mut n = check_square(a)?;
+    let mut h = a.clone();
+    let mut values = vec![];
+    let small = f64::EPSILON * max_abs(a).max(f64::MIN_POSITIVE);
+    let mut iterations = 0;
+
+    while n > 0 {
+        if n == 1 {
+            values.push((h[0][0], 0.0));
+            break;
+        }
+        // The matrix is not reduced to Hessenberg form, so a block only
+        // splits off once everything to its left in its rows is negligible.
+        let negligible = |h: &Matrix, rows: std::ops::Range<usize>, columns: usize| {
+            rows.clone().all(|i| h[i][..columns].iter().all(|x| x.abs() <= f64::EPSILON * h[i][i].abs() + small))
+        };
+        if negligible(&h, n - 1..n, n - 1) {
+            values.push((h[n - 1][n - 1], 0.0));
+            n -= 1;
+            iterations = 0;
+            continue;
+        }
+        if n == 2 || negligible(&h, n - 2..n, n - 2) {
+            let (first, second) = block_eigenvalues(&h, n - 2);
+            values.push(first);
+            values.push(second);
+            n -= 2;
+            iterations = 0;
+            continue;
+        }
+        iterations += 1;
+        if iterations > MAX_ITERATIONS {
+            return Err(ErrorWithMessage::new_box("eig did not converge"));
+        }
+
+        // Shift by the eigenvalue of the trailing block nearest its corner,
+        // with an occasional exceptional shift to break cycles.
+        let corner = h[n - 1][n - 1];
+        let shift = match block_eigenvalues(&h, n - 2) {
+            _ if iterations % 11 == 0 => corner + h[n - 1][n - 2].abs(),
+            ((x, 0.0), (y, 0.0)) if (x - corner).abs() < (y - corner).abs() => x,
+            ((_, 0.0), (y, 0.0)) => y,
+            _ => corner,
+        };
+        let active: Matrix = (0..n).map(|i| (0..n).map(|j| h[i][j] - if i == j { shift } else { 0.0 }).collect()).collect();
+        let (q, r) = qr(&active)?;
+        let next = multiply(&r, &q);
+        for i in 0..n {
+            for j in 0..n {
+                h[i][j] = next[i][j] + if i == j { shift } else { 0.0 };
+            }
+        }
+    }
+
+    values.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    Ok(values)
+}
+
+/// The eigenvalues of the 2×2 block of `h` whose top left corner is at
+/// `(k, k)`.
+fn block_eigenvalues(h: &Matrix, k: usize) -> ((f64, f64), (f64, f64)) {
+    let (a, b, c, d) = (h[k][k], h[k][k + 1], h[k + 1][k], h[k + 1][k + 1]);
+    let half_trace = (a + d) / 2.0;
+    let discriminant = ((a - d) / 2.0).powi(2) + b * c;
+    match discriminant >= 0.0 {
+        true => {
+            let root = discriminant.sqrt();
+            ((half_trace - root, 0.0), (half_trace + root, 0.0))
+        }
+        false => {
+            let root = (-discriminant).sqrt();
+            ((half_trace, -root), (half_trace, root))
+        }
+    }
+}
+
+/// The singular value decomposition `A = U diag(s) Vᵀ`, with the singular
+/// values in decreasing order, by one-sided Jacobi rotations.
+pub fn svd(a: &Matrix) -> Result<(Matrix, Vec<f64>, Matrix), SamError> {
+    let (m, n) = shape(a)?;
+    if m < n {
+        let (u, s, v) = svd(&transpose(a))?;
+        return Ok((v, s, u));
+    }
+
+    let mut u = a.clone();
+    let mut v = identity(n);
+    for sweep in 0.. {
+        if sweep == MAX_ITERATIONS {
+            return Err(ErrorWithMessage::new_box("svd did not converge"));
+        }
+        let mut rotated = false;
+        for p in 0..n {
+            for q in p + 1..n {
+                let (mut alpha, mut beta, mut gamma) = (0.0, 0.0, 0.0);
+                for row in &u {
+                    alpha += row[p] * row[p];
+                    beta += row[q] * row[q];
+                    gamma += row[p] * row[q];
+                }
+                if gamma.abs() <= f64::EPSILON * (alpha * beta).sqrt() || gamma == 0.0 {
+                    continue;
+                }
+                rotated = true;
+                let zeta = (beta - alpha) / (2.0 * gamma);
+                let t = 1.0_f64.copysign(zeta) / (zeta.abs() + (1.0 + zeta * zeta).sqrt());
+                let c = 1.0 / (1.0 + t * t).sqrt();
+                let s = c * t;
+                for row in u.iter_mut().chain(v.iter_mut()) {
+                    let (x, y) = (row[p], row[q]);
+                    row[p] = c * x - s * y;
+                    row[q] = s * x + c * y;
+                }
+            }
+        }
+        if !rotated {
+            break;
+        }
+    }
+
+    let mut singular: Vec<(f64, usize)> = (0..n).map(|j| (u.iter().map(|row| row[j] * row[j]).sum::<f64>().sqrt(), j)).collect();
+    singular.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let u = (0..m)
+        .map(|i| singular.iter().map(|(s, j)| if *s == 0.0 { 0.0 } else { u[i][*j] / s }).collect())
+        .collect();
+    let v = (0..n).map(|i| singular.iter().map(|(_, j)| v[i][*j]).collect()).collect();
+    Ok((u, singular.into_iter().map(|(s, _)| s).collect(), v))
+}
+
+/// The number of singular values that are not negligible.
+pub fn rank(a: &Matrix) -> Result<usize, SamError> {
+    let (m, n) = shape(a)?;
+    let (_, singular, _) = svd(a)?;
+    let tolerance = m.max(n) as f64 * f64::EPSILON * singular.first().copied().unwrap_or(0.0);
+    Ok(singular.iter().filter(|s| **s > tolerance).count())
+}
+
+fn least_squares(a: &Matrix, b: &Matrix) -> Result<Matrix, SamError> {
+    let n = a[0].len();
+    let (q, r) = qr(a)?;
+    let tolerance = a.len() as f64 * f64::EPSILON * max_abs(a);
+    if (0..n).any(|i| r[i][i].abs() <= tolerance) {
+        return Err(ErrorWithMessage::new_box("matrix is rank deficient"));
+    }
+    let qtb = multiply(&transpose(&q), b);
+    let columns = b[0].len();
+    let mut x = vec![vec![0.0; columns]; n];
+    for column in 0..columns {
+        for i in (0..n).rev() {
+            let known: f64 = (i + 1..n).map(|k| r[i][k] * x[k][column]).sum();
+            x[i][column] = (qtb[i][column] - known) / r[i][i];
+        }
+    }
+    Ok(x)
+}
+
+fn shape(a: &Matrix) -> Result<(usize, usize), SamError> {
+    let columns = a.first().map_or(0, Vec::len);
+    if columns == 0 || a.iter().any(|row| row.len() != columns) {
+        return Err(ErrorWithMessage::new_box("expected a matrix, a list of rows of equal length"));
+    }
+    Ok((a.len(), columns))
+}
+
+fn check_square(a: &Matrix) -> Result<usize, SamError> {
+    match shape(a)? {
+        (m, n) if m == n => Ok(n),
+        (m, n) => Err(ErrorWithMessage::new_box(&format!("expected a square matrix but found a {}×{} one", m, n))),
+    }
+}
+
+fn is_symmetric(a: &Matrix) -> bool {
+    let tolerance = 1e-12 * max_abs(a);
+    (0..a.len()).all(|i| (0..i).all(|j| (a[i][j] - a[j][i]).abs() <= tolerance))
+}
+
+fn max_abs(a: &Matrix) -> f64 {
+    a.iter().flatten().fold(0.0, |m, x| m.max(x.abs()))
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{cholesky, det, eigenvalues, inverse, multiply, qr, rank, solve, svd, transpose, Matrix};
+
+    fn close(a: &Matrix, b: &Matrix) -> bool {
+        a.iter().flatten().zip(b.iter().flatten()).all(|(x, y)| (x - y).abs() < 1e-10)
+    }
+
+    #[test]
+    fn test_linear_algebra() {
+        let a = vec![vec![4.0, 3.0, 2.0], vec![2.0, 1.0, 3.0], vec![3.0, 2.0, 1.0]];
+        assert!((det(&a).unwrap() - 3.0).abs() < 1e-12);
+        assert!(close(&multiply(&a, &inverse(&a).unwrap()), &super::identity(3)));
+        let x = solve(&a, &vec![vec![1.0], vec![2.0], vec![3.0]]).unwrap();
+        assert!(close(&multiply(&a, &x), &vec![vec![1.0], vec![2.0], vec![3.0]]));
+        assert!(inverse(&vec![vec![1.0, 2.0], vec![2.0, 4.0]]).is_err());
+
+        let (q, r) = qr(&a).unwrap();
+        assert!(close(&multiply(&q, &r), &a));
+        let spd = vec![vec![4.0, 2.0], vec![2.0, 3.0]];
+        let l = cholesky(&spd).unwrap();
+        assert!(close(&multiply(&l, &transpose(&l)), &spd));
+
+        let values = eigenvalues(&vec![vec![2.0, 1.0], vec![1.0, 2.0]]).unwrap();
+        assert!(close(&vec![vec![values[0].0, values[1].0]], &vec![vec![1.0, 3.0]]));
+        let values = eigenvalues(&vec![vec![0.0, -1.0], vec![1.0, 0.0]]).unwrap();
+        assert!(values == [(0.0, -1.0), (0.0, 1.0)]);
+
+        let b = vec![vec![3.0, 2.0, 2.0], vec![2.0, 3.0, -2.0]];
+        let (u, s, v) = svd(&b).unwrap();
+        assert!((s[0] - 5.0).abs() < 1e-12 && (s[1] - 3.0).abs() < 1e-12);
+        let sigma: Matrix = (0..2).map(|i| (0..2).map(|j| if i == j { s[i] } else { 0.0 }).collect()).collect();
+        assert!(close(&multiply(&multiply(&u, &sigma), &transpose(&v)), &b));
+        assert!(rank(&vec![vec![1.0, 2.0], vec![2.0, 4.0]]).unwrap() == 1);
+    }
+}
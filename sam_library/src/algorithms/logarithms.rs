@@ -31,11 +31,9 @@ pub fn pow(x: Real, y: Real) -> Real {
             match y {
                 Real::Float(y) =>  Real::Float((x as f64).powf(y)), 
                 Real::Int(y) => {
-                    if let Ok(y) = y.to_string().parse::<u32>() {
-                        Real::Int(x.pow(y))
-                    }
-                    else {
-                        Real::Float((x as f64).powf(y as f64))
+                    match u32::try_from(y).ok().and_then(|y| x.checked_pow(y)) {
+                        Some(power) => Real::Int(power),
+                        None => Real::Float((x as f64).powf(y as f64)),
                     }
                 }
             }
@@ -52,4 +50,32 @@ pub fn ln(x: Real) -> Real {
             Real::Float((x as f64).ln())
         }
     }
-}
\ No newline at end of file
+}
+
+pub fn exp(x: Real) -> Real {
+    Real::Float(Into::<f64>::into(x).exp())
+}
+
+pub fn log2(x: Real) -> Real {
+    Real::Float(Into::<f64>::into(x).log2())
+}
+
+/// The real cube root, which is negative for negative `x`.
+pub fn cbrt(x: Real) -> Real {
+    Real::Float(Into::<f64>::into(x).cbrt())
+}
+
+/// The real `n`th root. Odd roots of negative numbers are negative, where
+/// `x^(1/n)` would not be a real number.
+pub fn nthroot(x: Real, n: Real) -> Real {
+    let (x, n): (f64, f64) = (x.into(), n.into());
+    match x < 0.0 && n.fract() == 0.0 && n % 2.0 != 0.0 {
+        true => Real::Float(-(-x).powf(1.0 / n)),
+        false => Real::Float(x.powf(1.0 / n)),
+    }
+}
+
+/// `√(x² + y²)` without overflowing for large `x` or `y`.
+pub fn hypot(x: Real, y: Real) -> Real {
+    Real::Float(Into::<f64>::into(x).hypot(y.into()))
+}
@@ -1,2 +1,19 @@
 pub mod trigonometry;
-pub mod logarithms;
\ No newline at end of file
+pub mod logarithms;
+pub mod roots;
+pub mod quadrature;
+pub mod differentiation;
+pub mod ode;
+pub mod optimization;
+pub mod least_squares;
+pub mod linalg;
+pub mod statistics;
+pub mod special;
+pub mod distributions;
+pub mod random;
+pub mod number_theory;
+pub mod big_integer;
+pub mod combinatorics;
+pub mod rounding;
+pub mod series;
+pub mod polynomial;
\ No newline at end of file
@@ -0,0 +1,242 @@
+use crate::errors::{ErrorWithMessage, SamError};
+
+/// Witnesses that make Miller-Rabin exact for every 64-bit number.
+const WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+pub fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// `⌊√n⌋`, exactly.
+pub fn isqrt(n: u64) -> u64 {
+    // The float estimate is off by at most one either way.
+    let mut root = (n as f64).sqrt() as u64;
+    while root.checked_mul(root).is_none_or(|square| square > n) {
+        root -= 1;
+    }
+    while (root + 1).checked_mul(root + 1).is_some_and(|square| square <= n) {
+        root += 1;
+    }
+    root
+}
+
+pub fn mod_mul(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+/// `base^exponent mod m` by repeated squaring.
+pub fn mod_pow(base: u64, mut exponent: u64, m: u64) -> u64 {
+    let mut result = 1 % m;
+    let mut base = base % m;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mod_mul(result, base, m);
+        }
+        base = mod_mul(base, base, m);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// The `x` in `[0, m)` with `a·x ≡ 1 (mod m)`, by the extended Euclidean
+/// algorithm.
+pub fn mod_inv(a: i64, m: i64) -> Result<i64, SamError> {
+    if m <= 0 {
+        return Err(ErrorWithMessage::new_box("the modulus must be positive"));
+    }
+    let (mut r0, mut r1) = ((a as i128).rem_euclid(m as i128), m as i128);
+    let (mut s0, mut s1) = (1_i128, 0_i128);
+    while r1 != 0 {
+        let q = r0 / r1;
+        (r0, r1) = (r1, r0 - q * r1);
+        (s0, s1) = (s1, s0 - q * s1);
+    }
+    match r0 {
+        1 => Ok(s0.rem_euclid(m as i128) as i64),
+        _ if m == 1 => Ok(0),
+        _ => Err(ErrorWithMessage::new_box(&format!("{} has no inverse modulo {}", a, m))),
+    }
+}
+
+/// Deterministic Miller-Rabin.
+pub fn is_prime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for p in WITNESSES {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+    let shift = (n - 1).trailing_zeros();
+    let odd = (n - 1) >> shift;
+    WITNESSES.iter().all(|&witness| {
+        let mut x = mod_pow(witness, odd, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..shift {
+            x = mod_mul(x, x, n);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// The smallest prime greater than `n`.
+pub fn next_prime(n: u64) -> Result<u64, SamError> {
+    let mut candidate = n.checked_add(1).ok_or_else(overflow)?;
+    while !is_prime(candidate) {
+        candidate = candidate.checked_add(1).ok_or_else(overflow)?;
+    }
+    Ok(candidate)
+}
+
+/// The prime factorisation of `n` as `(prime, exponent)` pairs in increasing
+/// order. Small factors are found by trial division and the rest by
+/// Pollard's rho.
+pub fn factorize(mut n: u64) -> Vec<(u64, u32)> {
+    let mut primes = vec![];
+    for p in [2, 3, 5] {
+        while n.is_multiple_of(p) {
+            primes.push(p);
+            n /= p;
+        }
+    }
+    let mut p = 7;
+    while p < 1000 && p * p <= n {
+        while n.is_multiple_of(p) {
+            primes.push(p);
+            n /= p;
+        }
+        p += 2;
+    }
+    split(n, &mut primes);
+    primes.sort_unstable();
+
+    let mut factors: Vec<(u64, u32)> = vec![];
+    for p in primes {
+        match factors.last_mut() {
+            Some((last, count)) if *last == p => *count += 1,
+            _ => factors.push((p, 1)),
+        }
+    }
+    factors
+}
+
+/// Every divisor of `n` in increasing order.
+pub fn divisors(n: u64) -> Vec<u64> {
+    let mut divisors = vec![1];
+    for (p, count) in factorize(n) {
+        let current = divisors.clone();
+        let mut power = 1;
+        for _ in 0..count {
+            power *= p;
+            divisors.extend(current.iter().map(|d| d * power));
+        }
+    }
+    divisors.sort_unstable();
+    divisors
+}
+
+/// Euler's totient: how many numbers up to `n` are coprime to it.
+pub fn totient(n: u64) -> u64 {
+    factorize(n).iter().fold(n, |phi, (p, _)| phi / p * (p - 1))
+}
+
+fn split(n: u64, primes: &mut Vec<u64>) {
+    if n == 1 {
+        return;
+    }
+    if is_prime(n) {
+        primes.push(n);
+        return;
+    }
+    let root = isqrt(n);
+    if root * root == n {
+        split(root, primes);
+        split(root, primes);
+        return;
+    }
+    let divisor = (1..).find_map(|c| pollard_rho(n, c)).unwrap();
+    split(divisor, primes);
+    split(n / divisor, primes);
+}
+
+/// A proper divisor of the composite `n` from Pollard's rho with Brent's
+/// cycle detection and the polynomial `x² + c`, or `None` if this `c` fails.
+fn pollard_rho(n: u64, c: u64) -> Option<u64> {
+    let step = |x: u64| ((mod_mul(x, x, n) as u128 + c as u128) % n as u128) as u64;
+    let (mut x, mut y): (u64, u64) = (2, 2);
+    let mut power = 1;
+    let mut length = 1;
+    loop {
+        // Batch the gcds by multiplying the differences together.
+        let mut product = 1;
+        let saved = y;
+        for _ in 0..length.min(128) {
+            y = step(y);
+            product = mod_mul(product, x.abs_diff(y), n);
+        }
+        let mut divisor = gcd(product, n);
+        if divisor == n {
+            // Too many steps were batched, so retrace them one at a time.
+            y = saved;
+            loop {
+                y = step(y);
+                divisor = gcd(x.abs_diff(y), n);
+                if divisor != 1 {
+                    break;
+                }
+            }
+        }
+        if divisor == n {
+            return None;
+        }
+        if divisor != 1 {
+            return Some(divisor);
+        }
+        length -= length.min(128);
+        if length == 0 {
+            power *= 2;
+            length = power;
+            x = y;
+        }
+    }
+}
+
+fn overflow() -> SamError {
+    ErrorWithMessage::new_box("the result is too large")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{divisors, factorize, is_prime, isqrt, mod_inv, mod_pow, next_prime, totient};
+
+    #[test]
+    fn test_number_theory() {
+        assert!(isqrt(u64::MAX) == u32::MAX as u64);
+        assert!(isqrt(99) == 9 && isqrt(100) == 10);
+        assert!(mod_pow(4, 13, 497) == 445);
+        assert!(mod_inv(3, 11).unwrap() == 4);
+        assert!(mod_inv(-3, 11).unwrap() == 7);
+        assert!(mod_inv(2, 4).is_err());
+
+        assert!(is_prime(2) && is_prime(97) && is_prime(18446744073709551557));
+        assert!(!is_prime(1) && !is_prime(561) && !is_prime(3215031751));
+        assert!(next_prime(89).unwrap() == 97);
+        assert!(next_prime(u64::MAX - 1).is_err());
+
+        assert!(factorize(360) == vec![(2, 3), (3, 2), (5, 1)]);
+        assert!(factorize(600851475143) == vec![(71, 1), (839, 1), (1471, 1), (6857, 1)]);
+        assert!(factorize(4611686014132420609) == vec![(2147483647, 2)]);
+        assert!(factorize(1000000016000000063) == vec![(1000000007, 1), (1000000009, 1)]);
+        assert!(divisors(12) == vec![1, 2, 3, 4, 6, 12]);
+        assert!(totient(36) == 12 && totient(1) == 1);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::errors::{ErrorWithMessage, SamError};
+
+/// The tolerance `ode` uses unless given one, both absolute and relative to
+/// the size of the solution.
+pub const DEFAULT_TOLERANCE: f64 = 1e-12;
+
+const MAX_STEPS: usize = 100_000;
+
+/// The right-hand side `f(t, y)` of a system `y' = f(t, y)`.
+pub type System<'a> = dyn FnMut(f64, &[f64]) -> Result<Vec<f64>, SamError> + 'a;
+
+// The Dormand–Prince tableau. The last row of `A` doubles as the fifth
+// order weights, so the final stage is the first stage of the next step.
+const C: [f64; 7] = [0.0, 1.0 / 5.0, 3.0 / 10.0, 4.0 / 5.0, 8.0 / 9.0, 1.0, 1.0];
+const A: [[f64; 6]; 7] = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+    [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+    [19372.0 / 6561.0, -25360.0 / 2187.0, 64448.0 / 6561.0, -212.0 / 729.0, 0.0, 0.0],
+    [9017.0 / 3168.0, -355.0 / 33.0, 46732.0 / 5247.0, 49.0 / 176.0, -5103.0 / 18656.0, 0.0],
+    [35.0 / 384.0, 0.0, 500.0 / 1113.0, 125.0 / 192.0, -2187.0 / 6784.0, 11.0 / 84.0],
+];
+/// Fifth order weights minus the embedded fourth order ones.
+const ERROR_WEIGHTS: [f64; 7] = [
+    35.0 / 384.0 - 5179.0 / 57600.0,
+    0.0,
+    500.0 / 1113.0 - 7571.0 / 16695.0,
+    125.0 / 192.0 - 393.0 / 640.0,
+    -2187.0 / 6784.0 + 92097.0 / 339200.0,
+    11.0 / 84.0 - 187.0 / 2100.0,
+    -1.0 / 40.0,
+];
+
+/// Integrates `y' = f(t, y)` from `t0` to `t1`, which may be before `t0`,
+/// with the adaptive Dormand–Prince 5(4) method and returns `y(t1)`.
+pub fn dormand_prince(f: &mut System, t0: f64, y0: &[f64], t1: f64, tolerance: f64) -> Result<Vec<f64>, SamError> {
+    let mut t = t0;
+    let mut y = y0.to_vec();
+    if y.is_empty() {
+        return Err(ErrorWithMessage::new_box("ode: y0 is empty"));
+    }
+    if t0 == t1 {
+        return Ok(y);
+    }
+    // Checked on every call, before the stages index into the result.
+    let dimension = y.len();
+    let mut f = |t: f64, y: &[f64]| match f(t, y)? {
+        dy if dy.len() == dimension => Ok(dy),
+        _ => Err(ErrorWithMessage::new_box("ode: f(t, y) must have the same length as y")),
+    };
+    let direction = (t1 - t0).signum();
+    let mut h = (t1 - t0) / 100.0;
+    let mut k = vec![f(t, &y)?];
+
+    for _ in 0..MAX_STEPS {
+        if (t1 - t - h) * direction < 0.0 {
+            h = t1 - t;
+        }
+        k.truncate(1);
+        let mut stage = vec![];
+        for i in 1..7 {
+            stage = (0..y.len())
+                .map(|n| y[n] + h * (0..i).map(|j| A[i][j] * k[j][n]).sum::<f64>())
+                .collect();
+            k.push(f(t + C[i] * h, &stage)?);
+        }
+
+        // The root mean square of the error relative to the tolerance.
+        let error = (0..y.len())
+            .map(|n| {
+                let estimate = h * (0..7).map(|j| ERROR_WEIGHTS[j] * k[j][n]).sum::<f64>();
+                let scale = tolerance + tolerance * y[n].abs().max(stage[n].abs());
+                (estimate / scale).powi(2)
+            })
+            .sum::<f64>();
+        let error = (error / y.len() as f64).sqrt();
+        if !error.is_finite() {
+            return Err(ErrorWithMessage::new_box(&format!("ode: the solution is not finite near t = {:?}", t)));
+        }
+
+        if error <= 1.0 {
+            t += h;
+            y = stage;
+            if t == t1 {
+                return Ok(y);
+            }
+            k.swap(0, 6);
+        }
+        h *= (0.9 * error.powf(-0.2)).clamp(0.2, 5.0);
+        if t + h == t {
+            return Err(ErrorWithMessage::new_box(&format!("ode: the step size vanished at t = {:?}", t)));
+        }
+    }
+    Err(ErrorWithMessage::new_box(&format!(
+        "ode: gave up at t = {:?} after {} steps; the problem may be stiff",
+        t, MAX_STEPS
+    )))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{dormand_prince, DEFAULT_TOLERANCE};
+
+    #[test]
+    fn test_dormand_prince() {
+        let y = dormand_prince(&mut |_, y| Ok(vec![y[0]]), 0.0, &[1.0], 1.0, DEFAULT_TOLERANCE).unwrap();
+        assert!((y[0] - std::f64::consts::E).abs() < 1e-8);
+
+        // y'' = -y as a system, integrated backwards over a full period.
+        let tau = std::f64::consts::TAU;
+        let y = dormand_prince(&mut |_, y| Ok(vec![y[1], -y[0]]), tau, &[0.0, 1.0], 0.0, DEFAULT_TOLERANCE).unwrap();
+        assert!(y[0].abs() < 1e-8 && (y[1] - 1.0).abs() < 1e-8);
+
+        let wrong_length = dormand_prince(&mut |_, y| Ok(vec![y[1]]), 0.0, &[0.0, 1.0], 1.0, DEFAULT_TOLERANCE);
+        assert!(wrong_length.is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use crate::errors::{ErrorWithMessage, SamError};
+
+/// The tolerance `minimize` uses unless given one.
+pub const DEFAULT_TOLERANCE: f64 = 1e-10;
+
+const MAX_ITERATIONS: usize = 10_000;
+
+/// A function that gives its value and gradient at a point.
+pub type Differentiable<'a> = dyn FnMut(&[f64]) -> Result<(f64, Vec<f64>), SamError> + 'a;
+
+/// Minimises `f` from `x0` with the Nelder–Mead simplex method, which only
+/// needs values of `f`. Stops once the values at the corners of the
+/// simplex agree to within `tolerance`.
+pub fn nelder_mead(
+    f: &mut dyn FnMut(&[f64]) -> Result<f64, SamError>,
+    x0: &[f64],
+    tolerance: f64,
+) -> Result<Vec<f64>, SamError> {
+    let n = x0.len();
+    let mut simplex = vec![x0.to_vec()];
+    for i in 0..n {
+        let mut corner = x0.to_vec();
+        corner[i] += if corner[i] == 0.0 { 0.00025 } else { 0.05 * corner[i] };
+        simplex.push(corner);
+    }
+    let mut values = simplex.iter().map(|x| f(x)).collect::<Result<Vec<f64>, SamError>>()?;
+
+    for _ in 0..MAX_ITERATIONS * n.max(1) {
+        let mut order: Vec<usize> = (0..=n).collect();
+        order.sort_by(|a, b| values[*a].total_cmp(&values[*b]));
+        simplex = order.iter().map(|i| simplex[*i].clone()).collect();
+        values = order.iter().map(|i| values[*i]).collect();
+
+        let (best, worst) = (values[0], values[n]);
+        let size = simplex[1..]
+            .iter()
+            .flat_map(|x| x.iter().zip(&simplex[0]).map(|(a, b)| (a - b).abs()))
+            .fold(0.0, f64::max);
+        if (worst - best).abs() <= tolerance * best.abs().max(1.0) && size <= tolerance.sqrt() * norm(&simplex[0]).max(1.0) {
+            return Ok(simplex.swap_remove(0));
+        }
+
+        let centroid: Vec<f64> = (0..n).map(|i| simplex[..n].iter().map(|x| x[i]).sum::<f64>() / n as f64).collect();
+        let towards = |t: f64| -> Vec<f64> { (0..n).map(|i| centroid[i] + t * (simplex[n][i] - centroid[i])).collect() };
+
+        let reflected = towards(-1.0);
+        let reflected_value = f(&reflected)?;
+        if reflected_value < values[0] {
+            let expanded = towards(-2.0);
+            let expanded_value = f(&expanded)?;
+            (simplex[n], values[n]) = match expanded_value < reflected_value {
+                true => (expanded, expanded_value),
+                false => (reflected, reflected_value),
+            };
+        } else if reflected_value < values[n - 1] {
+            (simplex[n], values[n]) = (reflected, reflected_value);
+        } else {
+            let contracted = towards(if reflected_value < values[n] { -0.5 } else { 0.5 });
+            let contracted_value = f(&contracted)?;
+            if contracted_value < values[n].min(reflected_value) {
+                (simplex[n], values[n]) = (contracted, contracted_value);
+            } else {
+                // Shrink everything towards the best corner.
+                for i in 1..=n {
+                    simplex[i] = (0..n).map(|k| simplex[0][k] + 0.5 * (simplex[i][k] - simplex[0][k])).collect();
+                    values[i] = f(&simplex[i])?;
+                }
+            }
+        }
+    }
+    Err(ErrorWithMessage::new_box("minimize did not converge"))
+}
+
+/// Minimises `f` from `x0` by BFGS with a backtracking line search.
+pub fn bfgs(f: &mut Differentiable, x0: &[f64], tolerance: f64) -> Result<Vec<f64>, SamError> {
+    let n = x0.len();
+    let mut x = x0.to_vec();
+    let (mut value, mut gradient) = f(&x)?;
+    // The approximate inverse Hessian, starting from the identity.
+    let mut inverse: Vec<Vec<f64>> = (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+
+    for _ in 0..MAX_ITERATIONS {
+        if norm(&gradient) <= tolerance * value.abs().max(1.0) {
+            return Ok(x);
+        }
+        let mut direction: Vec<f64> = inverse.iter().map(|row| -dot(row, &gradient)).collect();
+        let mut slope = dot(&direction, &gradient);
+        if slope >= 0.0 {
+            // Not a descent direction, so start again from steepest descent.
+            direction = gradient.iter().map(|g| -g).collect();
+            slope = -dot(&gradient, &gradient);
+            inverse = (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+        }
+
+        let mut step = 1.0;
+        let (next, next_value, next_gradient) = loop {
+            let next: Vec<f64> = x.iter().zip(&direction).map(|(x, d)| x + step * d).collect();
+            let (next_value, next_gradient) = f(&next)?;
+            if next_value <= value + 1e-4 * step * slope {
+                break (next, next_value, next_gradient);
+            }
+            step /= 2.0;
+            if step * norm(&direction) <= f64::EPSILON * norm(&x).max(1.0) {
+                // No further progress is possible at this precision.
+                return Ok(x);
+            }
+        };
+
+        let s: Vec<f64> = next.iter().zip(&x).map(|(a, b)| a - b).collect();
+        let y: Vec<f64> = next_gradient.iter().zip(&gradient).map(|(a, b)| a - b).collect();
+        let sy = dot(&s, &y);
+        if sy > 0.0 {
+            let hy: Vec<f64> = inverse.iter().map(|row| dot(row, &y)).collect();
+            let yhy = dot(&y, &hy);
+            for i in 0..n {
+                for j in 0..n {
+                    inverse[i][j] += ((sy + yhy) * s[i] * s[j] / sy - hy[i] * s[j] - s[i] * hy[j]) / sy;
+                }
+            }
+        }
+        x = next;
+        value = next_value;
+        gradient = next_gradient;
+    }
+    Err(ErrorWithMessage::new_box("minimize did not converge"))
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+fn norm(a: &[f64]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bfgs, nelder_mead, DEFAULT_TOLERANCE};
+
+    fn rosenbrock(x: &[f64]) -> f64 {
+        (1.0 - x[0]).powi(2) + 100.0 * (x[1] - x[0] * x[0]).powi(2)
+    }
+
+    #[test]
+    fn test_minimize() {
+        let x = nelder_mead(&mut |x| Ok(rosenbrock(x)), &[-1.2, 1.0], DEFAULT_TOLERANCE).unwrap();
+        assert!((x[0] - 1.0).abs() < 1e-4 && (x[1] - 1.0).abs() < 1e-4);
+
+        let mut f = |x: &[f64]| {
+            let gradient = vec![
+                -2.0 * (1.0 - x[0]) - 400.0 * x[0] * (x[1] - x[0] * x[0]),
+                200.0 * (x[1] - x[0] * x[0]),
+            ];
+            Ok((rosenbrock(x), gradient))
+        };
+        let x = bfgs(&mut f, &[-1.2, 1.0], DEFAULT_TOLERANCE).unwrap();
+        assert!((x[0] - 1.0).abs() < 1e-8 && (x[1] - 1.0).abs() < 1e-8);
+    }
+}
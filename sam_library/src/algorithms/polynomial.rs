@@ -0,0 +1,318 @@
+use std::ops::{Add, Mul, Neg, Sub};
+
+use crate::errors::{ErrorWithMessage, SamError};
+
+use super::{
+    linalg::{self, Matrix},
+    roots::{complex_add, complex_div, complex_mul, polynomial_roots},
+};
+
+/// Relative size below which numeric results are taken to be rounding
+/// error, as in `solve`.
+const ROUNDING: f64 = 1e-12;
+
+/// Durand–Kerner finds a root of multiplicity `m` only to about `1/m` of the
+/// digits, as `m` roots spread around it. Roots this close together
+/// relative to their size are taken to be such a spread.
+const CLUSTER: f64 = 1e-4;
+
+const POLISHING_STEPS: usize = 8;
+
+/// A polynomial in one named variable with real coefficients.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polynomial {
+    /// Lowest power first, without trailing zeros, so the zero polynomial
+    /// has none.
+    coefficients: Vec<f64>,
+    pub variable: String,
+}
+
+impl Polynomial {
+    /// From coefficients given lowest power first.
+    pub fn new(mut coefficients: Vec<f64>, variable: &str) -> Polynomial {
+        while coefficients.last() == Some(&0.0) {
+            coefficients.pop();
+        }
+        Polynomial {
+            coefficients,
+            variable: variable.to_owned(),
+        }
+    }
+
+    pub fn constant(c: f64, variable: &str) -> Polynomial {
+        Polynomial::new(vec![c], variable)
+    }
+
+    /// Lowest power first.
+    pub fn coefficients(&self) -> &[f64] {
+        &self.coefficients
+    }
+
+    /// The degree, which is taken to be 0 for the zero polynomial too.
+    pub fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.coefficients.is_empty()
+    }
+
+    /// The value at `x`, by Horner's method.
+    pub fn eval(&self, x: f64) -> f64 {
+        self.coefficients.iter().rev().fold(0.0, |total, c| total * x + c)
+    }
+
+    pub fn pow(&self, mut exponent: u32) -> Polynomial {
+        let mut result = Polynomial::constant(1.0, &self.variable);
+        let mut base = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = &result * &base;
+            }
+            base = &base * &base;
+            exponent >>= 1;
+        }
+        result
+    }
+
+    /// The quotient and remainder of long division by `divisor`.
+    /// Remainder coefficients that are only rounding error are dropped, so
+    /// that exact division leaves none.
+    pub fn div_rem(&self, divisor: &Polynomial) -> Result<(Polynomial, Polynomial), SamError> {
+        let lead = *divisor
+            .coefficients
+            .last()
+            .ok_or_else(|| ErrorWithMessage::new_box("division by the zero polynomial"))?;
+        let d = divisor.degree();
+        let mut remainder = self.coefficients.clone();
+        let mut quotient = vec![0.0; remainder.len().saturating_sub(d)];
+        for k in (0..quotient.len()).rev() {
+            let q = remainder[k + d] / lead;
+            quotient[k] = q;
+            for (j, c) in divisor.coefficients.iter().enumerate() {
+                remainder[k + j] -= q * c;
+            }
+            remainder[k + d] = 0.0;
+        }
+        let scale = largest(&self.coefficients);
+        for r in remainder.iter_mut() {
+            if r.abs() <= ROUNDING * scale {
+                *r = 0.0;
+            }
+        }
+        Ok((Polynomial::new(quotient, &self.variable), Polynomial::new(remainder, &self.variable)))
+    }
+
+    pub fn derivative(&self) -> Polynomial {
+        let coefficients = self.coefficients.iter().enumerate().skip(1).map(|(k, c)| k as f64 * c).collect();
+        Polynomial::new(coefficients, &self.variable)
+    }
+
+    /// The antiderivative that is `constant` at 0.
+    pub fn integral(&self, constant: f64) -> Polynomial {
+        let terms = self.coefficients.iter().enumerate().map(|(k, c)| c / (k + 1) as f64);
+        Polynomial::new(std::iter::once(constant).chain(terms).collect(), &self.variable)
+    }
+
+    /// This polynomial with `inner` substituted for its variable.
+    pub fn compose(&self, inner: &Polynomial) -> Polynomial {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(Polynomial::constant(0.0, &inner.variable), |total, c| {
+                &(&total * inner) + &Polynomial::constant(*c, &inner.variable)
+            })
+    }
+
+    /// All complex roots `(re, im)`, repeated by multiplicity: the real ones
+    /// in increasing order, then the rest.
+    pub fn roots(&self) -> Vec<(f64, f64)> {
+        let mut clusters: Vec<Vec<(f64, f64)>> = vec![];
+        for root in polynomial_roots(&self.coefficients) {
+            let size = root.0.hypot(root.1).max(1.0);
+            let near = |cluster: &&mut Vec<(f64, f64)>| {
+                let (re, im) = mean(cluster);
+                (re - root.0).hypot(im - root.1) <= CLUSTER * size
+            };
+            match clusters.iter_mut().find(near) {
+                Some(cluster) => cluster.push(root),
+                None => clusters.push(vec![root]),
+            }
+        }
+
+        let mut roots: Vec<(f64, f64)> = clusters
+            .iter()
+            .flat_map(|cluster| {
+                let (re, im) = self.polish(mean(cluster), cluster.len());
+                let size = re.hypot(im).max(1.0);
+                let im = match im.abs() <= 1e-9 * size {
+                    true => 0.0,
+                    false => tidy(im, size),
+                };
+                std::iter::repeat_n((tidy(re, size), im), cluster.len())
+            })
+            .collect();
+        roots.sort_by(|a, b| (a.1 != 0.0).cmp(&(b.1 != 0.0)).then(a.0.total_cmp(&b.0)).then(a.1.total_cmp(&b.1)));
+        roots
+    }
+
+    /// A root of multiplicity `m` is a simple root of the `m - 1`th
+    /// derivative, where Newton's method converges quickly. A step that
+    /// would leave the cluster is not taken.
+    fn polish(&self, start: (f64, f64), multiplicity: usize) -> (f64, f64) {
+        let mut z = start;
+        let mut p = self.clone();
+        for _ in 1..multiplicity {
+            p = p.derivative();
+        }
+        let dp = p.derivative();
+        for _ in 0..POLISHING_STEPS {
+            let step = complex_div(p.eval_complex(z), dp.eval_complex(z));
+            if !(step.0.is_finite() && step.1.is_finite()) {
+                break;
+            }
+            let next = (z.0 - step.0, z.1 - step.1);
+            if (next.0 - start.0).hypot(next.1 - start.1) > CLUSTER * start.0.hypot(start.1).max(1.0) {
+                break;
+            }
+            z = next;
+            if step.0.hypot(step.1) <= f64::EPSILON * z.0.hypot(z.1) {
+                break;
+            }
+        }
+        z
+    }
+
+    fn eval_complex(&self, z: (f64, f64)) -> (f64, f64) {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold((0.0, 0.0), |total, c| complex_add(complex_mul(total, z), (*c, 0.0)))
+    }
+
+    /// The least squares fit of the given degree to the points `(xs[i],
+    /// ys[i])`.
+    pub fn fit(xs: &[f64], ys: &[f64], degree: usize, variable: &str) -> Result<Polynomial, SamError> {
+        if xs.len() != ys.len() {
+            return Err(ErrorWithMessage::new_box("polyfit needs as many x values as y values"));
+        }
+        if xs.len() <= degree {
+            return Err(ErrorWithMessage::new_box("polyfit needs more points than the degree"));
+        }
+        // Fitting in a centred and scaled variable keeps the system well
+        // conditioned.
+        let centre = xs.iter().sum::<f64>() / xs.len() as f64;
+        let scale = match largest(&xs.iter().map(|x| x - centre).collect::<Vec<f64>>()) {
+            spread if spread > 0.0 => spread,
+            _ => 1.0,
+        };
+        let a: Matrix = xs
+            .iter()
+            .map(|x| (0..=degree).map(|k| ((x - centre) / scale).powi(k as i32)).collect())
+            .collect();
+        let b: Matrix = ys.iter().map(|y| vec![*y]).collect();
+        let fitted = Polynomial::new(linalg::solve(&a, &b)?.into_iter().map(|row| row[0]).collect(), variable);
+        Ok(fitted.compose(&Polynomial::new(vec![-centre / scale, 1.0 / scale], variable)))
+    }
+}
+
+impl Add for &Polynomial {
+    type Output = Polynomial;
+    fn add(self, other: &Polynomial) -> Polynomial {
+        let length = self.coefficients.len().max(other.coefficients.len());
+        let coefficient = |p: &Polynomial, k: usize| p.coefficients.get(k).copied().unwrap_or(0.0);
+        let coefficients = (0..length).map(|k| coefficient(self, k) + coefficient(other, k)).collect();
+        Polynomial::new(coefficients, &self.variable)
+    }
+}
+
+impl Sub for &Polynomial {
+    type Output = Polynomial;
+    fn sub(self, other: &Polynomial) -> Polynomial {
+        self + &-other
+    }
+}
+
+impl Mul for &Polynomial {
+    type Output = Polynomial;
+    fn mul(self, other: &Polynomial) -> Polynomial {
+        if self.is_zero() || other.is_zero() {
+            return Polynomial::new(vec![], &self.variable);
+        }
+        let mut coefficients = vec![0.0; self.coefficients.len() + other.coefficients.len() - 1];
+        for (i, a) in self.coefficients.iter().enumerate() {
+            for (j, b) in other.coefficients.iter().enumerate() {
+                coefficients[i + j] += a * b;
+            }
+        }
+        Polynomial::new(coefficients, &self.variable)
+    }
+}
+
+impl Neg for &Polynomial {
+    type Output = Polynomial;
+    fn neg(self) -> Polynomial {
+        Polynomial::new(self.coefficients.iter().map(|c| -c).collect(), &self.variable)
+    }
+}
+
+fn largest(xs: &[f64]) -> f64 {
+    xs.iter().fold(0.0, |largest, x| largest.max(x.abs()))
+}
+
+fn mean(points: &[(f64, f64)]) -> (f64, f64) {
+    let n = points.len() as f64;
+    let (re, im) = points.iter().fold((0.0, 0.0), |(re, im), point| (re + point.0, im + point.1));
+    (re / n, im / n)
+}
+
+/// Snaps values within rounding error of zero or of a whole number.
+fn tidy(x: f64, scale: f64) -> f64 {
+    if x.abs() <= ROUNDING * scale {
+        0.0
+    } else if (x - x.round()).abs() <= ROUNDING * scale {
+        x.round()
+    } else {
+        x
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Polynomial;
+
+    fn poly(coefficients: &[f64]) -> Polynomial {
+        Polynomial::new(coefficients.to_vec(), "x")
+    }
+
+    #[test]
+    fn test_polynomials() {
+        // x^2 - 3x + 2 = (x - 1)(x - 2)
+        let p = poly(&[2.0, -3.0, 1.0]);
+        assert!(&poly(&[-1.0, 1.0]) * &poly(&[-2.0, 1.0]) == p);
+        assert!((&p + &poly(&[-2.0, 3.0])).coefficients() == [0.0, 0.0, 1.0]);
+        assert!((&p - &p).is_zero() && p.eval(3.0) == 2.0);
+        assert!(p.pow(2) == &p * &p && p.pow(0) == poly(&[1.0]));
+
+        let (quotient, remainder) = p.div_rem(&poly(&[-1.0, 1.0])).unwrap();
+        assert!(quotient == poly(&[-2.0, 1.0]) && remainder.is_zero());
+        let (quotient, remainder) = poly(&[1.0, 0.0, 1.0]).div_rem(&poly(&[-1.0, 1.0])).unwrap();
+        assert!(quotient == poly(&[1.0, 1.0]) && remainder == poly(&[2.0]));
+        assert!(p.div_rem(&poly(&[])).is_err());
+
+        assert!(p.derivative() == poly(&[-3.0, 2.0]));
+        assert!(poly(&[3.0, 2.0]).integral(1.0) == poly(&[1.0, 3.0, 1.0]));
+
+        assert!(p.roots() == [(1.0, 0.0), (2.0, 0.0)]);
+        assert!(poly(&[1.0, 0.0, 1.0]).roots() == [(0.0, -1.0), (0.0, 1.0)]);
+        // (x - 1)^3 (x + 2)
+        let repeated = &poly(&[-1.0, 1.0]).pow(3) * &poly(&[2.0, 1.0]);
+        assert!(repeated.roots() == [(-2.0, 0.0), (1.0, 0.0), (1.0, 0.0), (1.0, 0.0)]);
+
+        let xs = [1000.0, 1001.0, 1002.0, 1003.0];
+        let ys: Vec<f64> = xs.iter().map(|x| 2.0 * x * x - x + 5.0).collect();
+        let fitted = Polynomial::fit(&xs, &ys, 2, "x").unwrap();
+        assert!((fitted.eval(1004.0) - (2.0 * 1004.0 * 1004.0 - 1004.0 + 5.0)).abs() < 1e-6);
+        assert!(Polynomial::fit(&xs, &ys, 4, "x").is_err());
+    }
+}
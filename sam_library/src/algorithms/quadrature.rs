@@ -0,0 +1,190 @@
+use crate::errors::{ErrorWithMessage, SamError};
+
+/// Nodes of the 15 point Kronrod rule on `[-1, 1]`, largest first; the odd
+/// ones are also the nodes of the 7 point Gauss rule.
+const KRONROD_NODES: [f64; 8] = [
+    0.9914553711208126,
+    0.9491079123427585,
+    0.8648644233597691,
+    0.7415311855993945,
+    0.5860872354676911,
+    0.4058451513773972,
+    0.20778495500789848,
+    0.0,
+];
+
+const KRONROD_WEIGHTS: [f64; 8] = [
+    0.022935322010529224,
+    0.06309209262997856,
+    0.10479001032225019,
+    0.14065325971552592,
+    0.1690047266392679,
+    0.19035057806478542,
+    0.20443294007529889,
+    0.20948214108472782,
+];
+
+const GAUSS_WEIGHTS: [f64; 4] = [
+    0.1294849661688697,
+    0.27970539148927664,
+    0.3818300505051189,
+    0.4179591836734694,
+];
+
+/// Subdivision stops once the estimated error is below this, in absolute
+/// terms or relative to the integral.
+pub const DEFAULT_TOLERANCE: f64 = 1e-10;
+
+const MAX_INTERVALS: usize = 2000;
+
+/// An integral and an estimate of its absolute error.
+#[derive(Clone, Copy, Debug)]
+pub struct Quadrature {
+    pub value: f64,
+    pub error: f64,
+}
+
+/// Integrates `f` over `[a, b]` by adaptive Gauss–Kronrod quadrature,
+/// repeatedly halving the subinterval with the largest error. Infinite
+/// bounds are mapped onto a finite interval first.
+pub fn integrate(
+    f: &mut dyn FnMut(f64) -> Result<f64, SamError>,
+    a: f64,
+    b: f64,
+    tolerance: f64,
+) -> Result<Quadrature, SamError> {
+    if a.is_nan() || b.is_nan() {
+        return Err(ErrorWithMessage::new_box("integrate: the bounds must be numbers"));
+    }
+    if a == b {
+        return Ok(Quadrature { value: 0.0, error: 0.0 });
+    }
+    if a > b {
+        let result = integrate(f, b, a, tolerance)?;
+        return Ok(Quadrature { value: -result.value, ..result });
+    }
+
+    // Subintervals can get narrow enough that a node rounds onto an infinite
+    // bound, where a convergent integrand vanishes.
+    let mut f = |x: f64| -> Result<f64, SamError> {
+        if x.is_infinite() {
+            return Ok(0.0);
+        }
+        let y = f(x)?;
+        match y.is_finite() {
+            true => Ok(y),
+            false => Err(ErrorWithMessage::new_box(&format!("integrate: the integrand is {} at {:?}", y, x))),
+        }
+    };
+    match (a.is_infinite(), b.is_infinite()) {
+        (false, false) => adaptive(&mut f, a, b, tolerance),
+        // x = a + t / (1 - t)
+        (false, true) => adaptive(
+            &mut |t| Ok(f(a + t / (1.0 - t))? / ((1.0 - t) * (1.0 - t))),
+            0.0,
+            1.0,
+            tolerance,
+        ),
+        // x = b - (1 - t) / t
+        (true, false) => adaptive(&mut |t| Ok(f(b - (1.0 - t) / t)? / (t * t)), 0.0, 1.0, tolerance),
+        // x = t / (1 - t^2)
+        (true, true) => adaptive(
+            &mut |t| {
+                let s = 1.0 - t * t;
+                Ok(f(t / s)? * (1.0 + t * t) / (s * s))
+            },
+            -1.0,
+            1.0,
+            tolerance,
+        ),
+    }
+}
+
+fn adaptive(
+    f: &mut dyn FnMut(f64) -> Result<f64, SamError>,
+    a: f64,
+    b: f64,
+    tolerance: f64,
+) -> Result<Quadrature, SamError> {
+    let mut intervals = vec![(a, b, kronrod(f, a, b)?)];
+    loop {
+        let value: f64 = intervals.iter().map(|(_, _, q)| q.value).sum();
+        let error: f64 = intervals.iter().map(|(_, _, q)| q.error).sum();
+        if error <= tolerance.max(tolerance * value.abs()) {
+            return Ok(Quadrature { value, error });
+        }
+        if intervals.len() >= MAX_INTERVALS {
+            return Err(ErrorWithMessage::new_box(&format!(
+                "integrate did not converge: the estimated error is {:?}",
+                error
+            )));
+        }
+
+        let worst = (0..intervals.len())
+            .max_by(|i, j| intervals[*i].2.error.total_cmp(&intervals[*j].2.error))
+            .unwrap();
+        let (lo, hi, _) = intervals.swap_remove(worst);
+        let middle = lo + (hi - lo) / 2.0;
+        if middle <= lo || middle >= hi {
+            return Err(ErrorWithMessage::new_box(&format!(
+                "integrate did not converge near {:?}: the estimated error is {:?}",
+                middle, error
+            )));
+        }
+        intervals.push((lo, middle, kronrod(f, lo, middle)?));
+        intervals.push((middle, hi, kronrod(f, middle, hi)?));
+    }
+}
+
+/// The 15 point Kronrod estimate over `[a, b]`, with the difference from
+/// the embedded 7 point Gauss rule as its error.
+fn kronrod(f: &mut dyn FnMut(f64) -> Result<f64, SamError>, a: f64, b: f64) -> Result<Quadrature, SamError> {
+    let center = (a + b) / 2.0;
+    let half_width = (b - a) / 2.0;
+    let mut evaluate = |x: f64| -> Result<f64, SamError> {
+        let y = f(x)?;
+        match y.is_finite() {
+            true => Ok(y),
+            false => Err(ErrorWithMessage::new_box("integrate: the integral does not converge")),
+        }
+    };
+
+    let mut kronrod = 0.0;
+    let mut gauss = 0.0;
+    for (i, node) in KRONROD_NODES.iter().enumerate() {
+        let sum = match *node == 0.0 {
+            true => evaluate(center)?,
+            false => evaluate(center - half_width * node)? + evaluate(center + half_width * node)?,
+        };
+        kronrod += KRONROD_WEIGHTS[i] * sum;
+        if i % 2 == 1 {
+            gauss += GAUSS_WEIGHTS[i / 2] * sum;
+        }
+    }
+    Ok(Quadrature {
+        value: kronrod * half_width,
+        error: ((kronrod - gauss) * half_width).abs(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{integrate, DEFAULT_TOLERANCE};
+
+    fn close(value: f64, expected: f64) -> bool {
+        (value - expected).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_integrals() {
+        let result = integrate(&mut |x| Ok(x.sin()), 0.0, std::f64::consts::PI, DEFAULT_TOLERANCE).unwrap();
+        assert!(close(result.value, 2.0) && result.error < 1e-9);
+        let result = integrate(&mut |x| Ok(x * x), 3.0, 0.0, DEFAULT_TOLERANCE).unwrap();
+        assert!(close(result.value, -9.0));
+        let result = integrate(&mut |x| Ok((-x * x).exp()), f64::NEG_INFINITY, f64::INFINITY, DEFAULT_TOLERANCE);
+        assert!(close(result.unwrap().value, std::f64::consts::PI.sqrt()));
+        let result = integrate(&mut |x| Ok(1.0 / (x * x)), 1.0, f64::INFINITY, DEFAULT_TOLERANCE).unwrap();
+        assert!(close(result.value, 1.0));
+        assert!(integrate(&mut |x| Ok(1.0 / x), 0.0, 1.0, DEFAULT_TOLERANCE).is_err());
+    }
+}
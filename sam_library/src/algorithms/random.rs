@@ -0,0 +1,102 @@
+use std::{
+    collections::hash_map::RandomState,
+    f64::consts::PI,
+    hash::{BuildHasher, Hasher},
+};
+
+/// The xoshiro256** generator. Fast and statistically sound, but not
+/// suitable for cryptography.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    /// A generator whose sequence is determined entirely by `seed`.
+    pub fn new(seed: u64) -> Rng {
+        // Spread the seed over the state with splitmix64, which never
+        // produces the all-zero state xoshiro cannot leave.
+        let mut x = seed;
+        let mut state = [0; 4];
+        for word in state.iter_mut() {
+            x = x.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            *word = z ^ (z >> 31);
+        }
+        Rng { state }
+    }
+
+    /// A generator seeded differently on every run.
+    pub fn from_entropy() -> Rng {
+        Rng::new(RandomState::new().build_hasher().finish())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    /// A float uniformly distributed in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// A whole number uniformly distributed below `n`, which must not be 0.
+    pub fn below(&mut self, n: u64) -> u64 {
+        // Reject the top values that would make the remainders uneven.
+        let limit = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < limit {
+                return x % n;
+            }
+        }
+    }
+
+    /// A standard normal variate by the Box-Muller transform.
+    pub fn normal(&mut self) -> f64 {
+        let radius = (-2.0 * (1.0 - self.next_f64()).ln()).sqrt();
+        radius * (2.0 * PI * self.next_f64()).cos()
+    }
+
+    /// Shuffles `items` in place with the Fisher-Yates algorithm.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Rng;
+
+    #[test]
+    fn test_rng() {
+        let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+        assert!((0..100).all(|_| a.next_u64() == b.next_u64()));
+        assert!(Rng::new(1).next_u64() != Rng::new(2).next_u64());
+
+        let mut rng = Rng::new(7);
+        assert!((0..1000).all(|_| (0.0..1.0).contains(&rng.next_f64())));
+        assert!((0..1000).all(|_| rng.below(6) < 6));
+        let mean = (0..10_000).map(|_| rng.normal()).sum::<f64>() / 10_000.0;
+        assert!(mean.abs() < 0.05);
+
+        let mut items = [1, 2, 3, 4, 5];
+        rng.shuffle(&mut items);
+        items.sort();
+        assert!(items == [1, 2, 3, 4, 5]);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::errors::{ErrorWithMessage, SamError};
+
+/// Bisection stops once the bracket is this narrow, relative to the root.
+const TOLERANCE: f64 = 1e-15;
+
+/// The tolerance `root` and `newton` use unless given one.
+pub const DEFAULT_TOLERANCE: f64 = 1e-15;
+
+const MAX_ITERATIONS: usize = 500;
+
+/// Finds every root of `f` in `[a, b]` where `f` changes sign between
+/// `samples` evenly spaced points, refining each one by bisection. Poles
+/// such as those of `tan` also change sign, so candidates where `f` is not
+/// close to zero are dropped.
+pub fn find_roots(
+    f: &mut dyn FnMut(f64) -> Result<f64, SamError>,
+    a: f64,
+    b: f64,
+    samples: usize,
+) -> Result<Vec<f64>, SamError> {
+    let step = (b - a) / samples as f64;
+    let mut roots: Vec<f64> = vec![];
+    let mut x0 = a;
+    let mut y0 = f(x0)?;
+    for i in 1..=samples {
+        let x1 = a + step * i as f64;
+        let y1 = f(x1)?;
+        if y0 == 0.0 {
+            roots.push(x0);
+        } else if y0.is_finite() && y1.is_finite() && y0.signum() != y1.signum() && y1 != 0.0 {
+            let root = bisect(f, x0, x1, y0)?;
+            let scale = y0.abs().min(y1.abs()).max(1.0);
+            if f(root)?.abs() <= 1e-6 * scale {
+                roots.push(root);
+            }
+        }
+        x0 = x1;
+        y0 = y1;
+    }
+    if y0 == 0.0 {
+        roots.push(x0);
+    }
+    Ok(roots)
+}
+
+/// Narrows a bracket `[a, b]` around a sign change of `f`, where `fa` is
+/// `f(a)`.
+pub fn bisect(
+    f: &mut dyn FnMut(f64) -> Result<f64, SamError>,
+    mut a: f64,
+    mut b: f64,
+    mut fa: f64,
+) -> Result<f64, SamError> {
+    for _ in 0..MAX_ITERATIONS {
+        let middle = a + (b - a) / 2.0;
+        if (b - a).abs() <= TOLERANCE * middle.abs().max(1.0) || middle == a || middle == b {
+            return Ok(middle);
+        }
+        let fm = f(middle)?;
+        if fm == 0.0 {
+            return Ok(middle);
+        }
+        if fm.signum() == fa.signum() {
+            a = middle;
+            fa = fm;
+        } else {
+            b = middle;
+        }
+    }
+    Ok(a + (b - a) / 2.0)
+}
+
+/// Finds a root of `f` in `[a, b]`, where `f(a)` and `f(b)` have opposite
+/// signs, by Brent's method: inverse quadratic interpolation or secant
+/// steps when they make progress, bisection otherwise.
+pub fn brent(
+    f: &mut dyn FnMut(f64) -> Result<f64, SamError>,
+    mut a: f64,
+    mut b: f64,
+    tolerance: f64,
+) -> Result<f64, SamError> {
+    let (mut fa, mut fb) = (f(a)?, f(b)?);
+    if fa == 0.0 {
+        return Ok(a);
+    }
+    if fb == 0.0 {
+        return Ok(b);
+    }
+    if fa.signum() == fb.signum() || !fa.is_finite() || !fb.is_finite() {
+        return Err(ErrorWithMessage::new_box("f(a) and f(b) must have opposite signs"));
+    }
+
+    let (mut c, mut fc) = (a, fa);
+    let mut d = b - a;
+    let mut e = d;
+    for _ in 0..MAX_ITERATIONS {
+        if fb.signum() == fc.signum() {
+            (c, fc) = (a, fa);
+            d = b - a;
+            e = d;
+        }
+        if fc.abs() < fb.abs() {
+            (a, fa) = (b, fb);
+            (b, fb) = (c, fc);
+            (c, fc) = (a, fa);
+        }
+
+        let bound = 2.0 * f64::EPSILON * b.abs() + tolerance / 2.0;
+        let middle = (c - b) / 2.0;
+        if middle.abs() <= bound || fb == 0.0 {
+            return Ok(b);
+        }
+
+        if e.abs() >= bound && fa.abs() > fb.abs() {
+            let s = fb / fa;
+            let (mut p, mut q) = if a == c {
+                (2.0 * middle * s, 1.0 - s)
+            } else {
+                let (q, r) = (fa / fc, fb / fc);
+                (
+                    s * (2.0 * middle * q * (q - r) - (b - a) * (r - 1.0)),
+                    (q - 1.0) * (r - 1.0) * (s - 1.0),
+                )
+            };
+            if p > 0.0 {
+                q = -q;
+            } else {
+                p = -p;
+            }
+            if 2.0 * p < (3.0 * middle * q - (bound * q).abs()).min((e * q).abs()) {
+                e = d;
+                d = p / q;
+            } else {
+                d = middle;
+                e = d;
+            }
+        } else {
+            d = middle;
+            e = d;
+        }
+
+        (a, fa) = (b, fb);
+        b += if d.abs() > bound { d } else { bound.copysign(middle) };
+        fb = f(b)?;
+    }
+    Err(ErrorWithMessage::new_box("root finding did not converge"))
+}
+
+/// Newton's method from `x0`, where `f` gives both the value and the
+/// derivative at a point. Stops once a step is within `tolerance`.
+pub fn newton(
+    f: &mut dyn FnMut(f64) -> Result<(f64, f64), SamError>,
+    x0: f64,
+    tolerance: f64,
+) -> Result<f64, SamError> {
+    let mut x = x0;
+    for _ in 0..MAX_ITERATIONS {
+        let (y, slope) = f(x)?;
+        if y == 0.0 {
+            return Ok(x);
+        }
+        if slope == 0.0 || !slope.is_finite() {
+            return Err(ErrorWithMessage::new_box(&format!("newton: the derivative is {} at {}", slope, x)));
+        }
+        let step = y / slope;
+        x -= step;
+        if !x.is_finite() {
+            break;
+        }
+        if step.abs() <= tolerance * x.abs().max(1.0) {
+            return Ok(x);
+        }
+    }
+    Err(ErrorWithMessage::new_box(&format!(
+        "newton did not converge from {} in {} iterations",
+        x0, MAX_ITERATIONS
+    )))
+}
+
+/// All complex roots `(re, im)` of a polynomial with coefficients given
+/// lowest power first, by the Durand–Kerner method.
+pub fn polynomial_roots(coefficients: &[f64]) -> Vec<(f64, f64)> {
+    let degree = match coefficients.iter().rposition(|c| *c != 0.0) {
+        Some(degree) if degree > 0 => degree,
+        _ => return vec![],
+    };
+    let leading = coefficients[degree];
+    let monic: Vec<f64> = coefficients[..=degree].iter().map(|c| c / leading).collect();
+
+    // Start from distinct points spread around a circle that holds every root.
+    let radius = 1.0 + monic[..degree].iter().fold(0.0_f64, |m, c| m.max(c.abs()));
+    let mut roots: Vec<(f64, f64)> = (0..degree)
+        .map(|k| {
+            let angle = 2.0 * std::f64::consts::PI * k as f64 / degree as f64 + 0.4;
+            (radius * angle.cos(), radius * angle.sin())
+        })
+        .collect();
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut change: f64 = 0.0;
+        for i in 0..degree {
+            let z = roots[i];
+            let mut numerator = (1.0, 0.0);
+            for c in monic[..degree].iter().rev() {
+                numerator = complex_add(complex_mul(numerator, z), (*c, 0.0));
+            }
+            let mut denominator = (1.0, 0.0);
+            for (j, w) in roots.iter().enumerate() {
+                if i != j {
+                    denominator = complex_mul(denominator, (z.0 - w.0, z.1 - w.1));
+                }
+            }
+            let step = complex_div(numerator, denominator);
+            roots[i] = (z.0 - step.0, z.1 - step.1);
+            change = change.max(step.0.hypot(step.1));
+        }
+        if change < 1e-14 {
+            break;
+        }
+    }
+    roots
+}
+
+pub(super) fn complex_add(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+pub(super) fn complex_mul(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+}
+
+pub(super) fn complex_div(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    let norm = b.0 * b.0 + b.1 * b.1;
+    ((a.0 * b.0 + a.1 * b.1) / norm, (a.1 * b.0 - a.0 * b.1) / norm)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{brent, newton};
+
+    #[test]
+    fn test_brent_and_newton() {
+        let root = brent(&mut |x| Ok(x * x - 2.0), 0.0, 2.0, 1e-14).unwrap();
+        assert!((root - 2.0_f64.sqrt()).abs() < 1e-13);
+        assert!(brent(&mut |x| Ok(x * x + 1.0), -1.0, 1.0, 1e-12).is_err());
+
+        let root = newton(&mut |x| Ok((x.cos() - x, -x.sin() - 1.0)), 1.0, 1e-14).unwrap();
+        assert!((root - 0.7390851332151607).abs() < 1e-13);
+        assert!(newton(&mut |x| Ok((x * x + 1.0, 2.0 * x)), 1.0, 1e-12).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{
+    errors::{ErrorWithMessage, SamError},
+    interpreter::data_types::Real,
+};
+
+// Whole numbers come back from all of these as `Real::Int`, or as a float
+// past the range of `i64`; the VM rounds integers exactly before calling
+// these, see `exact_integer_builtin`.
+
+pub fn abs(x: Real) -> Real {
+    match x {
+        Real::Int(n) => n.checked_abs().map_or(Real::Float((n as f64).abs()), Real::Int),
+        Real::Float(x) => Real::Float(x.abs()),
+    }
+}
+
+/// -1, 0 or 1. The sign of NaN is NaN.
+pub fn sign(x: Real) -> Real {
+    match x {
+        Real::Int(n) => Real::Int(n.signum()),
+        Real::Float(x) if x == 0.0 || x.is_nan() => Real::Float(x),
+        Real::Float(x) => Real::Float(x.signum()),
+    }
+}
+
+pub fn floor(x: Real) -> Real {
+    float_only(x, f64::floor)
+}
+
+pub fn ceil(x: Real) -> Real {
+    float_only(x, f64::ceil)
+}
+
+pub fn trunc(x: Real) -> Real {
+    float_only(x, f64::trunc)
+}
+
+/// The part after the decimal point, with the sign of `x`.
+pub fn frac(x: Real) -> Real {
+    match x {
+        Real::Int(_) => Real::Int(0),
+        Real::Float(x) => Real::Float(x.fract()),
+    }
+}
+
+/// Rounds to `digits` decimal places, or to a multiple of `10^-digits` when
+/// `digits` is negative. Halves round away from zero.
+pub fn round(x: Real, digits: i32) -> Real {
+    match x {
+        Real::Int(n) if digits >= 0 => Real::Int(n),
+        Real::Int(n) => match 10_i64.checked_pow(digits.unsigned_abs()) {
+            Some(scale) => {
+                let (quotient, remainder) = (n / scale, n % scale);
+                let quotient = match 2 * remainder.unsigned_abs() >= scale as u64 {
+                    true => quotient + n.signum(),
+                    false => quotient,
+                };
+                quotient.checked_mul(scale).map_or(Real::Float(quotient as f64 * scale as f64), Real::Int)
+            }
+            None => Real::Int(0),
+        },
+        // Past 2^52 a float has no fractional digits left to round, and
+        // scaling back would only add error.
+        Real::Float(x) if digits >= 0 => match x * 10_f64.powi(digits) {
+            scaled if scaled.abs() < MAX_FRACTIONAL => Real::Float(scaled.round() / 10_f64.powi(digits)),
+            _ => Real::Float(x),
+        },
+        Real::Float(x) => match x / 10_f64.powi(-digits) {
+            scaled if scaled.abs() < MAX_FRACTIONAL => Real::Float(scaled.round() * 10_f64.powi(-digits)),
+            _ => Real::Float(x),
+        },
+    }
+}
+
+/// Floats at least this large are whole numbers.
+const MAX_FRACTIONAL: f64 = 4503599627370496.0;
+
+/// `x` limited to the range from `low` to `high`.
+pub fn clamp(x: Real, low: Real, high: Real) -> Result<Real, SamError> {
+    if low > high {
+        return Err(ErrorWithMessage::new_box(&format!(
+            "clamp needs the lower bound {} to be at most the upper bound {}",
+            low.to_string(),
+            high.to_string()
+        )));
+    }
+    Ok(match x {
+        _ if x < low => low,
+        _ if x > high => high,
+        x => x,
+    })
+}
+
+fn float_only(x: Real, f: fn(f64) -> f64) -> Real {
+    match x {
+        Real::Int(n) => Real::Int(n),
+        Real::Float(x) => Real::Float(f(x)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::data_types::Real;
+
+    use super::{abs, ceil, clamp, floor, frac, round, sign};
+
+    fn is_int(x: Real, n: i64) -> bool {
+        matches!(x, Real::Int(m) if m == n)
+    }
+
+    fn is_float(x: Real, y: f64) -> bool {
+        matches!(x, Real::Float(z) if (z - y).abs() < 1e-12)
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert!(is_int(floor(Real::Int(-3)), -3) && is_float(floor(Real::Float(-2.5)), -3.0));
+        assert!(is_float(ceil(Real::Float(2.1)), 3.0));
+        assert!(is_int(abs(Real::Int(-4)), 4) && is_float(abs(Real::Int(i64::MIN)), 9.223372036854776e18));
+        assert!(is_int(sign(Real::Int(-7)), -1) && is_float(sign(Real::Float(2.5)), 1.0));
+        assert!(is_float(frac(Real::Float(-2.75)), -0.75) && is_int(frac(Real::Int(3)), 0));
+
+        assert!(is_float(round(Real::Float(2.5), 0), 3.0));
+        assert!(is_float(round(Real::Float(1.23456), 2), 1.23));
+        assert!(round(Real::Float(1e300), 5) == Real::Float(1e300) && round(Real::Float(1e300), -5) == Real::Float(1e300));
+        assert!(is_float(round(Real::Float(1234.5), -2), 1200.0));
+        assert!(is_int(round(Real::Int(1250), -2), 1300) && is_int(round(Real::Int(-1250), -2), -1300));
+        assert!(is_int(round(Real::Int(1249), -2), 1200) && is_int(round(Real::Int(17), 3), 17));
+        assert!(is_int(round(Real::Int(17), -30), 0));
+
+        assert!(is_int(clamp(Real::Int(12), Real::Int(0), Real::Int(10)).unwrap(), 10));
+        assert!(is_float(clamp(Real::Float(0.5), Real::Int(0), Real::Int(1)).unwrap(), 0.5));
+        assert!(clamp(Real::Int(1), Real::Int(2), Real::Int(0)).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use crate::errors::{ErrorWithMessage, SamError};
+
+/// How many terms are added up one by one before extrapolating instead.
+const DIRECT_TERMS: u64 = 10_000;
+
+/// The sum has settled once this many terms in a row no longer change it.
+const SETTLED_TERMS: u32 = 5;
+
+/// The highest order of Levin transform tried; beyond it cancellation loses
+/// more than the extra terms gain.
+const MAX_ORDER: usize = 24;
+
+/// How closely successive Levin estimates must agree to be trusted.
+const TOLERANCE: f64 = 1e-8;
+
+/// The sum of the infinite series `term(0) + term(1) + ...`. Quickly
+/// converging series are added up until the terms stop mattering; slower
+/// ones are extrapolated with the Levin u transform, which is usually good
+/// to eight digits or more.
+pub fn sum(term: &mut dyn FnMut(u64) -> Result<f64, SamError>) -> Result<f64, SamError> {
+    let mut terms = Vec::with_capacity(MAX_ORDER + 1);
+    let (mut total, mut halfway, mut settled) = (0.0, 0.0, 0);
+    for k in 0..DIRECT_TERMS {
+        let t = term(k)?;
+        if !t.is_finite() {
+            return Err(ErrorWithMessage::new_box(&format!("term {} of the series is {}", k, t)));
+        }
+        if terms.len() <= MAX_ORDER {
+            terms.push(t);
+        }
+        total += t;
+        settled = match t.abs() <= f64::EPSILON * total.abs() {
+            true => settled + 1,
+            false => 0,
+        };
+        if settled == SETTLED_TERMS {
+            return Ok(total);
+        }
+        if k + 1 == DIRECT_TERMS / 2 {
+            halfway = total;
+        }
+    }
+
+    // Divergent series such as 1 - 1 + 1 - ... can have a Levin estimate
+    // too, so the partial sums must also be closing in on it.
+    match levin(&terms) {
+        Some(estimate) if (estimate - total).abs() < (estimate - halfway).abs() => Ok(estimate),
+        _ => Err(ErrorWithMessage::new_box("the series does not seem to converge")),
+    }
+}
+
+/// The infinite product `factor(0) * factor(1) * ...`, as the exponential of
+/// the sum of the logarithms of the factors.
+pub fn product(factor: &mut dyn FnMut(u64) -> Result<f64, SamError>) -> Result<f64, SamError> {
+    let (mut negative, mut zero) = (false, false);
+    let logarithm = sum(&mut |k| {
+        let x = factor(k)?;
+        negative ^= x < 0.0;
+        zero |= x == 0.0;
+        Ok(if x == 0.0 { 0.0 } else { x.abs().ln() })
+    })?;
+    Ok(match (zero, negative) {
+        (true, _) => 0.0,
+        (false, true) => -logarithm.exp(),
+        (false, false) => logarithm.exp(),
+    })
+}
+
+/// The Levin estimate that agrees best with those of the two orders below
+/// it, if they agree well enough. Two neighbouring orders alone sometimes
+/// agree by accident.
+fn levin(terms: &[f64]) -> Option<f64> {
+    let mut best: Option<(f64, f64)> = None;
+    let (mut previous, mut before) = (f64::NAN, f64::NAN);
+    for order in 2..terms.len() {
+        let estimate = levin_u(terms, order);
+        let change = (estimate - previous).abs().max((previous - before).abs());
+        if change.is_finite() && best.is_none_or(|(smallest, _)| change < smallest) {
+            best = Some((change, estimate));
+        }
+        (previous, before) = (estimate, previous);
+    }
+    best.filter(|(change, estimate)| *change <= TOLERANCE * estimate.abs())
+        .map(|(_, estimate)| estimate)
+}
+
+/// The Levin u transform of order `k` of the partial sums of `terms`. It
+/// models the remainder after term `n` as `(n + 1) a_n` times a polynomial
+/// in `1 / (n + 1)` of degree `k - 1`, and eliminates that polynomial.
+fn levin_u(terms: &[f64], k: usize) -> f64 {
+    let (mut numerator, mut denominator, mut partial_sum) = (0.0, 0.0, 0.0);
+    // (-1)^j C(k, j)
+    let mut binomial = 1.0;
+    for (j, term) in terms[..=k].iter().enumerate() {
+        partial_sum += term;
+        let n = (j + 1) as f64;
+        let weight = binomial * (n / (k + 1) as f64).powi(k as i32 - 1) / (n * term);
+        numerator += weight * partial_sum;
+        denominator += weight;
+        binomial *= -((k - j) as f64) / n;
+    }
+    numerator / denominator
+}
+
+#[cfg(test)]
+mod test {
+    use std::f64::consts::{E, LN_2, PI};
+
+    use super::{product, sum};
+
+    fn is_close(x: f64, y: f64, tolerance: f64) -> bool {
+        (x - y).abs() <= tolerance * y.abs()
+    }
+
+    #[test]
+    fn test_series() {
+        let mut factorial = 1.0;
+        let exp = sum(&mut |k| {
+            factorial *= k.max(1) as f64;
+            Ok(1.0 / factorial)
+        });
+        assert!(is_close(exp.unwrap(), E, 1e-15));
+
+        let basel = sum(&mut |k| Ok(1.0 / ((k + 1) * (k + 1)) as f64)).unwrap();
+        assert!(is_close(basel, PI * PI / 6.0, 1e-8));
+        let alternating = sum(&mut |k| Ok(if k % 2 == 0 { 1.0 } else { -1.0 } / (k + 1) as f64)).unwrap();
+        assert!(is_close(alternating, LN_2, 1e-12));
+        let wallis = product(&mut |k| Ok(1.0 - 1.0 / ((k + 2) * (k + 2)) as f64)).unwrap();
+        assert!(is_close(wallis, 0.5, 1e-8));
+        let lorentzian = sum(&mut |k| Ok(1.0 / (1 + k * k) as f64)).unwrap();
+        assert!(is_close(lorentzian, (1.0 + PI / PI.tanh()) / 2.0, 1e-8));
+
+        assert!(sum(&mut |k| Ok(1.0 / (k + 1) as f64)).is_err());
+        assert!(sum(&mut |k| Ok(1.0 / ((k + 1) as f64).sqrt())).is_err());
+        assert!(sum(&mut |k| Ok(if k % 2 == 0 { 1.0 } else { -1.0 })).is_err());
+    }
+}
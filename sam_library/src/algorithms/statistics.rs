@@ -0,0 +1,135 @@
+use crate::errors::{ErrorWithMessage, SamError};
+
+pub fn mean(xs: &[f64]) -> Result<f64, SamError> {
+    check_not_empty(xs)?;
+    Ok(xs.iter().sum::<f64>() / xs.len() as f64)
+}
+
+pub fn median(xs: &[f64]) -> Result<f64, SamError> {
+    quantile(xs, 0.5)
+}
+
+/// The most frequent value, and the smallest of them if there is a tie.
+pub fn mode(xs: &[f64]) -> Result<f64, SamError> {
+    let sorted = sorted(xs)?;
+    let mut best = (sorted[0], 0);
+    let mut start = 0;
+    for i in 1..=sorted.len() {
+        if i == sorted.len() || sorted[i] != sorted[start] {
+            if i - start > best.1 {
+                best = (sorted[start], i - start);
+            }
+            start = i;
+        }
+    }
+    Ok(best.0)
+}
+
+/// The sample variance, dividing by `n - 1`.
+pub fn variance(xs: &[f64]) -> Result<f64, SamError> {
+    covariance(xs, xs)
+}
+
+/// The sample standard deviation.
+pub fn std_dev(xs: &[f64]) -> Result<f64, SamError> {
+    Ok(variance(xs)?.sqrt())
+}
+
+/// The `q`-th quantile for `q` between 0 and 1, interpolating linearly
+/// between the closest ranks.
+pub fn quantile(xs: &[f64], q: f64) -> Result<f64, SamError> {
+    if !(0.0..=1.0).contains(&q) {
+        return Err(ErrorWithMessage::new_box(&format!("quantile {} is not between 0 and 1", q)));
+    }
+    let sorted = sorted(xs)?;
+    let position = q * (sorted.len() - 1) as f64;
+    let (below, above) = (position.floor() as usize, position.ceil() as usize);
+    Ok(sorted[below] + (position - below as f64) * (sorted[above] - sorted[below]))
+}
+
+/// The `p`-th percentile for `p` between 0 and 100.
+pub fn percentile(xs: &[f64], p: f64) -> Result<f64, SamError> {
+    if !(0.0..=100.0).contains(&p) {
+        return Err(ErrorWithMessage::new_box(&format!("percentile {} is not between 0 and 100", p)));
+    }
+    quantile(xs, p / 100.0)
+}
+
+/// The sample covariance of paired data.
+pub fn covariance(xs: &[f64], ys: &[f64]) -> Result<f64, SamError> {
+    check_paired(xs, ys)?;
+    if xs.len() < 2 {
+        return Err(ErrorWithMessage::new_box("need at least two values"));
+    }
+    let (mx, my) = (mean(xs)?, mean(ys)?);
+    Ok(xs.iter().zip(ys).map(|(x, y)| (x - mx) * (y - my)).sum::<f64>() / (xs.len() - 1) as f64)
+}
+
+/// Pearson's correlation coefficient.
+pub fn correlation(xs: &[f64], ys: &[f64]) -> Result<f64, SamError> {
+    let spread = (variance(xs)? * variance(ys)?).sqrt();
+    if spread == 0.0 {
+        return Err(ErrorWithMessage::new_box("correlation is undefined for constant data"));
+    }
+    Ok(covariance(xs, ys)? / spread)
+}
+
+/// The least squares line through paired data as `(slope, intercept, r²)`.
+pub fn linear_regression(xs: &[f64], ys: &[f64]) -> Result<(f64, f64, f64), SamError> {
+    let vx = variance(xs)?;
+    if vx == 0.0 {
+        return Err(ErrorWithMessage::new_box("linreg needs at least two different x values"));
+    }
+    let slope = covariance(xs, ys)? / vx;
+    let intercept = mean(ys)? - slope * mean(xs)?;
+    let vy = variance(ys)?;
+    let r_squared = match vy == 0.0 {
+        true => 1.0,
+        false => slope * slope * vx / vy,
+    };
+    Ok((slope, intercept, r_squared))
+}
+
+fn sorted(xs: &[f64]) -> Result<Vec<f64>, SamError> {
+    check_not_empty(xs)?;
+    let mut sorted = xs.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    Ok(sorted)
+}
+
+fn check_not_empty(xs: &[f64]) -> Result<(), SamError> {
+    match xs.is_empty() {
+        true => Err(ErrorWithMessage::new_box("need at least one value")),
+        false => Ok(()),
+    }
+}
+
+fn check_paired(xs: &[f64], ys: &[f64]) -> Result<(), SamError> {
+    match xs.len() == ys.len() {
+        true => Ok(()),
+        false => Err(ErrorWithMessage::new_box(&format!(
+            "paired data must have the same length, not {} and {}",
+            xs.len(),
+            ys.len()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{linear_regression, median, mode, percentile, std_dev, variance};
+
+    #[test]
+    fn test_statistics() {
+        let xs = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        assert!(median(&xs).unwrap() == 4.5);
+        assert!(mode(&xs).unwrap() == 4.0);
+        assert!((variance(&xs).unwrap() - 32.0 / 7.0).abs() < 1e-12);
+        assert!((std_dev(&xs).unwrap() - (32.0_f64 / 7.0).sqrt()).abs() < 1e-12);
+        assert!(percentile(&[1.0, 2.0, 3.0, 4.0], 25.0).unwrap() == 1.75);
+        assert!(median(&[]).is_err());
+
+        let (slope, intercept, r_squared) = linear_regression(&[1.0, 2.0, 3.0], &[3.0, 5.0, 7.0]).unwrap();
+        assert!((slope - 2.0).abs() < 1e-12 && (intercept - 1.0).abs() < 1e-12 && (r_squared - 1.0).abs() < 1e-12);
+    }
+}
@@ -1,4 +1,93 @@
-use crate::interpreter::data_types::Real;
+use std::{
+    f64::consts::TAU,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use crate::{
+    errors::{ErrorWithMessage, SamError},
+    interpreter::data_types::Real,
+};
+
+/// The unit that trigonometric functions take angles in, and that inverse
+/// trigonometric functions give them in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AngleMode {
+    #[default]
+    Radians,
+    Degrees,
+    Gradians,
+}
+
+impl AngleMode {
+    pub fn full_turn(self) -> f64 {
+        match self {
+            AngleMode::Radians => TAU,
+            AngleMode::Degrees => 360.0,
+            AngleMode::Gradians => 400.0,
+        }
+    }
+
+    /// How many radians one unit of this mode is.
+    pub fn radians_per_unit(self) -> f64 {
+        TAU / self.full_turn()
+    }
+
+    /// `f` of an angle given in this mode.
+    pub fn apply(self, f: fn(Real) -> Real, angle: Real) -> Real {
+        if self == AngleMode::Radians {
+            return f(angle);
+        }
+        // Reducing by whole turns first keeps angles such as -30° exact.
+        let full_turn = self.full_turn();
+        let mut angle = Into::<f64>::into(angle).rem_euclid(full_turn);
+        if angle > full_turn / 2.0 {
+            angle -= full_turn;
+        }
+        Real::Float(snap(f(Real::Float(angle * self.radians_per_unit())).into(), 0.5))
+    }
+
+    /// The inverse trigonometric function `f` with its result in this mode.
+    pub fn apply_inverse(self, f: fn(Real) -> Real, x: Real) -> Real {
+        match self {
+            AngleMode::Radians => f(x),
+            _ => Real::Float(snap(Into::<f64>::into(f(x)) / self.radians_per_unit(), 1.0)),
+        }
+    }
+}
+
+/// Converting an angle to radians rounds it, which leaves `sin(30°)` a hair
+/// off 0.5. Results within a few ulps of a multiple of `step` are snapped to
+/// it.
+fn snap(x: f64, step: f64) -> f64 {
+    let nearest = (x / step).round() * step;
+    match (x - nearest).abs() <= 4.0 * f64::EPSILON * nearest.abs().max(1.0) {
+        true => nearest,
+        false => x,
+    }
+}
+
+impl Display for AngleMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AngleMode::Radians => write!(f, "rad"),
+            AngleMode::Degrees => write!(f, "deg"),
+            AngleMode::Gradians => write!(f, "grad"),
+        }
+    }
+}
+
+impl FromStr for AngleMode {
+    type Err = SamError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rad" | "radians" => Ok(AngleMode::Radians),
+            "deg" | "degrees" => Ok(AngleMode::Degrees),
+            "grad" | "gradians" => Ok(AngleMode::Gradians),
+            _ => Err(ErrorWithMessage::new_box(&format!("unknown angle mode {}, expected rad, deg or grad", s))),
+        }
+    }
+}
 
 pub fn sin(x: Real) -> Real {
     return match x {
@@ -64,4 +153,50 @@ pub fn atan(x: Real) -> Real {
             Real::Float((x as f64).atan())
         }
     }
-}
\ No newline at end of file
+}
+
+pub fn sinh(x: Real) -> Real {
+    float(x, f64::sinh)
+}
+
+pub fn asinh(x: Real) -> Real {
+    float(x, f64::asinh)
+}
+
+pub fn cosh(x: Real) -> Real {
+    float(x, f64::cosh)
+}
+
+pub fn acosh(x: Real) -> Real {
+    float(x, f64::acosh)
+}
+
+pub fn tanh(x: Real) -> Real {
+    float(x, f64::tanh)
+}
+
+pub fn atanh(x: Real) -> Real {
+    float(x, f64::atanh)
+}
+
+pub fn sec(x: Real) -> Real {
+    float(x, |x| 1.0 / x.cos())
+}
+
+pub fn csc(x: Real) -> Real {
+    float(x, |x| 1.0 / x.sin())
+}
+
+pub fn cot(x: Real) -> Real {
+    float(x, |x| 1.0 / x.tan())
+}
+
+/// The angle of the point `(x, y)` from the positive x axis, between `-π`
+/// and `π`.
+pub fn atan2(y: Real, x: Real) -> Real {
+    Real::Float(Into::<f64>::into(y).atan2(x.into()))
+}
+
+fn float(x: Real, f: fn(f64) -> f64) -> Real {
+    Real::Float(f(x.into()))
+}
@@ -1,3 +0,0 @@
-use super::operations::Operation;
-
-pub type AstBlock = Vec<Operation>;
\ No newline at end of file
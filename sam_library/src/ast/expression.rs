@@ -0,0 +1,113 @@
+use std::{
+    fmt::{Display, Formatter, Result},
+    rc::Rc,
+};
+
+use crate::{algorithms::big_integer::BigInt, interpreter::data_types::Real};
+
+use super::operators::{BinaryOp, UnaryOp, UNARY_PRECEDENCE};
+
+/// A parsed statement or expression. The parser produces one tree per line
+/// of input, which the compiler then lowers to bytecode.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Literal(Real),
+    /// An integer literal too large for a `Real`.
+    BigInteger(Rc<BigInt>),
+    Variable(String),
+    PeekStack,
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    List(Vec<Expr>),
+    Assignment(String, Box<Expr>),
+    FunctionDeclaration(String, Vec<String>, Box<Expr>),
+    Lambda(Vec<String>, Box<Expr>),
+}
+
+const ATOM_PRECEDENCE: u8 = u8::MAX;
+
+impl Expr {
+    pub fn unary(op: UnaryOp, operand: Expr) -> Expr {
+        Expr::Unary(op, Box::new(operand))
+    }
+
+    pub fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+        Expr::Binary(op, Box::new(lhs), Box::new(rhs))
+    }
+
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Expr::Literal(x) if *x < Real::Int(0) => UNARY_PRECEDENCE,
+            Expr::BigInteger(n) if n.is_negative() => UNARY_PRECEDENCE,
+            Expr::Literal(_) | Expr::BigInteger(_) | Expr::Variable(_) | Expr::PeekStack | Expr::Call(_, _) | Expr::List(_) => ATOM_PRECEDENCE,
+            Expr::Unary(_, _) => UNARY_PRECEDENCE,
+            Expr::Binary(op, _, _) => op.precedence(),
+            Expr::Conditional(_, _, _) | Expr::Assignment(_, _) | Expr::FunctionDeclaration(_, _, _) | Expr::Lambda(_, _) => 0,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut Formatter<'_>, parenthesize: bool) -> Result {
+        if parenthesize {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Expr::Literal(x) => write!(f, "{}", x.to_string()),
+            Expr::BigInteger(n) => write!(f, "{}", n),
+            Expr::Variable(name) => write!(f, "{}", name),
+            Expr::PeekStack => write!(f, "$"),
+            Expr::Unary(op, operand) => {
+                write!(f, "{}", op.symbol())?;
+                operand.fmt_operand(f, operand.precedence() <= UNARY_PRECEDENCE)
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let precedence = op.precedence();
+                let right_assoc = op.is_right_associative();
+                lhs.fmt_operand(f, lhs.precedence() < precedence || (right_assoc && lhs.precedence() == precedence))?;
+                match op {
+                    BinaryOp::Mul | BinaryOp::Div | BinaryOp::Pow => write!(f, "{}", op.symbol())?,
+                    _ => write!(f, " {} ", op.symbol())?,
+                }
+                rhs.fmt_operand(f, rhs.precedence() < precedence || (!right_assoc && rhs.precedence() == precedence))
+            }
+            Expr::Conditional(condition, then, otherwise) => {
+                condition.fmt_operand(f, condition.precedence() == 0)?;
+                write!(f, " ? {} : {}", then, otherwise)
+            }
+            Expr::Call(name, args) => {
+                write!(f, "{}(", name)?;
+                fmt_list(f, args)?;
+                write!(f, ")")
+            }
+            Expr::List(items) => {
+                write!(f, "[")?;
+                fmt_list(f, items)?;
+                write!(f, "]")
+            }
+            Expr::Assignment(name, value) => write!(f, "{} = {}", name, value),
+            Expr::FunctionDeclaration(name, params, body) => {
+                write!(f, "{}({}) = {}", name, params.join(", "), body)
+            }
+            Expr::Lambda(params, body) if params.len() == 1 => write!(f, "{} -> {}", params[0], body),
+            Expr::Lambda(params, body) => write!(f, "({}) -> {}", params.join(", "), body),
+        }
+    }
+}
+
+fn fmt_list(f: &mut Formatter<'_>, items: &[Expr]) -> Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
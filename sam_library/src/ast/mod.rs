@@ -1,3 +1,2 @@
-pub mod operations;
-pub mod ast_block;
-pub mod user_functions;
+pub mod expression;
+pub mod operators;
@@ -0,0 +1,127 @@
+use crate::{algorithms::logarithms::pow, interpreter::data_types::Real};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Mod,
+    BitAnd,
+    BitOr,
+    RightShift,
+    LeftShift,
+    Lt,
+    Gt,
+    Lte,
+    Gte,
+    Neq,
+    Eq,
+    BoolAnd,
+    BoolOr,
+    /// `a = b` inside an expression, as taken by `solve`.
+    Equation,
+    /// `a \ b` is `b / a`, or the solution of `a x = b` for a matrix `a`.
+    LeftDiv,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    BitCompliment,
+}
+
+/// Prefix operators bind tighter than every binary operator except `**`,
+/// so `-2 ** 2` is `-(2 ** 2)` and `-a * b` is `(-a) * b`.
+pub const UNARY_PRECEDENCE: u8 = 10;
+
+impl BinaryOp {
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Self::Equation => 0,
+            Self::BoolOr => 1,
+            Self::BoolAnd => 2,
+            Self::Lt | Self::Gt | Self::Lte | Self::Gte | Self::Eq | Self::Neq => 3,
+            Self::BitOr => 4,
+            Self::BitAnd => 6,
+            Self::RightShift | Self::LeftShift => 7,
+            Self::Add | Self::Sub => 8,
+            Self::Mul | Self::Div | Self::Mod | Self::LeftDiv => 9,
+            Self::Pow => 11,
+        }
+    }
+
+    pub fn is_right_associative(&self) -> bool {
+        *self == Self::Pow
+    }
+
+    pub fn apply(&self, a: Real, b: Real) -> Real {
+        match self {
+            Self::Add => a + b,
+            Self::Sub => a - b,
+            Self::Mul => a * b,
+            Self::Div => a / b,
+            Self::Pow => pow(a, b),
+            Self::Mod => a % b,
+            Self::BitAnd => a & b,
+            Self::BitOr => a | b,
+            Self::RightShift => a >> b,
+            Self::LeftShift => a << b,
+            Self::Lt => Real::Int((a < b) as i64),
+            Self::Gt => Real::Int((a > b) as i64),
+            Self::Lte => Real::Int((a <= b) as i64),
+            Self::Gte => Real::Int((a >= b) as i64),
+            Self::Neq => Real::Int((a != b) as i64),
+            Self::Eq => Real::Int((a == b) as i64),
+            Self::BoolAnd => Real::Int((a != Real::Int(0) && b != Real::Int(0)) as i64),
+            Self::BoolOr => Real::Int((a != Real::Int(0) || b != Real::Int(0)) as i64),
+            Self::Equation => Real::Int((a == b) as i64),
+            Self::LeftDiv => b / a,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Pow => "^",
+            Self::Mod => "%",
+            Self::BitAnd => "&",
+            Self::BitOr => "|",
+            Self::RightShift => ">>",
+            Self::LeftShift => "<<",
+            Self::Lt => "<",
+            Self::Gt => ">",
+            Self::Lte => "<=",
+            Self::Gte => ">=",
+            Self::Neq => "<>",
+            Self::Eq => "==",
+            Self::BoolAnd => "&&",
+            Self::BoolOr => "||",
+            Self::Equation => "=",
+            Self::LeftDiv => "\\",
+        }
+    }
+}
+
+impl UnaryOp {
+    pub fn apply(&self, x: Real) -> Real {
+        match self {
+            Self::Neg => -x,
+            Self::Not => Real::Int((x == Real::Int(0)) as i64),
+            Self::BitCompliment => !x,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Neg => "-",
+            Self::Not => "!",
+            Self::BitCompliment => "~",
+        }
+    }
+}
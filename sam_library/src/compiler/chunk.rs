@@ -0,0 +1,104 @@
+use std::rc::Rc;
+
+use crate::{
+    ast::expression::Expr,
+    errors::{ErrorWithMessage, SamError},
+    interpreter::data_types::{Real, Value},
+    util::hash_str::hash_str,
+};
+
+use super::instructions::Instruction;
+
+/// Compiled bytecode for a user function or for a whole line of input.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Chunk {
+    pub name: String,
+    pub parameters: Vec<String>,
+    /// The expression a user function was compiled from, kept so that its
+    /// derivatives can be taken later.
+    pub body: Option<Rc<Expr>>,
+    /// The values of the last parameters, bound when a lambda that uses the
+    /// parameters of an enclosing function is created.
+    pub captured: Vec<Value>,
+    pub code: Vec<Instruction>,
+    pub constants: Vec<Value>,
+    pub names: Vec<u64>,
+    /// The names as written, parallel to `names`.
+    pub symbols: Vec<String>,
+    pub functions: Vec<Rc<Chunk>>,
+}
+
+impl Chunk {
+    pub fn new(name: &str, parameters: Vec<String>) -> Chunk {
+        Chunk {
+            name: name.to_owned(),
+            parameters,
+            ..Default::default()
+        }
+    }
+
+    /// The number of arguments a call passes, not counting captured values.
+    pub fn arity(&self) -> usize {
+        self.parameters.len() - self.captured.len()
+    }
+
+    pub fn add_constant(&mut self, value: Value) -> Result<u16, SamError> {
+        let existing = self.constants.iter().position(|constant| {
+            match (constant, &value) {
+                (Value::Real(Real::Int(a)), Value::Real(Real::Int(b))) => a == b,
+                (Value::Real(Real::Float(a)), Value::Real(Real::Float(b))) => a.to_bits() == b.to_bits(),
+                _ => false,
+            }
+        });
+        match existing {
+            Some(index) => Ok(index as u16),
+            None => {
+                self.constants.push(value);
+                pool_index(self.constants.len() - 1)
+            }
+        }
+    }
+
+    pub fn add_name(&mut self, name: &str) -> Result<u16, SamError> {
+        let key = hash_str(name);
+        match self.names.iter().position(|name| *name == key) {
+            Some(index) => Ok(index as u16),
+            None => {
+                self.names.push(key);
+                self.symbols.push(name.to_owned());
+                pool_index(self.names.len() - 1)
+            }
+        }
+    }
+
+    pub fn add_function(&mut self, function: Chunk) -> Result<u16, SamError> {
+        self.functions.push(Rc::new(function));
+        pool_index(self.functions.len() - 1)
+    }
+
+    /// A call is in tail position when nothing but jumps separate it from
+    /// the `Return` of its chunk.
+    pub fn mark_tail_calls(&mut self) {
+        for pc in 0..self.code.len() {
+            if let Instruction::Call(name, arg_count) = self.code[pc] {
+                if self.returns_from(pc + 1) {
+                    self.code[pc] = Instruction::TailCall(name, arg_count);
+                }
+            }
+        }
+    }
+
+    fn returns_from(&self, mut pc: usize) -> bool {
+        loop {
+            match self.code.get(pc) {
+                Some(Instruction::Return) => return true,
+                Some(Instruction::Jump(target)) if *target as usize > pc => pc = *target as usize,
+                _ => return false,
+            }
+        }
+    }
+}
+
+fn pool_index(index: usize) -> Result<u16, SamError> {
+    u16::try_from(index).map_err(|_| ErrorWithMessage::new_box("too many constants in one chunk"))
+}
@@ -0,0 +1,269 @@
+use std::rc::Rc;
+
+use crate::{
+    ast::{
+        expression::Expr,
+        operators::{BinaryOp, UnaryOp},
+    },
+    errors::{ErrorWithMessage, SamError},
+    interpreter::data_types::{Real, Value},
+    symbolic::{
+        derivative::{builtin_derivative, differentiate, expand_diff, split_primes, takes_angle},
+        substitute, var,
+    },
+};
+
+use super::{chunk::Chunk, compile_function, instructions::Instruction};
+
+/// Lowers an expression tree into a `Chunk`.
+pub struct CodeGenerator {
+    chunk: Chunk,
+    parameters: Vec<String>,
+}
+
+impl CodeGenerator {
+    pub fn new(name: &str, parameters: Vec<String>) -> CodeGenerator {
+        CodeGenerator {
+            chunk: Chunk::new(name, parameters.clone()),
+            parameters,
+        }
+    }
+
+    pub fn generate(mut self, expr: &Expr) -> Result<Chunk, SamError> {
+        self.lower(expr)?;
+        self.emit(Instruction::Return);
+        Ok(self.chunk)
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        self.chunk.code.push(instruction);
+    }
+
+    fn emit_constant(&mut self, value: Value) -> Result<(), SamError> {
+        let index = self.chunk.add_constant(value)?;
+        self.emit(Instruction::Constant(index));
+        Ok(())
+    }
+
+    /// Emits a jump with a placeholder target and returns its position so
+    /// it can be patched once the target is known.
+    fn emit_jump(&mut self, instruction: Instruction) -> usize {
+        self.emit(instruction);
+        self.chunk.code.len() - 1
+    }
+
+    fn patch_jump(&mut self, at: usize) {
+        let target = self.chunk.code.len() as u32;
+        self.chunk.code[at] = match self.chunk.code[at] {
+            Instruction::Jump(_) => Instruction::Jump(target),
+            _ => Instruction::JumpIfFalse(target),
+        };
+    }
+
+    fn lower(&mut self, expr: &Expr) -> Result<(), SamError> {
+        match expr {
+            Expr::Literal(x) => self.emit_constant(Value::Real(*x))?,
+            Expr::BigInteger(n) => self.emit_constant(Value::BigInt(Rc::clone(n)))?,
+            Expr::PeekStack => self.emit(Instruction::PeekStack),
+            Expr::Variable(name) => {
+                let instruction = match self.parameters.iter().position(|param| param == name) {
+                    Some(slot) => Instruction::LoadLocal(slot as u16),
+                    None => Instruction::LoadGlobal(self.chunk.add_name(name)?),
+                };
+                self.emit(instruction);
+            }
+            Expr::Unary(op, operand) => {
+                self.lower(operand)?;
+                self.emit(match op {
+                    UnaryOp::Neg => Instruction::Neg,
+                    UnaryOp::Not => Instruction::Not,
+                    UnaryOp::BitCompliment => Instruction::BitCompliment,
+                });
+            }
+            Expr::Binary(BinaryOp::BoolAnd, lhs, rhs) => self.lower_logical(lhs, rhs, true)?,
+            Expr::Binary(BinaryOp::BoolOr, lhs, rhs) => self.lower_logical(lhs, rhs, false)?,
+            Expr::Binary(op, lhs, rhs) => {
+                self.lower(lhs)?;
+                self.lower(rhs)?;
+                self.emit(binary_instruction(*op));
+            }
+            Expr::Conditional(condition, then, otherwise) => {
+                self.lower(condition)?;
+                let jump_to_else = self.emit_jump(Instruction::JumpIfFalse(0));
+                self.lower(then)?;
+                let jump_to_end = self.emit_jump(Instruction::Jump(0));
+                self.patch_jump(jump_to_else);
+                self.lower(otherwise)?;
+                self.patch_jump(jump_to_end);
+            }
+            Expr::Call(name, args) if name == "diff" => self.lower_diff(args)?,
+            Expr::Call(name, args) if split_primes(name).1 > 0 => self.lower_derivative_call(name, args)?,
+            Expr::Call(name, args) => match series_index(name, args) {
+                Some(index) => self.lower_series(name, index, &args[1], &args[2], &args[3])?,
+                None => {
+                    for arg in args {
+                        self.lower(arg)?;
+                    }
+                    let arg_count = u8::try_from(args.len())
+                        .map_err(|_| ErrorWithMessage::new_box("too many arguments"))?;
+                    let name = self.chunk.add_name(name)?;
+                    self.emit(Instruction::Call(name, arg_count));
+                }
+            },
+            Expr::List(items) => {
+                for item in items {
+                    self.lower(item)?;
+                }
+                let length = u16::try_from(items.len()).map_err(|_| ErrorWithMessage::new_box("list literal is too long"))?;
+                self.emit(Instruction::MakeList(length));
+            }
+            Expr::Assignment(name, value) => {
+                self.lower(value)?;
+                let name = self.chunk.add_name(name)?;
+                self.emit(Instruction::StoreGlobal(name));
+            }
+            Expr::FunctionDeclaration(name, params, body) => {
+                let function = compile_function(name, params, body)?;
+                let function = self.chunk.add_function(function)?;
+                let name = self.chunk.add_name(name)?;
+                self.emit(Instruction::DefineFunction(name, function));
+            }
+            Expr::Lambda(params, body) => self.lower_lambda(params, body)?,
+        }
+        Ok(())
+    }
+
+    /// Inside a function, differentiating with respect to a parameter gives
+    /// code for the derivative. Anywhere else the first argument is evaluated
+    /// and the `diff` builtin differentiates its value, with the variable
+    /// passed as an expression in case it is bound.
+    fn lower_diff(&mut self, args: &[Expr]) -> Result<(), SamError> {
+        let (expr, name, order) = match args {
+            [_, Expr::Variable(name), ..] if self.parameters.contains(name) => return self.lower(&expand_diff(args)?),
+            [expr, Expr::Variable(name), order @ ..] if order.len() < 2 => (expr, name, order),
+            _ => return Err(ErrorWithMessage::new_box("usage: diff(expression, variable[, order])")),
+        };
+        self.lower(expr)?;
+        self.emit_constant(Value::Expression(Rc::new(var(name))))?;
+        for order in order {
+            self.lower(order)?;
+        }
+        let name = self.chunk.add_name("diff")?;
+        self.emit(Instruction::Call(name, 2 + order.len() as u8));
+        Ok(())
+    }
+
+    /// Derivatives of builtins are inlined; derivatives of user functions,
+    /// and of builtins that depend on the angle mode, are compiled by the VM
+    /// when first called.
+    fn lower_derivative_call(&mut self, name: &str, args: &[Expr]) -> Result<(), SamError> {
+        let (function, order) = split_primes(name);
+        let order = u8::try_from(order).map_err(|_| ErrorWithMessage::new_box("derivative order is too high"))?;
+        if let [arg] = args {
+            if builtin_derivative(function, arg).is_some() && !takes_angle(function) {
+                let mut derivative = Expr::Call(function.to_owned(), vec![var("x")]);
+                for _ in 0..order {
+                    derivative = differentiate(&derivative, "x")?;
+                }
+                return self.lower(&substitute(&derivative, "x", arg));
+            }
+        }
+
+        for arg in args {
+            self.lower(arg)?;
+        }
+        let arg_count = u8::try_from(args.len()).map_err(|_| ErrorWithMessage::new_box("too many arguments"))?;
+        let name = self.chunk.add_name(function)?;
+        self.emit(Instruction::CallDerivative(name, order, arg_count));
+        Ok(())
+    }
+
+    /// The body of `sum(k, a, b, body)` or `prod(k, a, b, body)` is passed
+    /// to the builtin as a function of `k`. It may use the parameters of the
+    /// function it appears in, so they are passed along after `b`.
+    fn lower_series(&mut self, name: &str, index: &str, from: &Expr, to: &Expr, body: &Expr) -> Result<(), SamError> {
+        let captured: Vec<String> = self.parameters.iter().filter(|param| *param != index).cloned().collect();
+        let params: Vec<String> = std::iter::once(index.to_owned()).chain(captured.iter().cloned()).collect();
+        let function = compile_function("lambda", &params, body)?;
+        self.emit_constant(Value::Function(Rc::new(function)))?;
+        self.lower(from)?;
+        self.lower(to)?;
+        for param in &captured {
+            self.lower(&Expr::Variable(param.clone()))?;
+        }
+        let arg_count = u8::try_from(3 + captured.len()).map_err(|_| ErrorWithMessage::new_box("too many arguments"))?;
+        let name = self.chunk.add_name(name)?;
+        self.emit(Instruction::Call(name, arg_count));
+        Ok(())
+    }
+
+    /// A lambda may use the parameters of the function it appears in. They
+    /// become extra parameters of the lambda, bound to their values when the
+    /// lambda is created, as `lower_series` does for the body of a sum.
+    fn lower_lambda(&mut self, params: &[String], body: &Expr) -> Result<(), SamError> {
+        let captured: Vec<String> = self.parameters.iter().filter(|param| !params.contains(param)).cloned().collect();
+        let all_params: Vec<String> = params.iter().chain(&captured).cloned().collect();
+        let function = compile_function("lambda", &all_params, body)?;
+        self.emit_constant(Value::Function(Rc::new(function)))?;
+        if captured.is_empty() {
+            return Ok(());
+        }
+        for param in &captured {
+            self.lower(&Expr::Variable(param.clone()))?;
+        }
+        let count = u8::try_from(captured.len()).map_err(|_| ErrorWithMessage::new_box("too many parameters to capture"))?;
+        self.emit(Instruction::MakeClosure(count));
+        Ok(())
+    }
+
+    /// `&&` and `||` only evaluate their right operand when it can change
+    /// the result, and always produce `1` or `0`.
+    fn lower_logical(&mut self, lhs: &Expr, rhs: &Expr, is_and: bool) -> Result<(), SamError> {
+        self.lower(lhs)?;
+        if !is_and {
+            self.emit(Instruction::Not);
+        }
+        let short_circuit = self.emit_jump(Instruction::JumpIfFalse(0));
+        self.lower(rhs)?;
+        self.emit(Instruction::Not);
+        self.emit(Instruction::Not);
+        let jump_to_end = self.emit_jump(Instruction::Jump(0));
+        self.patch_jump(short_circuit);
+        self.emit_constant(Value::Real(Real::Int(if is_and { 0 } else { 1 })))?;
+        self.patch_jump(jump_to_end);
+        Ok(())
+    }
+}
+
+/// The index variable of `sum(k, a, b, body)` or `prod(k, a, b, body)`.
+/// With any other arguments these add up or multiply numbers.
+pub fn series_index<'a>(name: &str, args: &'a [Expr]) -> Option<&'a str> {
+    match args {
+        [Expr::Variable(index), _, _, _] if name == "sum" || name == "prod" => Some(index),
+        _ => None,
+    }
+}
+
+fn binary_instruction(op: BinaryOp) -> Instruction {
+    match op {
+        BinaryOp::Add => Instruction::Add,
+        BinaryOp::Sub => Instruction::Sub,
+        BinaryOp::Mul => Instruction::Mul,
+        BinaryOp::Div => Instruction::Div,
+        BinaryOp::LeftDiv => Instruction::LeftDiv,
+        BinaryOp::Pow => Instruction::Pow,
+        BinaryOp::Mod => Instruction::Mod,
+        BinaryOp::BitAnd => Instruction::BitAnd,
+        BinaryOp::BitOr => Instruction::BitOr,
+        BinaryOp::RightShift => Instruction::RightShift,
+        BinaryOp::LeftShift => Instruction::LeftShift,
+        BinaryOp::Lt => Instruction::Lt,
+        BinaryOp::Gt => Instruction::Gt,
+        BinaryOp::Lte => Instruction::Lte,
+        BinaryOp::Gte => Instruction::Gte,
+        BinaryOp::Neq => Instruction::Neq,
+        BinaryOp::Eq => Instruction::Eq,
+        BinaryOp::Equation => Instruction::Equation,
+        BinaryOp::BoolAnd | BinaryOp::BoolOr => unreachable!("logical operators short-circuit"),
+    }
+}
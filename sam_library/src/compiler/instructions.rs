@@ -0,0 +1,44 @@
+/// A single VM instruction. Operands index into the pools of the `Chunk`
+/// being executed, so every instruction stays small and `Copy`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Constant(u16),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    LeftDiv,
+    Pow,
+    Mod,
+    BitAnd,
+    BitOr,
+    RightShift,
+    LeftShift,
+    Neg,
+    Not,
+    BitCompliment,
+    Lt,
+    Gt,
+    Lte,
+    Gte,
+    Neq,
+    Eq,
+    Equation,
+    PeekStack,
+    /// Collects the given number of values from the stack into a list.
+    MakeList(u16),
+    LoadGlobal(u16),
+    StoreGlobal(u16),
+    LoadLocal(u16),
+    Jump(u32),
+    JumpIfFalse(u32),
+    Call(u16, u8),
+    TailCall(u16, u8),
+    /// Calls the derivative of the given order of a user function.
+    CallDerivative(u16, u8, u8),
+    DefineFunction(u16, u16),
+    /// Binds the given number of values from the stack to the function
+    /// below them, making a closure.
+    MakeClosure(u8),
+    Return,
+}
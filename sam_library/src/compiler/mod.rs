@@ -0,0 +1,48 @@
+mod chunk;
+mod code_generator;
+mod instructions;
+pub(crate) mod optimizer;
+
+pub use chunk::Chunk;
+pub use instructions::Instruction;
+
+use std::rc::Rc;
+
+use crate::{ast::expression::Expr, errors::SamError};
+
+use code_generator::CodeGenerator;
+
+pub fn compile(expr: &Expr) -> Result<Chunk, SamError> {
+    CodeGenerator::new("<input>", vec![]).generate(expr)
+}
+
+/// Compiles the body of a user function. The body is kept in the chunk so
+/// the function can be differentiated later.
+pub fn compile_function(name: &str, parameters: &[String], body: &Expr) -> Result<Chunk, SamError> {
+    let mut function = CodeGenerator::new(name, parameters.to_vec()).generate(body)?;
+    function.body = Some(Rc::new(body.clone()));
+    function.mark_tail_calls();
+    Ok(function)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::parse_input;
+
+    use super::{compile, Instruction};
+
+    #[test]
+    fn test_constant_pool_and_tail_calls() {
+        let chunk = compile(&parse_input("2 * 2 + 2").unwrap()).unwrap();
+        assert!(chunk.constants.len() == 1);
+        assert!(chunk.code == vec![
+            Instruction::Constant(0), Instruction::Constant(0), Instruction::Mul,
+            Instruction::Constant(0), Instruction::Add, Instruction::Return]);
+
+        let chunk = compile(&parse_input("f(n) = n > 1 ? f(n - 1) : 1").unwrap()).unwrap();
+        let function = &chunk.functions[0];
+        assert!(function.arity() == 1);
+        assert!(function.code.contains(&Instruction::TailCall(0, 1)));
+        assert!(!function.code.iter().any(|op| matches!(op, Instruction::Call(_, _))));
+    }
+}
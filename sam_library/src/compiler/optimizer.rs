@@ -0,0 +1,212 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    ast::{
+        expression::Expr,
+        operators::{BinaryOp, UnaryOp},
+    },
+    interpreter::{
+        builtin_functions::{exact_integer_builtin, Func},
+        data_types::{Real, Value},
+    },
+    util::hash_str::hash_str,
+};
+
+use super::code_generator::series_index;
+
+/// Simplifies an expression tree before it is compiled. Constants and pure
+/// builtins are looked up before user definitions by the VM, so folding
+/// them here cannot change what a program means.
+pub struct Optimizer<'a> {
+    builtins: &'a HashMap<u64, Func>,
+    constants: &'a HashMap<u64, Real>,
+    parameters: Vec<String>,
+}
+
+impl<'a> Optimizer<'a> {
+    pub fn new(builtins: &'a HashMap<u64, Func>, constants: &'a HashMap<u64, Real>) -> Optimizer<'a> {
+        Optimizer {
+            builtins,
+            constants,
+            parameters: vec![],
+        }
+    }
+
+    pub fn optimize(&self, expr: &Expr) -> Expr {
+        match expr {
+            Expr::Literal(_) | Expr::BigInteger(_) | Expr::PeekStack => expr.clone(),
+            Expr::Variable(name) => {
+                if self.parameters.contains(name) {
+                    return expr.clone();
+                }
+                match self.constants.get(&hash_str(name)) {
+                    Some(value) => Expr::Literal(*value),
+                    None => expr.clone(),
+                }
+            }
+            Expr::Unary(op, operand) => match (op, self.optimize(operand)) {
+                (_, Expr::Literal(x)) => Expr::Literal(op.apply(x)),
+                (UnaryOp::Neg, Expr::Unary(UnaryOp::Neg, inner)) => *inner,
+                (UnaryOp::Neg, Expr::BigInteger(n)) => Expr::BigInteger(Rc::new(-&*n)),
+                (_, operand) => Expr::unary(*op, operand),
+            },
+            Expr::Binary(op, lhs, rhs) => self.simplify_binary(*op, self.optimize(lhs), self.optimize(rhs)),
+            Expr::Conditional(condition, then, otherwise) => match self.optimize(condition) {
+                Expr::Literal(x) if equals(x, 0) => self.optimize(otherwise),
+                Expr::Literal(_) => self.optimize(then),
+                condition => Expr::Conditional(
+                    Box::new(condition),
+                    Box::new(self.optimize(then)),
+                    Box::new(self.optimize(otherwise)),
+                ),
+            },
+            // `diff` works on the expression as written, see the code generator.
+            Expr::Call(name, _) if name == "diff" => expr.clone(),
+            Expr::Call(name, args) => match (series_index(name, args), args.as_slice()) {
+                // The index of `sum(k, a, b, body)` is a parameter of the body.
+                (Some(index), [_, from, to, body]) => {
+                    let scope = Optimizer {
+                        builtins: self.builtins,
+                        constants: self.constants,
+                        parameters: self.parameters.iter().cloned().chain([index.to_owned()]).collect(),
+                    };
+                    Expr::Call(name.clone(), vec![args[0].clone(), self.optimize(from), self.optimize(to), scope.optimize(body)])
+                }
+                _ => {
+                    let args: Vec<Expr> = args.iter().map(|arg| self.optimize(arg)).collect();
+                    self.fold_builtin(name, &args).unwrap_or_else(|| Expr::Call(name.clone(), args))
+                }
+            },
+            Expr::List(items) => Expr::List(items.iter().map(|item| self.optimize(item)).collect()),
+            Expr::Assignment(name, value) => Expr::Assignment(name.clone(), Box::new(self.optimize(value))),
+            Expr::FunctionDeclaration(name, params, body) => {
+                let scope = Optimizer {
+                    builtins: self.builtins,
+                    constants: self.constants,
+                    parameters: params.clone(),
+                };
+                Expr::FunctionDeclaration(name.clone(), params.clone(), Box::new(scope.optimize(body)))
+            }
+            // Parameters of the enclosing function are captured by the lambda.
+            Expr::Lambda(params, body) => {
+                let scope = Optimizer {
+                    builtins: self.builtins,
+                    constants: self.constants,
+                    parameters: params.iter().chain(&self.parameters).cloned().collect(),
+                };
+                Expr::Lambda(params.clone(), Box::new(scope.optimize(body)))
+            }
+        }
+    }
+
+    /// Folds a call as the VM would make it, including the exact integer
+    /// versions of builtins such as `abs`.
+    fn fold_builtin(&self, name: &str, args: &[Expr]) -> Option<Expr> {
+        let values: Vec<Real> = args.iter().map(literal_value).collect::<Option<_>>()?;
+        let builtin = self.builtins.get(&hash_str(name))?;
+        let exact: Vec<Value> = values.iter().map(|x| Value::Real(*x)).collect();
+        match exact_integer_builtin(name, &exact) {
+            Some(Value::Real(x)) => return Some(Expr::Literal(x)),
+            Some(Value::BigInt(n)) => return Some(Expr::BigInteger(n)),
+            _ => {}
+        }
+        let result = match (builtin, values.as_slice()) {
+            (Func::Monad(f), [x]) => f(*x),
+            (Func::Diad(f), [x, y]) => f(*x, *y),
+            (Func::Variadic(f), values) => f(values).ok()?,
+            _ => return None,
+        };
+        Some(Expr::Literal(result))
+    }
+
+    /// Folds an operation on two literals and drops `x + 0`, `x * 1` and the
+    /// like. Only adjacent literals fold, in the order they would be
+    /// evaluated, since regrouping floating point arithmetic changes results.
+    fn simplify_binary(&self, op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+        if let (Expr::Literal(a), Expr::Literal(b)) = (&lhs, &rhs) {
+            if can_fold(op, *a, *b) {
+                return Expr::Literal(op.apply(*a, *b));
+            }
+        }
+        let is = |operand: &Expr, n: i64| literal_value(operand).is_some_and(|x| equals(x, n));
+        match op {
+            BinaryOp::Add if is(&rhs, 0) && self.is_numeric(&lhs) => lhs,
+            BinaryOp::Add if is(&lhs, 0) && self.is_numeric(&rhs) => rhs,
+            BinaryOp::Mul if is(&rhs, 1) && self.is_numeric(&lhs) => lhs,
+            BinaryOp::Mul if is(&lhs, 1) && self.is_numeric(&rhs) => rhs,
+            BinaryOp::Sub if is(&rhs, 0) && self.is_numeric(&lhs) => lhs,
+            BinaryOp::Sub if is(&lhs, 0) && self.is_numeric(&rhs) => Expr::unary(UnaryOp::Neg, rhs),
+            BinaryOp::Div | BinaryOp::Pow if is(&rhs, 1) && self.is_numeric(&lhs) => lhs,
+            _ => Expr::binary(op, lhs, rhs),
+        }
+    }
+
+    /// Whether `expr` certainly gives a number, for which identities such as
+    /// `x + 0` hold; a variable might hold a list. Builtins of one or two
+    /// numbers give a number, or an expression that the identity suits just
+    /// as well.
+    fn is_numeric(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Literal(_) | Expr::BigInteger(_) => true,
+            Expr::Unary(_, operand) => self.is_numeric(operand),
+            Expr::Binary(BinaryOp::Equation, _, _) => false,
+            Expr::Binary(_, lhs, rhs) => self.is_numeric(lhs) && self.is_numeric(rhs),
+            Expr::Call(name, _) => matches!(
+                self.builtins.get(&hash_str(name)),
+                Some(Func::Monad(_) | Func::Diad(_) | Func::Trig(_) | Func::InverseTrig(_))
+            ),
+            _ => false,
+        }
+    }
+}
+
+fn literal_value(expr: &Expr) -> Option<Real> {
+    match expr {
+        Expr::Literal(x) => Some(*x),
+        _ => None,
+    }
+}
+
+/// Compares numerically, so `0` and `0.0` both count as zero.
+fn equals(x: Real, n: i64) -> bool {
+    x == Real::Int(n)
+}
+
+/// Integer operations that would panic at runtime are left for the VM.
+pub(crate) fn can_fold(op: BinaryOp, a: Real, b: Real) -> bool {
+    match (op, a, b) {
+        (BinaryOp::Div | BinaryOp::Mod, Real::Int(x), Real::Int(y)) => x.checked_rem(y).is_some(),
+        (BinaryOp::Equation, _, _) => false,
+        (BinaryOp::RightShift | BinaryOp::LeftShift, _, _) => (0..64).contains(&Into::<i64>::into(b)),
+        (BinaryOp::Pow, Real::Int(x), Real::Int(y)) => y < 0 || u32::try_from(y).ok().and_then(|y| x.checked_pow(y)).is_some(),
+        (BinaryOp::Add, Real::Int(x), Real::Int(y)) => x.checked_add(y).is_some(),
+        (BinaryOp::Sub, Real::Int(x), Real::Int(y)) => x.checked_sub(y).is_some(),
+        (BinaryOp::Mul, Real::Int(x), Real::Int(y)) => x.checked_mul(y).is_some(),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parser::parse_input, SamVM};
+
+    fn simplify(input: &str) -> String {
+        SamVM::new().optimize(&parse_input(input).unwrap()).to_string()
+    }
+
+    #[test]
+    fn test_constant_folding() {
+        assert!(simplify("2 * 3 + 1") == "7");
+        assert!(simplify("area(r) = 2 * pi * r") == "area(r) = 6.283185307179586*r");
+        assert!(simplify("r * 2 * pi") == "r*2*3.141592653589793");
+        assert!(simplify("1e308 * x * 10").ends_with("*x*10"));
+        assert!(simplify("[1, 2] + 0") == "[1, 2] + 0");
+        assert!(simplify("s + 1 + 1") == "s + 1 + 1");
+        assert!(simplify("sin(x) * 1 + 0") == "sin(x)");
+        assert!(simplify("x * 1 + 0") == "x*1 + 0");
+        assert!(simplify("sqrt(16) + y") == "4 + y");
+        assert!(simplify("f(pi) = pi + 1") == "f(pi) = pi + 1");
+        assert!(simplify("1 ? a : b") == "a");
+        assert!(simplify("-100000000000000000000 * x") == "-100000000000000000000*x");
+    }
+}
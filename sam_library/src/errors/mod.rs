@@ -14,7 +14,7 @@ impl ErrorWithMessage {
         }
     }
 
-    pub fn new_box(message: &str) -> Box<ErrorWithMessage> {
+    pub fn new_box(message: &str) -> SamError {
         Box::new(ErrorWithMessage::new(message.to_owned()))
     }
 }
@@ -29,4 +29,34 @@ impl Error for ErrorWithMessage {
     fn description(&self) -> &str {
         &self.message
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub message: String,
+    /// The innermost frames followed by the outermost ones, with
+    /// `hidden_frames` left out in between when the call stack was deep.
+    pub backtrace: Vec<String>,
+    pub hidden_frames: usize,
+}
+
+impl RuntimeError {
+    pub fn new(message: String, backtrace: Vec<String>, hidden_frames: usize) -> RuntimeError {
+        RuntimeError { message, backtrace, hidden_frames }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        for (i, frame) in self.backtrace.iter().enumerate() {
+            if self.hidden_frames > 0 && i == self.backtrace.len() / 2 {
+                write!(f, "\n    ... {} more frames", self.hidden_frames)?;
+            }
+            write!(f, "\n    in {}", frame)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for RuntimeError {}
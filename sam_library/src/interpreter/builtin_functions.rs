@@ -1,26 +1,1260 @@
-use std::{collections::HashMap};
+use std::{collections::HashMap, rc::Rc};
 
-use crate::{util::hash_str::hash_str, algorithms::{trigonometry::*, logarithms::*}};
+use crate::{util::hash_str::hash_str, algorithms::{trigonometry::*, logarithms::*, roots::{self, find_roots, DEFAULT_TOLERANCE}, quadrature, differentiation, ode::{dormand_prince, DEFAULT_TOLERANCE as ODE_TOLERANCE}, optimization, least_squares, linalg::{self, Matrix}, statistics, distributions::Distribution, random::Rng, special, number_theory, combinatorics, rounding, series as infinite_series, polynomial::Polynomial, big_integer::BigInt}, ast::{expression::Expr, operators::BinaryOp}, compiler::compile_function, errors::{ErrorWithMessage, SamError}, symbolic::{self, add, algebra, mul, solve::{solve_linear_system, solve_polynomial, zero_form}, var, variables}};
 
-use super::data_types::Real;
+use super::{data_types::{Real, Value}, dual::Dual, virtual_machine::SamVM};
 
+#[derive(Clone, Copy)]
 pub enum Func {
     Monad(fn (Real) -> Real),
     Diad(fn (Real, Real) -> Real),
+    /// Builtins that work on whole values, or that need the VM to evaluate
+    /// something, receive all of their arguments at once.
+    Native(fn (&mut SamVM, Vec<Value>) -> Result<Value, SamError>),
+    /// Takes any number of numbers, either as separate arguments or as a
+    /// single list.
+    Variadic(fn (&[Real]) -> Result<Real, SamError>),
+    /// A trigonometric function, which takes its angle in the VM's angle
+    /// mode.
+    Trig(fn (Real) -> Real),
+    /// An inverse trigonometric function, which gives its angle in the VM's
+    /// angle mode.
+    InverseTrig(fn (Real) -> Real),
 }
 
 
 pub fn setup_builtins() -> HashMap<u64, Func> {
     let mut map: HashMap<u64, Func> = HashMap::new();
-    map.insert(hash_str("sin"), Func::Monad(|x| { sin(x) }));
-    map.insert(hash_str("asin"), Func::Monad(|x| { asin(x) }));
-    map.insert(hash_str("cos"), Func::Monad(|x| { cos(x) }));
-    map.insert(hash_str("acos"), Func::Monad(|x| { acos(x) }));
-    map.insert(hash_str("tan"), Func::Monad(|x| { tan(x) }));
-    map.insert(hash_str("atan"), Func::Monad(|x| { atan(x) }));
+    map.insert(hash_str("sin"), Func::Trig(|x| { sin(x) }));
+    map.insert(hash_str("asin"), Func::InverseTrig(|x| { asin(x) }));
+    map.insert(hash_str("cos"), Func::Trig(|x| { cos(x) }));
+    map.insert(hash_str("acos"), Func::InverseTrig(|x| { acos(x) }));
+    map.insert(hash_str("tan"), Func::Trig(|x| { tan(x) }));
+    map.insert(hash_str("atan"), Func::InverseTrig(|x| { atan(x) }));
+    map.insert(hash_str("atan2"), Func::Native(angle_of_point));
+    map.insert(hash_str("sec"), Func::Trig(sec));
+    map.insert(hash_str("csc"), Func::Trig(csc));
+    map.insert(hash_str("cot"), Func::Trig(cot));
+    map.insert(hash_str("deg"), Func::Native(|vm, args| convert_angle(vm, args, AngleMode::Degrees, "deg")));
+    map.insert(hash_str("rad"), Func::Native(|vm, args| convert_angle(vm, args, AngleMode::Radians, "rad")));
+    map.insert(hash_str("sinh"), Func::Monad(sinh));
+    map.insert(hash_str("asinh"), Func::Monad(asinh));
+    map.insert(hash_str("cosh"), Func::Monad(cosh));
+    map.insert(hash_str("acosh"), Func::Monad(acosh));
+    map.insert(hash_str("tanh"), Func::Monad(tanh));
+    map.insert(hash_str("atanh"), Func::Monad(atanh));
     map.insert(hash_str("log"), Func::Monad(|x| { log(x, Real::Float(10_f64)) }));
     map.insert(hash_str("ln"), Func::Monad(|x| { ln(x) }));
     map.insert(hash_str("log_base"), Func::Diad(|x, y| { log(x, y) }));
     map.insert(hash_str("sqrt"), Func::Monad(|x| { pow(x, Real::Float(0.5)) }));
+    map.insert(hash_str("exp"), Func::Monad(exp));
+    map.insert(hash_str("log2"), Func::Monad(log2));
+    map.insert(hash_str("cbrt"), Func::Monad(cbrt));
+    map.insert(hash_str("nthroot"), Func::Diad(nthroot));
+    map.insert(hash_str("hypot"), Func::Diad(hypot));
+    map.insert(hash_str("abs"), Func::Monad(rounding::abs));
+    map.insert(hash_str("sign"), Func::Monad(rounding::sign));
+    map.insert(hash_str("floor"), Func::Monad(rounding::floor));
+    map.insert(hash_str("ceil"), Func::Monad(rounding::ceil));
+    map.insert(hash_str("trunc"), Func::Monad(rounding::trunc));
+    map.insert(hash_str("frac"), Func::Monad(rounding::frac));
+    map.insert(hash_str("round"), Func::Variadic(round));
+    map.insert(hash_str("clamp"), Func::Variadic(|xs| match xs {
+        [x, low, high] => rounding::clamp(*x, *low, *high),
+        _ => Err(ErrorWithMessage::new_box("usage: clamp(x, low, high)")),
+    }));
+    map.insert(hash_str("xor"), Func::Diad(|x, y| { x ^ y }));
+    map.insert(hash_str("gamma"), Func::Monad(|x| Real::Float(special::gamma(x.into()))));
+    map.insert(hash_str("lgamma"), Func::Monad(|x| Real::Float(special::ln_gamma(x.into()))));
+    map.insert(hash_str("digamma"), Func::Monad(|x| Real::Float(special::digamma(x.into()))));
+    map.insert(hash_str("beta"), Func::Diad(|a, b| Real::Float(special::beta(a.into(), b.into()))));
+    map.insert(hash_str("erf"), Func::Monad(|x| Real::Float(special::erf(x.into()))));
+    map.insert(hash_str("erfc"), Func::Monad(|x| Real::Float(special::erfc(x.into()))));
+    map.insert(hash_str("erfinv"), Func::Monad(|x| Real::Float(special::erfinv(x.into()))));
+    map.insert(hash_str("zeta"), Func::Monad(|x| Real::Float(special::zeta(x.into()))));
+    map.insert(hash_str("besselj"), Func::Native(|_, args| bessel(args, "besselj", special::bessel_j)));
+    map.insert(hash_str("bessely"), Func::Native(|_, args| bessel(args, "bessely", special::bessel_y)));
+    map.insert(hash_str("fact"), Func::Native(factorial));
+    map.insert(hash_str("nCr"), Func::Native(choose));
+    map.insert(hash_str("choose"), Func::Native(choose));
+    map.insert(hash_str("nPr"), Func::Native(|_, args| {
+        let [n, k] = integers(&args, "nPr(n, k)")?;
+        Ok(Value::from_big_integer(combinatorics::permutations(natural(n, "nPr")?, natural(k, "nPr")?)?))
+    }));
+    map.insert(hash_str("multinomial"), Func::Native(|_, args| {
+        if args.is_empty() {
+            return Err(ErrorWithMessage::new_box("usage: multinomial(k1, k2, ...)"));
+        }
+        let groups = args.iter().map(|k| natural(k.as_integer()?, "multinomial")).collect::<Result<Vec<u64>, _>>()?;
+        Ok(Value::from_big_integer(combinatorics::multinomial(&groups)?))
+    }));
+    map.insert(hash_str("catalan"), Func::Native(|_, args| {
+        let [n] = integers(&args, "catalan(n)")?;
+        Ok(Value::from_big_integer(combinatorics::catalan(natural(n, "catalan")?)?))
+    }));
+    map.insert(hash_str("fib"), Func::Native(|_, args| {
+        let [n] = integers(&args, "fib(n)")?;
+        Ok(Value::from_big_integer(combinatorics::fibonacci(n)?))
+    }));
+    map.insert(hash_str("binomial"), Func::Native(binomial_expansion));
+    map.insert(hash_str("gcd"), Func::Native(|_, args| fold_integers(args, "gcd(a, b, ...)", BigInt::gcd)));
+    map.insert(hash_str("lcm"), Func::Native(|_, args| fold_integers(args, "lcm(a, b, ...)", BigInt::lcm)));
+    map.insert(hash_str("isprime"), Func::Native(|_, args| {
+        let [n] = integers(&args, "isprime(n)")?;
+        Ok(Value::Real(Real::Int((n >= 0 && number_theory::is_prime(n as u64)) as i64)))
+    }));
+    map.insert(hash_str("nextprime"), Func::Native(|_, args| {
+        let [n] = integers(&args, "nextprime(n)")?;
+        integer_value(number_theory::next_prime(n.max(1) as u64)?)
+    }));
+    map.insert(hash_str("modpow"), Func::Native(modpow));
+    map.insert(hash_str("modinv"), Func::Native(|_, args| {
+        let [a, m] = integers(&args, "modinv(a, m)")?;
+        Ok(Value::Real(Real::Int(number_theory::mod_inv(a, m)?)))
+    }));
+    map.insert(hash_str("totient"), Func::Native(|_, args| {
+        let [n] = integers(&args, "totient(n)")?;
+        integer_value(number_theory::totient(positive(n, "totient")?))
+    }));
+    map.insert(hash_str("divisors"), Func::Native(|_, args| {
+        let [n] = integers(&args, "divisors(n)")?;
+        let divisors = number_theory::divisors(positive(n, "divisors")?);
+        Ok(Value::List(Rc::new(divisors.into_iter().map(|d| Value::Real(Real::Int(d as i64))).collect())))
+    }));
+    map.insert(hash_str("isqrt"), Func::Native(|_, args| match args.as_slice() {
+        [Value::BigInt(n)] if !n.is_negative() => Ok(Value::from_big_integer(n.isqrt())),
+        _ => match integers(&args, "isqrt(n)")? {
+            [n] if n >= 0 => integer_value(number_theory::isqrt(n as u64)),
+            _ => Err(ErrorWithMessage::new_box("isqrt needs a number that is not negative")),
+        },
+    }));
+    map.insert(hash_str("eval"), Func::Native(eval));
+    map.insert(hash_str("diff"), Func::Native(diff));
+    map.insert(hash_str("grad"), Func::Native(grad));
+    map.insert(hash_str("expand"), Func::Native(|_, args| rewrite_with(args, "expand", algebra::expand)));
+    map.insert(hash_str("simplify"), Func::Native(|_, args| rewrite_with(args, "simplify", algebra::simplify)));
+    map.insert(hash_str("factor"), Func::Native(factor));
+    map.insert(hash_str("collect"), Func::Native(collect));
+    map.insert(hash_str("substitute"), Func::Native(substitute));
+    map.insert(hash_str("solve"), Func::Native(solve));
+    map.insert(hash_str("root"), Func::Native(root));
+    map.insert(hash_str("newton"), Func::Native(newton));
+    map.insert(hash_str("integrate"), Func::Native(integrate));
+    map.insert(hash_str("deriv"), Func::Native(deriv));
+    map.insert(hash_str("ode"), Func::Native(ode));
+    map.insert(hash_str("minimize"), Func::Native(minimize));
+    map.insert(hash_str("fit"), Func::Native(fit));
+    map.insert(hash_str("poly"), Func::Native(poly));
+    map.insert(hash_str("polyval"), Func::Native(polyval));
+    map.insert(hash_str("polydiv"), Func::Native(polydiv));
+    map.insert(hash_str("polyder"), Func::Native(|_, args| with_polynomial(args, "polyder(p)", |p| Ok(Value::Polynomial(Rc::new(p.derivative()))))));
+    map.insert(hash_str("polyint"), Func::Native(polyint));
+    map.insert(hash_str("polyfit"), Func::Native(polyfit));
+    map.insert(hash_str("roots"), Func::Native(|_, args| with_polynomial(args, "roots(p)", polynomial_roots)));
+    map.insert(hash_str("det"), Func::Native(|_, args| with_matrix(args, "det", |a| Ok(Value::Real(Real::Float(linalg::det(a)?))))));
+    map.insert(hash_str("inv"), Func::Native(|_, args| with_matrix(args, "inv", |a| Ok(Value::from_matrix(linalg::inverse(a)?)))));
+    map.insert(hash_str("transpose"), Func::Native(|_, args| with_matrix(args, "transpose", |a| Ok(Value::from_matrix(linalg::transpose(a))))));
+    map.insert(hash_str("trace"), Func::Native(|_, args| with_matrix(args, "trace", |a| Ok(Value::Real(Real::Float(linalg::trace(a)?))))));
+    map.insert(hash_str("rank"), Func::Native(|_, args| with_matrix(args, "rank", |a| Ok(Value::Real(Real::Int(linalg::rank(a)? as i64))))));
+    map.insert(hash_str("lu"), Func::Native(|_, args| with_matrix(args, "lu", lu)));
+    map.insert(hash_str("qr"), Func::Native(|_, args| with_matrix(args, "qr", qr)));
+    map.insert(hash_str("chol"), Func::Native(|_, args| with_matrix(args, "chol", |a| Ok(Value::from_matrix(linalg::cholesky(a)?)))));
+    map.insert(hash_str("eig"), Func::Native(|_, args| with_matrix(args, "eig", eig)));
+    map.insert(hash_str("svd"), Func::Native(|_, args| with_matrix(args, "svd", svd)));
+    map.insert(hash_str("sum"), Func::Native(|vm, args| series(vm, args, BinaryOp::Add)));
+    map.insert(hash_str("prod"), Func::Native(|vm, args| series(vm, args, BinaryOp::Mul)));
+    map.insert(hash_str("min"), Func::Variadic(|xs| extreme(xs, |x, best| x < best)));
+    map.insert(hash_str("max"), Func::Variadic(|xs| extreme(xs, |x, best| x > best)));
+    map.insert(hash_str("mean"), Func::Variadic(|xs| Ok(Real::Float(statistics::mean(&to_floats(xs))?))));
+    map.insert(hash_str("median"), Func::Variadic(|xs| Ok(Real::Float(statistics::median(&to_floats(xs))?))));
+    map.insert(hash_str("mode"), Func::Variadic(|xs| Ok(Real::Float(statistics::mode(&to_floats(xs))?))));
+    map.insert(hash_str("var"), Func::Variadic(|xs| Ok(Real::Float(statistics::variance(&to_floats(xs))?))));
+    map.insert(hash_str("std"), Func::Variadic(|xs| Ok(Real::Float(statistics::std_dev(&to_floats(xs))?))));
+    map.insert(hash_str("percentile"), Func::Native(|_, args| with_data(args, "percentile(xs, p)", statistics::percentile)));
+    map.insert(hash_str("quantile"), Func::Native(|_, args| with_data(args, "quantile(xs, q)", statistics::quantile)));
+    map.insert(hash_str("cov"), Func::Native(|_, args| with_paired_data(args, "cov(xs, ys)", statistics::covariance)));
+    map.insert(hash_str("corr"), Func::Native(|_, args| with_paired_data(args, "corr(xs, ys)", statistics::correlation)));
+    map.insert(hash_str("linreg"), Func::Native(linreg));
+    map.insert(hash_str("rand"), Func::Native(|vm, args| with_rng(vm, args, "rand()", |rng| Ok(Value::Real(Real::Float(rng.next_f64()))))));
+    map.insert(hash_str("randn"), Func::Native(randn));
+    map.insert(hash_str("randint"), Func::Native(randint));
+    map.insert(hash_str("choice"), Func::Native(choice));
+    map.insert(hash_str("shuffle"), Func::Native(shuffle));
+    map.insert(hash_str("seed"), Func::Native(seed));
+    map.insert(hash_str("normpdf"), Func::Native(|_, args| with_distribution(args, "normpdf(x[, mu, sigma])", normal, Distribution::pdf)));
+    map.insert(hash_str("normcdf"), Func::Native(|_, args| with_distribution(args, "normcdf(x[, mu, sigma])", normal, Distribution::cdf)));
+    map.insert(hash_str("norminv"), Func::Native(|_, args| with_distribution(args, "norminv(q[, mu, sigma])", normal, Distribution::inverse_cdf)));
+    map.insert(hash_str("binopdf"), Func::Native(|_, args| with_distribution(args, "binopdf(k, n, p)", binomial, Distribution::pdf)));
+    map.insert(hash_str("binocdf"), Func::Native(|_, args| with_distribution(args, "binocdf(k, n, p)", binomial, Distribution::cdf)));
+    map.insert(hash_str("binoinv"), Func::Native(|_, args| with_distribution(args, "binoinv(q, n, p)", binomial, Distribution::inverse_cdf)));
+    map.insert(hash_str("poisspdf"), Func::Native(|_, args| with_distribution(args, "poisspdf(k, lambda)", poisson, Distribution::pdf)));
+    map.insert(hash_str("poisscdf"), Func::Native(|_, args| with_distribution(args, "poisscdf(k, lambda)", poisson, Distribution::cdf)));
+    map.insert(hash_str("poissinv"), Func::Native(|_, args| with_distribution(args, "poissinv(q, lambda)", poisson, Distribution::inverse_cdf)));
+    map.insert(hash_str("exppdf"), Func::Native(|_, args| with_distribution(args, "exppdf(x, lambda)", exponential, Distribution::pdf)));
+    map.insert(hash_str("expcdf"), Func::Native(|_, args| with_distribution(args, "expcdf(x, lambda)", exponential, Distribution::cdf)));
+    map.insert(hash_str("expinv"), Func::Native(|_, args| with_distribution(args, "expinv(q, lambda)", exponential, Distribution::inverse_cdf)));
+    map.insert(hash_str("tpdf"), Func::Native(|_, args| with_distribution(args, "tpdf(x, nu)", student_t, Distribution::pdf)));
+    map.insert(hash_str("tcdf"), Func::Native(|_, args| with_distribution(args, "tcdf(x, nu)", student_t, Distribution::cdf)));
+    map.insert(hash_str("tinv"), Func::Native(|_, args| with_distribution(args, "tinv(q, nu)", student_t, Distribution::inverse_cdf)));
+    map.insert(hash_str("chi2pdf"), Func::Native(|_, args| with_distribution(args, "chi2pdf(x, k)", chi_squared, Distribution::pdf)));
+    map.insert(hash_str("chi2cdf"), Func::Native(|_, args| with_distribution(args, "chi2cdf(x, k)", chi_squared, Distribution::cdf)));
+    map.insert(hash_str("chi2inv"), Func::Native(|_, args| with_distribution(args, "chi2inv(q, k)", chi_squared, Distribution::inverse_cdf)));
+    map.insert(hash_str("fpdf"), Func::Native(|_, args| with_distribution(args, "fpdf(x, d1, d2)", f_distribution, Distribution::pdf)));
+    map.insert(hash_str("fcdf"), Func::Native(|_, args| with_distribution(args, "fcdf(x, d1, d2)", f_distribution, Distribution::cdf)));
+    map.insert(hash_str("finv"), Func::Native(|_, args| with_distribution(args, "finv(q, d1, d2)", f_distribution, Distribution::inverse_cdf)));
     return map;
 }
+
+/// The same builtins on dual numbers, used when a function is being
+/// differentiated with `grad`.
+#[derive(Clone, Copy)]
+pub enum DualFunc {
+    Monad(fn (Dual) -> Dual),
+    Diad(fn (Dual, Dual) -> Dual),
+}
+
+pub fn setup_dual_builtins() -> HashMap<u64, DualFunc> {
+    let mut map: HashMap<u64, DualFunc> = HashMap::new();
+    map.insert(hash_str("sin"), DualFunc::Monad(Dual::sin));
+    map.insert(hash_str("asin"), DualFunc::Monad(Dual::asin));
+    map.insert(hash_str("cos"), DualFunc::Monad(Dual::cos));
+    map.insert(hash_str("acos"), DualFunc::Monad(Dual::acos));
+    map.insert(hash_str("tan"), DualFunc::Monad(Dual::tan));
+    map.insert(hash_str("atan"), DualFunc::Monad(Dual::atan));
+    map.insert(hash_str("sec"), DualFunc::Monad(|x| Dual::constant(Real::Int(1)) / x.cos()));
+    map.insert(hash_str("csc"), DualFunc::Monad(|x| Dual::constant(Real::Int(1)) / x.sin()));
+    map.insert(hash_str("cot"), DualFunc::Monad(|x| Dual::constant(Real::Int(1)) / x.tan()));
+    map.insert(hash_str("sinh"), DualFunc::Monad(Dual::sinh));
+    map.insert(hash_str("asinh"), DualFunc::Monad(Dual::asinh));
+    map.insert(hash_str("cosh"), DualFunc::Monad(Dual::cosh));
+    map.insert(hash_str("acosh"), DualFunc::Monad(Dual::acosh));
+    map.insert(hash_str("tanh"), DualFunc::Monad(Dual::tanh));
+    map.insert(hash_str("atanh"), DualFunc::Monad(Dual::atanh));
+    map.insert(hash_str("log"), DualFunc::Monad(|x| x.log(Dual::constant(Real::Int(10)))));
+    map.insert(hash_str("ln"), DualFunc::Monad(Dual::ln));
+    map.insert(hash_str("log_base"), DualFunc::Diad(Dual::log));
+    map.insert(hash_str("sqrt"), DualFunc::Monad(Dual::sqrt));
+    map.insert(hash_str("exp"), DualFunc::Monad(Dual::exp));
+    map.insert(hash_str("log2"), DualFunc::Monad(|x| x.log(Dual::constant(Real::Int(2)))));
+    map.insert(hash_str("cbrt"), DualFunc::Monad(Dual::cbrt));
+    map.insert(hash_str("nthroot"), DualFunc::Diad(|x, n| x.pow(Dual::constant(Real::Int(1)) / n)));
+    map.insert(hash_str("hypot"), DualFunc::Diad(|x, y| (x * x + y * y).sqrt()));
+    map.insert(hash_str("abs"), DualFunc::Monad(Dual::abs));
+    map.insert(hash_str("sign"), DualFunc::Monad(|x| Dual::new(x.value.signum(), 0.0)));
+    map.insert(hash_str("floor"), DualFunc::Monad(|x| Dual::new(x.value.floor(), 0.0)));
+    map.insert(hash_str("ceil"), DualFunc::Monad(|x| Dual::new(x.value.ceil(), 0.0)));
+    map.insert(hash_str("trunc"), DualFunc::Monad(|x| Dual::new(x.value.trunc(), 0.0)));
+    map.insert(hash_str("frac"), DualFunc::Monad(|x| Dual::new(x.value.fract(), x.derivative)));
+    map.insert(hash_str("gamma"), DualFunc::Monad(Dual::gamma));
+    map.insert(hash_str("lgamma"), DualFunc::Monad(Dual::ln_gamma));
+    map.insert(hash_str("erf"), DualFunc::Monad(Dual::erf));
+    map.insert(hash_str("erfc"), DualFunc::Monad(|x| -x.erf() + Dual::constant(Real::Int(1))));
+    map.insert(hash_str("xor"), DualFunc::Diad(|x, y| Dual::constant(Real::Float(x.value) ^ Real::Float(y.value))));
+    map
+}
+
+/// `n!` exactly for whole numbers, and `Γ(x + 1)` otherwise.
+fn factorial(_: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [Value::Dual(x)] => Ok(Value::Dual((*x + Dual::constant(Real::Int(1))).gamma())),
+        [x] if x.is_symbolic() => Ok(Value::from_expr(algebra::simplify(&symbolic::call("fact", vec![x.to_expr()?])))),
+        [x] => match x.as_integer() {
+            Ok(n) if n >= 0 => Ok(Value::from_big_integer(combinatorics::factorial(n as u64)?)),
+            Ok(n) => Err(ErrorWithMessage::new_box(&format!("the factorial of the negative number {} is undefined", n))),
+            _ => Ok(Value::Real(Real::Float(special::gamma(Into::<f64>::into(x.as_real()?) + 1.0)))),
+        },
+        _ => Err(ErrorWithMessage::new_box("usage: fact(n)")),
+    }
+}
+
+/// `choose(n, k)`, also `nCr`, counts the ways to pick `k` of `n` items. For
+/// negative `n` it is the generalised coefficient
+/// `(-1)^k choose(k - n - 1, k)`.
+fn choose(_: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let [n, k] = integers(&args, "choose(n, k)")?;
+    if k < 0 {
+        return Ok(Value::Real(Real::Int(0)));
+    }
+    let k = k as u64;
+    let coefficient = match n >= 0 {
+        true => combinatorics::choose(n as u64, k)?,
+        false if k.is_multiple_of(2) => combinatorics::choose(n.unsigned_abs() + k - 1, k)?,
+        false => -&combinatorics::choose(n.unsigned_abs() + k - 1, k)?,
+    };
+    Ok(Value::from_big_integer(coefficient))
+}
+
+/// `binomial(n)` is row `n` of Pascal's triangle and `binomial(a, b, n)` is
+/// the expansion of `(a + b)^n`.
+fn binomial_expansion(_: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [n] => {
+            let n = natural(n.as_integer()?, "binomial")?;
+            let row = (0..=n)
+                .map(|k| combinatorics::choose(n, k).map(Value::from_big_integer))
+                .collect::<Result<Vec<Value>, SamError>>()?;
+            Ok(Value::List(Rc::new(row)))
+        }
+        [a, b, n] => {
+            let n = natural(n.as_integer()?, "binomial")?;
+            let power = symbolic::pow(add(a.to_expr()?, b.to_expr()?), symbolic::int(n as i64));
+            Ok(Value::from_expr(algebra::expand(&power)))
+        }
+        _ => Err(ErrorWithMessage::new_box("usage: binomial(n) or binomial(a, b, n)")),
+    }
+}
+
+/// `round(x)` to a whole number or `round(x, digits)` to that many decimal
+/// places.
+fn round(xs: &[Real]) -> Result<Real, SamError> {
+    match xs {
+        [x] => Ok(rounding::round(*x, 0)),
+        [x, digits] => {
+            let digits: f64 = (*digits).into();
+            match digits.fract() == 0.0 {
+                true => Ok(rounding::round(*x, digits.clamp(-400.0, 400.0) as i32)),
+                false => Err(ErrorWithMessage::new_box("round needs a whole number of digits")),
+            }
+        }
+        _ => Err(ErrorWithMessage::new_box("usage: round(x) or round(x, digits)")),
+    }
+}
+
+/// Builtins of integers that give integers, worked out exactly so that they
+/// carry on as big integers where the `Real` versions would overflow `i64`
+/// or take big integers as floats. `None` for anything else.
+pub fn exact_integer_builtin(name: &str, args: &[Value]) -> Option<Value> {
+    if !matches!(name, "abs" | "sign" | "floor" | "ceil" | "trunc" | "frac" | "round" | "xor") {
+        return None;
+    }
+    let integers: Vec<BigInt> = args.iter().map(Value::as_big_integer).collect::<Option<_>>()?;
+    let result = match (name, integers.as_slice()) {
+        ("abs", [n]) => n.abs(),
+        ("sign", [n]) => BigInt::from(n.cmp(&BigInt::zero()) as i64),
+        ("floor" | "ceil" | "trunc" | "round", [n]) => n.clone(),
+        ("frac", [_]) => BigInt::zero(),
+        ("round", [n, digits]) => round_integer(n, digits.to_i64()?),
+        ("xor", [a, b]) => a ^ b,
+        _ => return None,
+    };
+    Some(Value::from_big_integer(result))
+}
+
+/// `n` rounded to a multiple of `10^-digits`, halves away from zero.
+fn round_integer(n: &BigInt, digits: i64) -> BigInt {
+    if digits >= 0 {
+        return n.clone();
+    }
+    if digits.unsigned_abs() > n.to_string().len() as u64 {
+        return BigInt::zero();
+    }
+    let scale = BigInt::from(10_u64).pow(digits.unsigned_abs() as u32);
+    let (quotient, remainder) = n.div_rem(&scale);
+    let quotient = match &remainder.abs() * &BigInt::from(2_u64) >= scale {
+        true => &quotient + &BigInt::from(if n.is_negative() { -1_i64 } else { 1 }),
+        false => quotient,
+    };
+    &quotient * &scale
+}
+
+/// `atan2(y, x)` is the angle of the point `(x, y)` from the positive x
+/// axis, in the angle mode.
+fn angle_of_point(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [y, x] if y.is_symbolic() || x.is_symbolic() => {
+            Ok(Value::from_expr(algebra::simplify(&symbolic::call("atan2", vec![y.to_expr()?, x.to_expr()?]))))
+        }
+        [Value::Real(y), Value::Real(x)] => Ok(Value::Real(vm.angle_mode().apply_inverse(|x| x, atan2(*y, *x)))),
+        [y, x] => {
+            let scale = Dual::constant(Real::Float(1.0 / vm.angle_mode().radians_per_unit()));
+            Ok(Value::Dual(y.as_dual()?.atan2(x.as_dual()?) * scale))
+        }
+        _ => Err(ErrorWithMessage::new_box("usage: atan2(y, x)")),
+    }
+}
+
+/// `deg(x)` and `rad(x)` give `x` degrees or radians as an angle in the
+/// angle mode, so `sin(deg(30))` is 0.5 whatever the mode. `30°` is
+/// `deg(30)`.
+fn convert_angle(vm: &mut SamVM, args: Vec<Value>, unit: AngleMode, name: &str) -> Result<Value, SamError> {
+    let scale = unit.radians_per_unit() / vm.angle_mode().radians_per_unit();
+    match args.as_slice() {
+        [x] if x.is_symbolic() => Ok(Value::from_expr(algebra::simplify(&symbolic::call(name, vec![x.to_expr()?])))),
+        [x] if scale == 1.0 => Ok(x.clone()),
+        [Value::Dual(x)] => Ok(Value::Dual(*x * Dual::constant(Real::Float(scale)))),
+        [x] => Ok(Value::Real(Real::Float(Into::<f64>::into(x.as_real()?) * scale))),
+        _ => Err(ErrorWithMessage::new_box(&format!("usage: {}(angle)", name))),
+    }
+}
+
+/// Bessel functions are only defined here for whole orders.
+fn bessel(args: Vec<Value>, name: &str, f: fn(i32, f64) -> f64) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [n, x] if n.is_symbolic() || x.is_symbolic() => {
+            Ok(Value::from_expr(algebra::simplify(&symbolic::call(name, vec![n.to_expr()?, x.to_expr()?]))))
+        }
+        [n, x] => {
+            let n: f64 = n.as_real()?.into();
+            if n.fract() != 0.0 || n.abs() > i32::MAX as f64 {
+                return Err(ErrorWithMessage::new_box(&format!("{} needs a whole number order, not {}", name, n)));
+            }
+            Ok(Value::Real(Real::Float(f(n as i32, x.as_real()?.into()))))
+        }
+        _ => Err(ErrorWithMessage::new_box(&format!("usage: {}(n, x)", name))),
+    }
+}
+
+/// Evaluates an expression value, such as the result of `diff`, using the
+/// current variables.
+fn eval(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [Value::Expression(expr)] => vm.evaluate(expr),
+        [value] => Ok(value.clone()),
+        _ => Err(ErrorWithMessage::new_box("usage: eval(expression)")),
+    }
+}
+
+/// `diff(expr, x, n)` differentiates the value of `expr`, such as an
+/// expression held in a variable or a polynomial. The code generator passes
+/// the variable as an expression, so it may be bound too.
+fn diff(_: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let (value, name, order) = match args.as_slice() {
+        [value, variable] => (value, variable_name(variable)?, 1),
+        [value, variable, order] => (value, variable_name(variable)?, order.as_integer()?),
+        _ => return Err(ErrorWithMessage::new_box("usage: diff(expression, variable[, order])")),
+    };
+    if order < 0 {
+        return Err(ErrorWithMessage::new_box("diff needs an order that is not negative"));
+    }
+    match value {
+        Value::Polynomial(p) if p.variable == name => {
+            let derivative = (0..order).fold((**p).clone(), |p, _| p.derivative());
+            Ok(Value::Polynomial(Rc::new(derivative)))
+        }
+        _ => {
+            let mut derivative = value.to_expr()?;
+            for _ in 0..order {
+                derivative = symbolic::derivative::differentiate(&derivative, &name)?;
+            }
+            Ok(Value::from_expr(derivative))
+        }
+    }
+}
+
+/// `grad(f, [x1, x2, ...])` gives the partial derivatives of `f` at a point.
+fn grad(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let (function, point) = match args.as_slice() {
+        [function, point] => (function, point.as_list()?),
+        _ => return Err(ErrorWithMessage::new_box("usage: grad(function, [x1, x2, ...])")),
+    };
+    let point = point
+        .iter()
+        .map(|x| x.as_real().map(Into::into))
+        .collect::<Result<Vec<f64>, SamError>>()?;
+    let gradient = vm.gradient(function, &point)?;
+    Ok(Value::List(Rc::new(gradient.into_iter().map(|x| Value::Real(Real::Float(x))).collect())))
+}
+
+fn rewrite_with(args: Vec<Value>, name: &str, rewrite: fn(&Expr) -> Expr) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [expr] => Ok(Value::from_expr(rewrite(&expr.to_expr()?))),
+        _ => Err(ErrorWithMessage::new_box(&format!("usage: {}(expression)", name))),
+    }
+}
+
+/// `factor` of a number is its prime factorisation as `[prime, exponent]`
+/// pairs, with `[-1, 1]` first for negative numbers. Expressions are
+/// factored symbolically.
+fn factor(_: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let n = match args.as_slice() {
+        [Value::BigInt(n)] => (**n).clone(),
+        [Value::Real(_)] => BigInt::from(integers::<1>(&args, "factor(n)")?[0]),
+        _ => return rewrite_with(args, "factor", algebra::factor),
+    };
+    if n.is_zero() {
+        return Err(ErrorWithMessage::new_box("0 has no prime factorisation"));
+    }
+    let sign = if n.is_negative() { vec![(-1, 1)] } else { vec![] };
+    let factors = prime_factors(&n.abs())?.into_iter().map(|(p, count)| (BigInt::from(p), count as i64));
+    let pairs = sign
+        .into_iter()
+        .map(|(p, count)| (BigInt::from(p as i64), count))
+        .chain(factors)
+        .map(|(p, count)| Value::List(Rc::new(vec![Value::from_big_integer(p), Value::Real(Real::Int(count))])))
+        .collect();
+    Ok(Value::List(Rc::new(pairs)))
+}
+
+/// Big integers are divided by small primes until the rest fits in 64 bits.
+fn prime_factors(n: &BigInt) -> Result<Vec<(u64, u32)>, SamError> {
+    const TRIAL_LIMIT: u64 = 1_000_000;
+    let mut rest = n.clone();
+    let mut factors = vec![];
+    let mut p = 2;
+    while rest.to_i64().is_none() {
+        if p > TRIAL_LIMIT {
+            return Err(ErrorWithMessage::new_box(&format!(
+                "factor can't split {}, which is too large and has no prime factors below {}",
+                rest, TRIAL_LIMIT
+            )));
+        }
+        let mut count = 0;
+        while let (quotient, 0) = rest.div_rem_u64(p) {
+            rest = quotient;
+            count += 1;
+        }
+        if count > 0 {
+            factors.push((p, count));
+        }
+        p += if p == 2 { 1 } else { 2 };
+    }
+    factors.extend(number_theory::factorize(rest.to_i64().unwrap() as u64));
+    Ok(factors)
+}
+
+/// `collect(expr, x)` groups the terms of `expr` by powers of `x`.
+fn collect(_: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [expr, variable] => Ok(Value::from_expr(algebra::collect(&expr.to_expr()?, &variable_name(variable)?))),
+        _ => Err(ErrorWithMessage::new_box("usage: collect(expression, variable)")),
+    }
+}
+
+/// `substitute(expr, x, value)` replaces `x` with `value` and simplifies.
+fn substitute(_: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [expr, variable, value] => {
+            let result = symbolic::substitute(&expr.to_expr()?, &variable_name(variable)?, &value.to_expr()?);
+            Ok(Value::from_expr(algebra::simplify(&result)))
+        }
+        _ => Err(ErrorWithMessage::new_box("usage: substitute(expression, variable, value)")),
+    }
+}
+
+/// Equations without a polynomial solution are searched for numerically
+/// over this interval unless another one is given.
+const DEFAULT_SEARCH_INTERVAL: (f64, f64) = (-100.0, 100.0);
+const SEARCH_SAMPLES: usize = 20_000;
+
+/// `solve(equation, x)` gives a list of the real roots. A trailing `complex`
+/// includes complex roots of polynomials, and a trailing `[a, b]` sets the
+/// interval searched for other equations. `solve([equations], [x, y])`
+/// solves a system of linear equations.
+fn solve(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [Value::List(equations), Value::List(variables)] => {
+            let equations = equations.iter().map(Value::to_expr).collect::<Result<Vec<Expr>, SamError>>()?;
+            let names = variables.iter().map(variable_name).collect::<Result<Vec<String>, SamError>>()?;
+            let solution = solve_linear_system(&equations, &names)?;
+            Ok(Value::List(Rc::new(solution.into_iter().map(Value::Real).collect())))
+        }
+        [equation, variable, options @ ..] => {
+            let equation = equation.to_expr()?;
+            let name = variable_name(variable)?;
+            let mut complex = false;
+            let (mut a, mut b) = DEFAULT_SEARCH_INTERVAL;
+            for option in options {
+                match option {
+                    Value::List(interval) if interval.len() == 2 => {
+                        a = interval[0].as_real()?.into();
+                        b = interval[1].as_real()?.into();
+                    }
+                    _ if matches!(variable_name(option).as_deref(), Ok("complex")) => complex = true,
+                    _ => return Err(ErrorWithMessage::new_box(&format!("unknown option {} for solve", option))),
+                }
+            }
+
+            if let Some(roots) = solve_polynomial(&equation, &name, complex)? {
+                return Ok(Value::List(Rc::new(roots.into_iter().map(Value::from_expr).collect())));
+            }
+            let function = Value::Function(Rc::new(compile_function("solve", &[name], &zero_form(&equation))?));
+            let mut f = |x: f64| -> Result<f64, SamError> {
+                Ok(vm.call(&function, vec![Value::Real(Real::Float(x))])?.as_real()?.into())
+            };
+            let roots = find_roots(&mut f, a, b, SEARCH_SAMPLES)?;
+            Ok(Value::List(Rc::new(roots.into_iter().map(|x| Value::Real(Real::Float(x))).collect())))
+        }
+        _ => Err(ErrorWithMessage::new_box("usage: solve(equation, variable) or solve([equations], [variables])")),
+    }
+}
+
+/// `sum(k, a, b, body)` adds up `body` for the whole numbers `k` from `a`
+/// to `b`, and `prod` multiplies. The code generator passes `body` in as a
+/// function of `k`, followed by the values it captures. Either bound can be
+/// infinite. Given anything else, `sum` and `prod` combine the numbers they
+/// are given, like the other variadic builtins.
+fn series(vm: &mut SamVM, args: Vec<Value>, op: BinaryOp) -> Result<Value, SamError> {
+    let (name, identity) = match op {
+        BinaryOp::Add => ("sum", Real::Int(0)),
+        _ => ("prod", Real::Int(1)),
+    };
+    let (function, from, to, captured) = match args.as_slice() {
+        [function @ Value::Function(_), from, to, captured @ ..] => (function, from, to, captured),
+        _ => return Ok(Value::Real(variadic_arguments(&args)?.iter().fold(identity, |total, x| op.apply(total, *x)))),
+    };
+    let (from, to) = (series_bound(from, name)?, series_bound(to, name)?);
+    let term = |vm: &mut SamVM, k: i64| {
+        let args = std::iter::once(Value::Real(Real::Int(k))).chain(captured.iter().cloned()).collect();
+        vm.call(function, args)
+    };
+    if from > to || from == f64::INFINITY || to == f64::NEG_INFINITY {
+        return Ok(Value::Real(identity));
+    }
+    if from.is_finite() && to.is_finite() {
+        let mut total = Value::Real(identity);
+        for k in from as i64..=to as i64 {
+            let value = term(vm, k)?;
+            total = vm.binary(op, total, value)?;
+        }
+        return Ok(total);
+    }
+
+    let infinite = |vm: &mut SamVM, index: &dyn Fn(u64) -> i64| {
+        let mut f = |j: u64| -> Result<f64, SamError> { Ok(term(vm, index(j))?.as_real()?.into()) };
+        match op {
+            BinaryOp::Add => infinite_series::sum(&mut f),
+            _ => infinite_series::product(&mut f),
+        }
+    };
+    let result = match (from.is_finite(), to.is_finite()) {
+        (true, _) => infinite(vm, &|j| from as i64 + j as i64)?,
+        (_, true) => infinite(vm, &|j| to as i64 - j as i64)?,
+        _ => {
+            let upward = infinite(vm, &|j| j as i64)?;
+            op.apply(Real::Float(upward), Real::Float(infinite(vm, &|j| -1 - j as i64)?)).into()
+        }
+    };
+    Ok(Value::Real(Real::Float(result)))
+}
+
+/// A whole number or infinity.
+fn series_bound(bound: &Value, name: &str) -> Result<f64, SamError> {
+    let x: f64 = bound.as_real()?.into();
+    match x.is_infinite() {
+        true => Ok(x),
+        false => bound.as_integer().map(|n| n as f64).map_err(|_| {
+            ErrorWithMessage::new_box(&format!("the bounds of {} must be whole numbers or infinite, not {}", name, bound))
+        }),
+    }
+}
+
+/// `root(f, a, b)` finds a root of `f` between `a` and `b`, where `f(a)`
+/// and `f(b)` have opposite signs. An optional fourth argument sets the
+/// tolerance.
+fn root(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let (function, a, b, tolerance) = match args.as_slice() {
+        [function, a, b] => (function, a, b, DEFAULT_TOLERANCE),
+        [function, a, b, tolerance] => (function, a, b, tolerance.as_real()?.into()),
+        _ => return Err(ErrorWithMessage::new_box("usage: root(function, a, b)")),
+    };
+    let mut f = |x: f64| -> Result<f64, SamError> {
+        Ok(vm.call(function, vec![Value::Real(Real::Float(x))])?.as_real()?.into())
+    };
+    let root = roots::brent(&mut f, a.as_real()?.into(), b.as_real()?.into(), tolerance)?;
+    Ok(Value::Real(Real::Float(root)))
+}
+
+/// `newton(f, x0)` runs Newton's method from `x0`, taking derivatives of `f`
+/// with dual numbers. An optional third argument sets the tolerance.
+fn newton(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let (function, x0, tolerance) = match args.as_slice() {
+        [function, x0] => (function, x0, DEFAULT_TOLERANCE),
+        [function, x0, tolerance] => (function, x0, tolerance.as_real()?.into()),
+        _ => return Err(ErrorWithMessage::new_box("usage: newton(function, x0)")),
+    };
+    let mut f = |x: f64| -> Result<(f64, f64), SamError> {
+        let y = vm.call(function, vec![Value::Dual(Dual::new(x, 1.0))])?.as_dual()?;
+        Ok((y.value, y.derivative))
+    };
+    let root = roots::newton(&mut f, x0.as_real()?.into(), tolerance)?;
+    Ok(Value::Real(Real::Float(root)))
+}
+
+/// `integrate(f, a, b)` integrates `f` from `a` to `b`, either of which may
+/// be `inf` or `-inf`. An optional fourth argument sets the tolerance for
+/// the estimated error.
+fn integrate(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let (function, a, b, tolerance) = match args.as_slice() {
+        [function, a, b] => (function, a, b, quadrature::DEFAULT_TOLERANCE),
+        [function, a, b, tolerance] => (function, a, b, tolerance.as_real()?.into()),
+        _ => return Err(ErrorWithMessage::new_box("usage: integrate(function, a, b)")),
+    };
+    let mut f = |x: f64| -> Result<f64, SamError> {
+        Ok(vm.call(function, vec![Value::Real(Real::Float(x))])?.as_real()?.into())
+    };
+    let result = quadrature::integrate(&mut f, a.as_real()?.into(), b.as_real()?.into(), tolerance)?;
+    Ok(Value::Real(Real::Float(result.value)))
+}
+
+/// `deriv(f, x)` estimates the derivative of `f` at `x` numerically, for
+/// functions that dual numbers cannot differentiate.
+fn deriv(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let (function, x) = match args.as_slice() {
+        [function, x] => (function, x.as_real()?.into()),
+        _ => return Err(ErrorWithMessage::new_box("usage: deriv(function, x)")),
+    };
+    let mut f = |x: f64| -> Result<f64, SamError> {
+        Ok(vm.call(function, vec![Value::Real(Real::Float(x))])?.as_real()?.into())
+    };
+    let (derivative, _) = differentiation::derivative(&mut f, x)?;
+    Ok(Value::Real(Real::Float(derivative)))
+}
+
+/// `ode(f, t0, y0, t1)` solves `y' = f(t, y)` with `y(t0) = y0` and gives
+/// `y(t1)`. For a system `y0` is a list, and `f` takes `t` and each
+/// component and returns a list. A fifth argument `n` gives a table of
+/// `[t, y]` at `n + 1` evenly spaced times instead.
+fn ode(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let (function, t0, y0, t1, samples) = match args.as_slice() {
+        [function, t0, y0, t1] => (function, t0, y0, t1, None),
+        [function, t0, y0, t1, samples] => (function, t0, y0, t1, Some(samples.as_real()?)),
+        _ => return Err(ErrorWithMessage::new_box("usage: ode(f, t0, y0, t1)")),
+    };
+    let is_system = matches!(y0, Value::List(_));
+    let to_value = |y: &[f64]| point_value(y, is_system);
+    let mut f = |t: f64, y: &[f64]| -> Result<Vec<f64>, SamError> {
+        let mut args = vec![Value::Real(Real::Float(t))];
+        args.extend(floats_to_values(y));
+        floats(&vm.call(function, args)?)
+    };
+
+    let (t0, t1): (f64, f64) = (t0.as_real()?.into(), t1.as_real()?.into());
+    let mut y = floats(y0)?;
+    let samples = match samples {
+        None => return Ok(to_value(&dormand_prince(&mut f, t0, &y, t1, ODE_TOLERANCE)?)),
+        Some(Real::Int(n)) if n > 0 => n as usize,
+        Some(Real::Float(n)) if n >= 1.0 && n.fract() == 0.0 => n as usize,
+        Some(n) => return Err(ErrorWithMessage::new_box(&format!("ode: {} is not a number of samples", n.to_string()))),
+    };
+    let mut table = vec![];
+    let mut t = t0;
+    for i in 0..=samples {
+        let next = t0 + (t1 - t0) * i as f64 / samples as f64;
+        y = dormand_prince(&mut f, t, &y, next, ODE_TOLERANCE)?;
+        t = next;
+        table.push(Value::List(Rc::new(vec![Value::Real(Real::Float(t)), to_value(&y)])));
+    }
+    Ok(Value::List(Rc::new(table)))
+}
+
+/// `minimize(f, x0)` gives a point near `x0` where `f` is smallest. For
+/// several variables `x0` is a list and `f` takes each coordinate. Uses
+/// BFGS when `f` can be differentiated with dual numbers, and Nelder–Mead
+/// otherwise. An optional third argument sets the tolerance.
+fn minimize(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let (function, x0, tolerance) = match args.as_slice() {
+        [function, x0] => (function, x0, optimization::DEFAULT_TOLERANCE),
+        [function, x0, tolerance] => (function, x0, tolerance.as_real()?.into()),
+        _ => return Err(ErrorWithMessage::new_box("usage: minimize(function, x0)")),
+    };
+    let start = floats(x0)?;
+    let minimum = match vm.gradient(function, &start) {
+        Ok(_) => {
+            let mut f = |x: &[f64]| -> Result<(f64, Vec<f64>), SamError> {
+                let value = vm.call(function, floats_to_values(x))?.as_real()?.into();
+                Ok((value, vm.gradient(function, x)?))
+            };
+            optimization::bfgs(&mut f, &start, tolerance)?
+        }
+        Err(_) => {
+            let mut f = |x: &[f64]| -> Result<f64, SamError> { Ok(vm.call(function, floats_to_values(x))?.as_real()?.into()) };
+            optimization::nelder_mead(&mut f, &start, tolerance)?
+        }
+    };
+    Ok(point_value(&minimum, matches!(x0, Value::List(_))))
+}
+
+/// `fit(model, xs, ys, p0)` finds the parameters `p` for which `model(x, p1,
+/// p2, ...)` best fits the data in the least squares sense, starting from
+/// `p0`, and gives `[p, residuals]` with the residuals `y - model(x, p)`.
+fn fit(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let (model, xs, ys, p0) = match args.as_slice() {
+        [model, xs, ys, p0] => (model, floats(xs)?, floats(ys)?, p0),
+        _ => return Err(ErrorWithMessage::new_box("usage: fit(model, xs, ys, p0)")),
+    };
+    if xs.len() != ys.len() {
+        return Err(ErrorWithMessage::new_box("fit: xs and ys must have the same length"));
+    }
+    if xs.is_empty() {
+        return Err(ErrorWithMessage::new_box("fit: there is no data to fit"));
+    }
+    let start = floats(p0)?;
+    let mut point = vec![xs[0]];
+    point.extend(&start);
+    // Without dual numbers the Jacobian comes from finite differences.
+    let exact = vm.gradient(model, &point).is_ok();
+
+    let evaluate = |vm: &mut SamVM, x: f64, p: &[f64]| -> Result<f64, SamError> {
+        let mut args = vec![Value::Real(Real::Float(x))];
+        args.extend(floats_to_values(p));
+        Ok(vm.call(model, args)?.as_real()?.into())
+    };
+    let mut residuals = |p: &[f64]| -> Result<(Vec<f64>, Vec<Vec<f64>>), SamError> {
+        let mut r = vec![];
+        let mut jacobian = vec![];
+        for (x, y) in xs.iter().zip(&ys) {
+            let value = evaluate(vm, *x, p)?;
+            r.push(value - y);
+            jacobian.push(match exact {
+                true => {
+                    let mut point = vec![*x];
+                    point.extend(p);
+                    vm.gradient(model, &point)?.split_off(1)
+                }
+                false => (0..p.len())
+                    .map(|i| {
+                        let mut shifted = p.to_vec();
+                        let h = f64::EPSILON.sqrt() * p[i].abs().max(1.0);
+                        shifted[i] += h;
+                        Ok((evaluate(vm, *x, &shifted)? - value) / h)
+                    })
+                    .collect::<Result<Vec<f64>, SamError>>()?,
+            });
+        }
+        Ok((r, jacobian))
+    };
+    let p = least_squares::levenberg_marquardt(&mut residuals, &start, 1e-12)?;
+    let residuals = xs
+        .iter()
+        .zip(&ys)
+        .map(|(x, y)| Ok(y - evaluate(vm, *x, &p)?))
+        .collect::<Result<Vec<f64>, SamError>>()?;
+    Ok(Value::List(Rc::new(vec![
+        point_value(&p, matches!(p0, Value::List(_))),
+        point_value(&residuals, true),
+    ])))
+}
+
+fn with_matrix(args: Vec<Value>, name: &str, f: fn(&Matrix) -> Result<Value, SamError>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [matrix] => f(&matrix.as_matrix()?),
+        _ => Err(ErrorWithMessage::new_box(&format!("usage: {}(matrix)", name))),
+    }
+}
+
+/// `lu(A)` gives `[L, U, P]` with `P A = L U`.
+fn lu(a: &Matrix) -> Result<Value, SamError> {
+    let lu = linalg::lu(a)?;
+    Ok(Value::List(Rc::new(vec![
+        Value::from_matrix(lu.lower),
+        Value::from_matrix(lu.upper),
+        Value::from_matrix(lu.permutation),
+    ])))
+}
+
+/// `qr(A)` gives `[Q, R]` with `A = Q R`.
+fn qr(a: &Matrix) -> Result<Value, SamError> {
+    let (q, r) = linalg::qr(a)?;
+    Ok(Value::List(Rc::new(vec![Value::from_matrix(q), Value::from_matrix(r)])))
+}
+
+/// `eig(A)` gives the eigenvalues of `A`, with complex ones in terms of `i`.
+fn eig(a: &Matrix) -> Result<Value, SamError> {
+    let values = linalg::eigenvalues(a)?.into_iter().map(complex_value).collect();
+    Ok(Value::List(Rc::new(values)))
+}
+
+/// A complex number `(re, im)` as an expression in `i`, or a plain number
+/// when it is real.
+fn complex_value((re, im): (f64, f64)) -> Value {
+    match im == 0.0 {
+        true => Value::Real(Real::Float(re)),
+        false => Value::from_expr(algebra::simplify(&add(
+            Expr::Literal(Real::Float(re)),
+            mul(Expr::Literal(Real::Float(im)), var("i")),
+        ))),
+    }
+}
+
+/// `svd(A)` gives `[U, s, V]` with `A = U diag(s) Vᵀ`.
+fn svd(a: &Matrix) -> Result<Value, SamError> {
+    let (u, s, v) = linalg::svd(a)?;
+    Ok(Value::List(Rc::new(vec![Value::from_matrix(u), point_value(&s, true), Value::from_matrix(v)])))
+}
+
+/// `poly(coefficients)` makes a polynomial in `x` from its coefficients,
+/// highest power first, and `poly(expression)` from a polynomial expression
+/// with numbers for coefficients. An optional second argument names the
+/// variable.
+fn poly(_: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let p = match args.as_slice() {
+        [p] => as_polynomial(p, None)?,
+        [p, variable] => as_polynomial(p, Some(&variable_name(variable)?))?,
+        _ => return Err(ErrorWithMessage::new_box("usage: poly(coefficients or expression[, variable])")),
+    };
+    Ok(Value::Polynomial(Rc::new(p)))
+}
+
+/// Polynomials, lists of coefficients highest power first, numbers, and
+/// polynomial expressions. Giving `variable` renames the variable of a
+/// polynomial, and picks the variable of an expression.
+fn as_polynomial(value: &Value, variable: Option<&str>) -> Result<Polynomial, SamError> {
+    match value {
+        Value::Polynomial(p) => Ok(Polynomial::new(p.coefficients().to_vec(), variable.unwrap_or(&p.variable))),
+        Value::List(coefficients) => {
+            let coefficients = coefficients.iter().rev().map(|c| c.as_real().map(Into::into)).collect::<Result<_, _>>()?;
+            Ok(Polynomial::new(coefficients, variable.unwrap_or("x")))
+        }
+        Value::Real(_) | Value::BigInt(_) => Ok(Polynomial::constant(value.as_real()?.into(), variable.unwrap_or("x"))),
+        Value::Expression(expr) => {
+            let name = match (variable, variables(expr).as_slice()) {
+                (Some(name), _) => name.to_owned(),
+                (None, [name]) => name.clone(),
+                _ => return Err(ErrorWithMessage::new_box(&format!("which variable is {} a polynomial in? Give it as in poly(p, x)", expr))),
+            };
+            algebra::numeric_coefficients(expr, &name)
+                .map(|coefficients| Polynomial::new(coefficients, &name))
+                .ok_or_else(|| ErrorWithMessage::new_box(&format!("{} is not a polynomial in {} with numbers for coefficients", expr, name)))
+        }
+        _ => Err(ErrorWithMessage::new_box(&format!("expected a polynomial but found {}", value))),
+    }
+}
+
+fn with_polynomial(args: Vec<Value>, usage: &str, f: fn(Polynomial) -> Result<Value, SamError>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [p] => f(as_polynomial(p, None)?),
+        _ => Err(ErrorWithMessage::new_box(&format!("usage: {}", usage))),
+    }
+}
+
+/// The value of `p` at `x`, which can be a number, a dual number, an
+/// expression or a list of any of these.
+pub fn evaluate_polynomial(p: &Polynomial, x: &Value) -> Result<Value, SamError> {
+    match x {
+        Value::List(items) => Ok(Value::List(Rc::new(items.iter().map(|x| evaluate_polynomial(p, x)).collect::<Result<_, _>>()?))),
+        Value::Dual(x) => Ok(Value::Dual(Dual::new(p.eval(x.value), p.derivative().eval(x.value) * x.derivative))),
+        Value::Expression(_) => {
+            let expr = symbolic::substitute(&Value::Polynomial(Rc::new(p.clone())).to_expr()?, &p.variable, &x.to_expr()?);
+            Ok(Value::from_expr(algebra::simplify(&expr)))
+        }
+        _ => Ok(Value::Real(Real::Float(p.eval(x.as_real()?.into())))),
+    }
+}
+
+/// `polyval(p, x)` evaluates a polynomial, or a list of coefficients, at `x`.
+fn polyval(_: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [p, x] => evaluate_polynomial(&as_polynomial(p, None)?, x),
+        _ => Err(ErrorWithMessage::new_box("usage: polyval(p, x)")),
+    }
+}
+
+/// `polydiv(p, q)` gives `[quotient, remainder]`.
+fn polydiv(_: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let (p, q) = match args.as_slice() {
+        [p, q] => {
+            let p = as_polynomial(p, None)?;
+            match q {
+                Value::Polynomial(q) if q.variable != p.variable => {
+                    return Err(ErrorWithMessage::new_box(&format!(
+                        "polydiv needs polynomials in the same variable, not {} and {}",
+                        p.variable, q.variable
+                    )))
+                }
+                q => {
+                    let q = as_polynomial(q, Some(&p.variable))?;
+                    (p, q)
+                }
+            }
+        }
+        _ => return Err(ErrorWithMessage::new_box("usage: polydiv(p, q)")),
+    };
+    let (quotient, remainder) = p.div_rem(&q)?;
+    Ok(Value::List(Rc::new(vec![Value::Polynomial(Rc::new(quotient)), Value::Polynomial(Rc::new(remainder))])))
+}
+
+/// `polyint(p)` integrates a polynomial; an optional second argument is the
+/// constant of integration.
+fn polyint(_: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let (p, constant) = match args.as_slice() {
+        [p] => (p, 0.0),
+        [p, constant] => (p, constant.as_real()?.into()),
+        _ => return Err(ErrorWithMessage::new_box("usage: polyint(p[, constant])")),
+    };
+    Ok(Value::Polynomial(Rc::new(as_polynomial(p, None)?.integral(constant))))
+}
+
+/// `polyfit(xs, ys, n)` fits a polynomial of degree `n` by least squares.
+fn polyfit(_: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [xs, ys, degree] => {
+            let degree = usize::try_from(degree.as_integer()?)
+                .map_err(|_| ErrorWithMessage::new_box("polyfit needs a degree that is not negative"))?;
+            Ok(Value::Polynomial(Rc::new(Polynomial::fit(&floats(xs)?, &floats(ys)?, degree, "x")?)))
+        }
+        _ => Err(ErrorWithMessage::new_box("usage: polyfit(xs, ys, degree)")),
+    }
+}
+
+/// `roots(p)` gives every complex root of a polynomial, repeated by
+/// multiplicity, with complex ones in terms of `i`.
+fn polynomial_roots(p: Polynomial) -> Result<Value, SamError> {
+    if p.is_zero() {
+        return Err(ErrorWithMessage::new_box("every number is a root of 0"));
+    }
+    Ok(Value::List(Rc::new(p.roots().into_iter().map(complex_value).collect())))
+}
+
+/// The arguments of a variadic builtin: the numbers in a single list
+/// argument, or else the arguments themselves.
+pub fn variadic_arguments(args: &[Value]) -> Result<Vec<Real>, SamError> {
+    match args {
+        [Value::List(items)] => items.iter().map(Value::as_real).collect(),
+        _ => args.iter().map(Value::as_real).collect(),
+    }
+}
+
+fn to_floats(xs: &[Real]) -> Vec<f64> {
+    xs.iter().map(|x| (*x).into()).collect()
+}
+
+/// The first value that beats every other by `better`.
+fn extreme(xs: &[Real], better: fn(Real, Real) -> bool) -> Result<Real, SamError> {
+    let (first, rest) = xs.split_first().ok_or_else(|| ErrorWithMessage::new_box("need at least one value"))?;
+    Ok(rest.iter().fold(*first, |best, x| if better(*x, best) { *x } else { best }))
+}
+
+fn with_data(args: Vec<Value>, usage: &str, f: fn(&[f64], f64) -> Result<f64, SamError>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [xs, p] => Ok(Value::Real(Real::Float(f(&floats(xs)?, p.as_real()?.into())?))),
+        _ => Err(ErrorWithMessage::new_box(&format!("usage: {}", usage))),
+    }
+}
+
+fn with_paired_data(args: Vec<Value>, usage: &str, f: fn(&[f64], &[f64]) -> Result<f64, SamError>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [xs, ys] => Ok(Value::Real(Real::Float(f(&floats(xs)?, &floats(ys)?)?))),
+        _ => Err(ErrorWithMessage::new_box(&format!("usage: {}", usage))),
+    }
+}
+
+/// `linreg(xs, ys)` gives `[slope, intercept, r²]` of the least squares
+/// line.
+fn linreg(_: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [xs, ys] => {
+            let (slope, intercept, r_squared) = statistics::linear_regression(&floats(xs)?, &floats(ys)?)?;
+            Ok(point_value(&[slope, intercept, r_squared], true))
+        }
+        _ => Err(ErrorWithMessage::new_box("usage: linreg(xs, ys)")),
+    }
+}
+
+/// Evaluates `f` of a distribution at the first argument, with the rest as
+/// the parameters of the distribution.
+fn with_distribution(
+    args: Vec<Value>,
+    usage: &str,
+    distribution: fn(&[f64]) -> Option<Distribution>,
+    f: fn(&Distribution, f64) -> Result<f64, SamError>,
+) -> Result<Value, SamError> {
+    let args = args.iter().map(|arg| arg.as_real().map(Into::into)).collect::<Result<Vec<f64>, _>>()?;
+    match args.split_first().and_then(|(x, parameters)| Some((*x, distribution(parameters)?))) {
+        Some((x, distribution)) => Ok(Value::Real(Real::Float(f(&distribution, x)?))),
+        None => Err(ErrorWithMessage::new_box(&format!("usage: {}", usage))),
+    }
+}
+
+fn normal(parameters: &[f64]) -> Option<Distribution> {
+    match parameters {
+        [] => Some(Distribution::Normal { mean: 0.0, std_dev: 1.0 }),
+        [mean, std_dev] => Some(Distribution::Normal { mean: *mean, std_dev: *std_dev }),
+        _ => None,
+    }
+}
+
+fn binomial(parameters: &[f64]) -> Option<Distribution> {
+    match parameters {
+        [trials, p] => Some(Distribution::Binomial { trials: *trials, p: *p }),
+        _ => None,
+    }
+}
+
+fn poisson(parameters: &[f64]) -> Option<Distribution> {
+    match parameters {
+        [rate] => Some(Distribution::Poisson { rate: *rate }),
+        _ => None,
+    }
+}
+
+fn exponential(parameters: &[f64]) -> Option<Distribution> {
+    match parameters {
+        [rate] => Some(Distribution::Exponential { rate: *rate }),
+        _ => None,
+    }
+}
+
+fn student_t(parameters: &[f64]) -> Option<Distribution> {
+    match parameters {
+        [dof] => Some(Distribution::StudentT { dof: *dof }),
+        _ => None,
+    }
+}
+
+fn chi_squared(parameters: &[f64]) -> Option<Distribution> {
+    match parameters {
+        [dof] => Some(Distribution::ChiSquared { dof: *dof }),
+        _ => None,
+    }
+}
+
+fn f_distribution(parameters: &[f64]) -> Option<Distribution> {
+    match parameters {
+        [dof1, dof2] => Some(Distribution::F { dof1: *dof1, dof2: *dof2 }),
+        _ => None,
+    }
+}
+
+fn with_rng(vm: &mut SamVM, args: Vec<Value>, usage: &str, f: fn(&mut Rng) -> Result<Value, SamError>) -> Result<Value, SamError> {
+    match args.is_empty() {
+        true => f(vm.rng()),
+        false => Err(ErrorWithMessage::new_box(&format!("usage: {}", usage))),
+    }
+}
+
+/// `randn()` is standard normal and `randn(mu, sigma)` has the given mean
+/// and standard deviation.
+fn randn(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let (mean, std_dev): (f64, f64) = match args.as_slice() {
+        [] => (0.0, 1.0),
+        [mean, std_dev] => (mean.as_real()?.into(), std_dev.as_real()?.into()),
+        _ => return Err(ErrorWithMessage::new_box("usage: randn() or randn(mu, sigma)")),
+    };
+    Ok(Value::Real(Real::Float(mean + std_dev * vm.rng().normal())))
+}
+
+/// `randint(a, b)` is a whole number from `a` to `b` inclusive.
+fn randint(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let (low, high) = match args.as_slice() {
+        [a, b] => (a.as_integer()?, b.as_integer()?),
+        _ => return Err(ErrorWithMessage::new_box("usage: randint(a, b)")),
+    };
+    if low > high {
+        return Err(ErrorWithMessage::new_box(&format!("randint needs a <= b, not {} > {}", low, high)));
+    }
+    // The span of the full i64 range does not fit in a u64, but then every
+    // u64 is a valid offset.
+    let offset = match (high as i128 - low as i128 + 1).try_into() {
+        Ok(span) => vm.rng().below(span),
+        Err(_) => vm.rng().next_u64(),
+    };
+    Ok(Value::Real(Real::Int(low.wrapping_add(offset as i64))))
+}
+
+fn choice(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [Value::List(items)] if !items.is_empty() => Ok(items[vm.rng().below(items.len() as u64) as usize].clone()),
+        [Value::List(_)] => Err(ErrorWithMessage::new_box("cannot choose from an empty list")),
+        _ => Err(ErrorWithMessage::new_box("usage: choice(list)")),
+    }
+}
+
+/// `shuffle(xs)` is a copy of the list in random order.
+fn shuffle(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [Value::List(items)] => {
+            let mut items = items.to_vec();
+            vm.rng().shuffle(&mut items);
+            Ok(Value::List(Rc::new(items)))
+        }
+        _ => Err(ErrorWithMessage::new_box("usage: shuffle(list)")),
+    }
+}
+
+fn seed(vm: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    match args.as_slice() {
+        [value] => {
+            vm.seed(value.as_integer()? as u64);
+            Ok(value.clone())
+        }
+        _ => Err(ErrorWithMessage::new_box("usage: seed(n)")),
+    }
+}
+
+/// Exactly `N` whole number arguments.
+fn integers<const N: usize>(args: &[Value], usage: &str) -> Result<[i64; N], SamError> {
+    if args.len() != N {
+        return Err(ErrorWithMessage::new_box(&format!("usage: {}", usage)));
+    }
+    if let Some(big) = args.iter().find(|arg| matches!(arg, Value::BigInt(_))) {
+        let name = usage.split('(').next().unwrap_or(usage);
+        return Err(ErrorWithMessage::new_box(&format!("{} does not support big integers such as {}", name, big)));
+    }
+    let integers = args.iter().map(Value::as_integer).collect::<Result<Vec<i64>, _>>()?;
+    Ok(integers.try_into().unwrap())
+}
+
+fn integer_value(n: u64) -> Result<Value, SamError> {
+    match i64::try_from(n) {
+        Ok(n) => Ok(Value::Real(Real::Int(n))),
+        Err(_) => Err(ErrorWithMessage::new_box("the result is too large")),
+    }
+}
+
+fn positive(n: i64, name: &str) -> Result<u64, SamError> {
+    match n > 0 {
+        true => Ok(n as u64),
+        false => Err(ErrorWithMessage::new_box(&format!("{} needs a positive number", name))),
+    }
+}
+
+fn natural(n: i64, name: &str) -> Result<u64, SamError> {
+    match n >= 0 {
+        true => Ok(n as u64),
+        false => Err(ErrorWithMessage::new_box(&format!("{} needs numbers that are not negative", name))),
+    }
+}
+
+/// Combines the sizes of one or more whole numbers with `f`.
+fn fold_integers(args: Vec<Value>, usage: &str, f: fn(&BigInt, &BigInt) -> BigInt) -> Result<Value, SamError> {
+    let exact = |arg: &Value| arg.as_big_integer().map_or_else(|| arg.as_integer().map(BigInt::from), Ok);
+    let integers = args.iter().map(exact).collect::<Result<Vec<BigInt>, _>>()?;
+    match integers.split_first() {
+        Some((first, rest)) => Ok(Value::from_big_integer(rest.iter().fold(first.abs(), |a, b| f(&a, b)))),
+        None => Err(ErrorWithMessage::new_box(&format!("usage: {}", usage))),
+    }
+}
+
+/// `modpow(b, e, m)` is `b^e mod m`, using the inverse of `b` for negative
+/// `e`.
+fn modpow(_: &mut SamVM, args: Vec<Value>) -> Result<Value, SamError> {
+    let [base, exponent, modulus] = integers(&args, "modpow(b, e, m)")?;
+    let modulus = positive(modulus, "modpow")?;
+    let base = match exponent < 0 {
+        true => number_theory::mod_inv(base, modulus as i64)? as u64,
+        false => base.rem_euclid(modulus as i64) as u64,
+    };
+    integer_value(number_theory::mod_pow(base, exponent.unsigned_abs(), modulus))
+}
+
+/// A number, or a list of numbers, as floats.
+fn floats(value: &Value) -> Result<Vec<f64>, SamError> {
+    match value {
+        Value::List(items) => items.iter().map(|x| x.as_real().map(Into::into)).collect(),
+        x => Ok(vec![x.as_real()?.into()]),
+    }
+}
+
+fn floats_to_values(xs: &[f64]) -> Vec<Value> {
+    xs.iter().map(|x| Value::Real(Real::Float(*x))).collect()
+}
+
+/// The inverse of `floats`: a list, or a single number unless `as_list`.
+fn point_value(xs: &[f64], as_list: bool) -> Value {
+    match (as_list, xs) {
+        (false, [x]) => Value::Real(Real::Float(*x)),
+        _ => Value::List(Rc::new(floats_to_values(xs))),
+    }
+}
+
+/// Unbound names evaluate to themselves, so a variable argument arrives as
+/// an expression holding just that name.
+fn variable_name(value: &Value) -> Result<String, SamError> {
+    match value {
+        Value::Expression(expr) => match &**expr {
+            Expr::Variable(name) => Ok(name.clone()),
+            _ => Err(ErrorWithMessage::new_box(&format!("expected a variable but found {}", expr))),
+        },
+        _ => Err(ErrorWithMessage::new_box(&format!("expected a variable but found {}, which is already bound", value))),
+    }
+}
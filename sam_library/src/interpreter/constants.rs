@@ -1,16 +1,130 @@
-use std::collections::HashMap;
-
-use crate::util::hash_str::hash_str;
+use std::{
+    f64::consts::{E, PI, TAU},
+    fmt::Display,
+};
 
 use super::data_types::Real;
 
-pub fn generate_constants() -> HashMap<u64, Real> {
-    let mut map = HashMap::new();
-    map.insert(hash_str("pi"), Real::Float(std::f64::consts::PI));
-    map.insert(hash_str("e"), Real::Float(std::f64::consts::E));
-    map.insert(hash_str("tau"), Real::Float(std::f64::consts::TAU));
-    map.insert(hash_str("G"), Real::Float(6.67428_f64.powi(-11)));
-    map.insert(hash_str("true"), Real::Int(1));
-    map.insert(hash_str("false"), Real::Int(0));
-    return map;
-}
\ No newline at end of file
+/// A named constant together with what it means, so that it can be looked
+/// up from the REPL.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constant {
+    pub name: String,
+    pub value: Real,
+    /// Empty for dimensionless numbers.
+    pub unit: String,
+    /// The standard uncertainty, which is 0 for exact values.
+    pub uncertainty: f64,
+    pub description: String,
+}
+
+impl Constant {
+    pub fn new(name: &str, value: f64, unit: &str, uncertainty: f64, description: &str) -> Constant {
+        Constant {
+            name: name.to_owned(),
+            value: Real::Float(value),
+            unit: unit.to_owned(),
+            uncertainty,
+            description: description.to_owned(),
+        }
+    }
+
+    /// Whether `name` can be written in an expression: identifiers, optionally
+    /// joined by dots as in `phys.c`.
+    pub fn is_valid_name(name: &str) -> bool {
+        name.split('.').all(|part| {
+            let mut chars = part.chars();
+            chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+    }
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value {
+            Real::Float(x) => write!(f, "{} = {}", self.name, readable(x))?,
+            Real::Int(n) => write!(f, "{} = {}", self.name, n)?,
+        }
+        if !self.unit.is_empty() {
+            write!(f, " {}", self.unit)?;
+        }
+        if self.uncertainty > 0.0 {
+            write!(f, " ± {}", readable(self.uncertainty))?;
+        }
+        write!(f, ", {}", self.description)
+    }
+}
+
+/// Very large and very small numbers in scientific notation.
+fn readable(x: f64) -> String {
+    match x != 0.0 && x.is_finite() && !(1e-3..1e7).contains(&x.abs()) {
+        true => format!("{:e}", x),
+        false => x.to_string(),
+    }
+}
+
+/// Mathematical constants, and the CODATA 2018 recommended values of the
+/// physical constants under `phys.` so that they leave short names free for
+/// variables.
+pub fn generate_constants() -> Vec<Constant> {
+    let mut constants = vec![
+        Constant::new("pi", PI, "", 0.0, "ratio of a circle's circumference to its diameter"),
+        Constant::new("e", E, "", 0.0, "base of the natural logarithm"),
+        Constant::new("tau", TAU, "", 0.0, "ratio of a circle's circumference to its radius"),
+        Constant::new("inf", f64::INFINITY, "", 0.0, "infinity"),
+        Constant::new("G", 6.67430e-11, "m^3/(kg s^2)", 1.5e-15, "Newtonian constant of gravitation, also phys.G"),
+        Constant::new("phys.c", 299792458.0, "m/s", 0.0, "speed of light in vacuum"),
+        Constant::new("phys.h", 6.62607015e-34, "J s", 0.0, "Planck constant"),
+        Constant::new("phys.hbar", 6.62607015e-34 / TAU, "J s", 0.0, "reduced Planck constant"),
+        Constant::new("phys.e", 1.602176634e-19, "C", 0.0, "elementary charge"),
+        Constant::new("phys.k_B", 1.380649e-23, "J/K", 0.0, "Boltzmann constant"),
+        Constant::new("phys.N_A", 6.02214076e23, "1/mol", 0.0, "Avogadro constant"),
+        Constant::new("phys.R", 8.314462618, "J/(mol K)", 0.0, "molar gas constant"),
+        Constant::new("phys.F", 96485.33212, "C/mol", 0.0, "Faraday constant"),
+        Constant::new("phys.sigma", 5.670374419e-8, "W/(m^2 K^4)", 0.0, "Stefan-Boltzmann constant"),
+        Constant::new("phys.b", 2.897771955e-3, "m K", 0.0, "Wien wavelength displacement law constant"),
+        Constant::new("phys.G", 6.67430e-11, "m^3/(kg s^2)", 1.5e-15, "Newtonian constant of gravitation"),
+        Constant::new("phys.g", 9.80665, "m/s^2", 0.0, "standard acceleration of gravity"),
+        Constant::new("phys.atm", 101325.0, "Pa", 0.0, "standard atmosphere"),
+        Constant::new("phys.eV", 1.602176634e-19, "J", 0.0, "electron volt"),
+        Constant::new("phys.m_e", 9.1093837015e-31, "kg", 2.8e-40, "electron mass"),
+        Constant::new("phys.m_p", 1.67262192369e-27, "kg", 5.1e-37, "proton mass"),
+        Constant::new("phys.m_n", 1.67492749804e-27, "kg", 9.5e-37, "neutron mass"),
+        Constant::new("phys.u", 1.66053906660e-27, "kg", 5.0e-37, "atomic mass constant"),
+        Constant::new("phys.mu_0", 1.25663706212e-6, "N/A^2", 1.9e-16, "vacuum magnetic permeability"),
+        Constant::new("phys.epsilon_0", 8.8541878128e-12, "F/m", 1.3e-21, "vacuum electric permittivity"),
+        Constant::new("phys.Z_0", 376.730313668, "ohm", 5.7e-8, "characteristic impedance of vacuum"),
+        Constant::new("phys.alpha", 7.2973525693e-3, "", 1.1e-12, "fine-structure constant"),
+        Constant::new("phys.R_inf", 10973731.568160, "1/m", 2.1e-5, "Rydberg constant"),
+        Constant::new("phys.a_0", 5.29177210903e-11, "m", 8.0e-21, "Bohr radius"),
+        Constant::new("phys.r_e", 2.8179403262e-15, "m", 1.3e-24, "classical electron radius"),
+        Constant::new("phys.lambda_C", 2.42631023867e-12, "m", 7.3e-22, "Compton wavelength"),
+        Constant::new("phys.mu_B", 9.2740100783e-24, "J/T", 2.8e-33, "Bohr magneton"),
+        Constant::new("phys.mu_N", 5.0507837461e-27, "J/T", 1.5e-36, "nuclear magneton"),
+        Constant::new("phys.Phi_0", 2.067833848e-15, "Wb", 0.0, "magnetic flux quantum"),
+        Constant::new("phys.K_J", 483597.8484e9, "Hz/V", 0.0, "Josephson constant"),
+        Constant::new("phys.R_K", 25812.80745, "ohm", 0.0, "von Klitzing constant"),
+        Constant::new("phys.l_P", 1.616255e-35, "m", 1.8e-40, "Planck length"),
+        Constant::new("phys.m_P", 2.176434e-8, "kg", 2.4e-13, "Planck mass"),
+        Constant::new("phys.t_P", 5.391247e-44, "s", 6.0e-49, "Planck time"),
+    ];
+    for (name, value, description) in [("true", 1, "logical true"), ("false", 0, "logical false")] {
+        constants.push(Constant { value: Real::Int(value), ..Constant::new(name, 0.0, "", 0.0, description) });
+    }
+    constants
+}
+
+#[cfg(test)]
+mod test {
+    use super::{generate_constants, Constant};
+
+    #[test]
+    fn test_constants() {
+        let constants = generate_constants();
+        assert!(constants.iter().all(|constant| Constant::is_valid_name(&constant.name)));
+        let gravity = constants.iter().find(|constant| constant.name == "G").unwrap();
+        assert!(Into::<f64>::into(gravity.value) == 6.6743e-11);
+        assert!(Constant::is_valid_name("my.units.foot") && !Constant::is_valid_name("2x") && !Constant::is_valid_name("a..b"));
+    }
+}
@@ -1,4 +1,193 @@
+use std::fmt::Display;
 use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Rem, Sub, Shr, Shl, Not, Neg};
+use std::rc::Rc;
+
+use crate::algorithms::{big_integer::BigInt, linalg::Matrix, polynomial::Polynomial};
+use crate::ast::{expression::Expr, operators::{BinaryOp, UnaryOp}};
+use crate::compiler::Chunk;
+use crate::errors::{ErrorWithMessage, SamError};
+
+use super::dual::Dual;
+
+/// Anything that can live on the VM stack or in a variable.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Real(Real),
+    Expression(Rc<Expr>),
+    List(Rc<Vec<Value>>),
+    Function(Rc<Chunk>),
+    Dual(Dual),
+    /// A whole number too large for `Real::Int`.
+    BigInt(Rc<BigInt>),
+    Polynomial(Rc<Polynomial>),
+}
+
+impl Value {
+    pub fn as_real(&self) -> Result<Real, SamError> {
+        match self {
+            Value::Real(x) => Ok(*x),
+            Value::BigInt(n) => Ok(Real::Float(n.to_f64())),
+            _ => Err(ErrorWithMessage::new_box(&format!("expected a number but found {}", self.describe()))),
+        }
+    }
+
+    /// A whole number, which may have been written as a float such as `2.0`.
+    pub fn as_integer(&self) -> Result<i64, SamError> {
+        if let Value::BigInt(_) = self {
+            return Err(ErrorWithMessage::new_box(&format!("{} is too large", self.describe())));
+        }
+        match self.as_real()? {
+            Real::Int(n) => Ok(n),
+            Real::Float(x) if x.fract() == 0.0 && x.abs() < (1_u64 << 53) as f64 => Ok(x as i64),
+            _ => Err(ErrorWithMessage::new_box(&format!("expected a whole number but found {}", self.describe()))),
+        }
+    }
+
+    /// Plain numbers become dual numbers with no derivative.
+    pub fn as_dual(&self) -> Result<Dual, SamError> {
+        match self {
+            Value::Real(x) => Ok(Dual::constant(*x)),
+            Value::Dual(x) => Ok(*x),
+            Value::BigInt(n) => Ok(Dual::constant(Real::Float(n.to_f64()))),
+            _ => Err(ErrorWithMessage::new_box(&format!("expected a number but found {}", self.describe()))),
+        }
+    }
+
+    /// Whole numbers held exactly, as `Real::Int` or `Value::BigInt`.
+    pub fn as_big_integer(&self) -> Option<BigInt> {
+        match self {
+            Value::Real(Real::Int(n)) => Some(BigInt::from(*n)),
+            Value::BigInt(n) => Some((**n).clone()),
+            _ => None,
+        }
+    }
+
+    /// Big integers that fit in an `i64` become `Real::Int`.
+    pub fn from_big_integer(n: BigInt) -> Value {
+        match n.to_i64() {
+            Some(n) => Value::Real(Real::Int(n)),
+            None => Value::BigInt(Rc::new(n)),
+        }
+    }
+
+    pub fn as_list(&self) -> Result<&[Value], SamError> {
+        match self {
+            Value::List(items) => Ok(items),
+            _ => Err(ErrorWithMessage::new_box(&format!("expected a list but found {}", self.describe()))),
+        }
+    }
+
+    /// A list of rows, each a list of numbers of the same length.
+    pub fn as_matrix(&self) -> Result<Matrix, SamError> {
+        let rows = self.as_list()?;
+        let matrix = rows
+            .iter()
+            .map(|row| row.as_list()?.iter().map(|x| x.as_real().map(Into::into)).collect())
+            .collect::<Result<Matrix, SamError>>();
+        match matrix {
+            Ok(matrix) if !matrix.is_empty() && !matrix[0].is_empty() && matrix.iter().all(|row| row.len() == matrix[0].len()) => Ok(matrix),
+            _ => Err(ErrorWithMessage::new_box(&format!("expected a matrix but found {}", self.describe()))),
+        }
+    }
+
+    pub fn from_matrix(matrix: Matrix) -> Value {
+        let rows = matrix
+            .into_iter()
+            .map(|row| Value::List(Rc::new(row.into_iter().map(|x| Value::Real(Real::Float(x))).collect())))
+            .collect();
+        Value::List(Rc::new(rows))
+    }
+
+    /// Numbers become literals, so that they can be combined with
+    /// expression values.
+    pub fn to_expr(&self) -> Result<Expr, SamError> {
+        match self {
+            Value::Real(x) => Ok(Expr::Literal(*x)),
+            Value::BigInt(n) => Ok(Expr::BigInteger(Rc::clone(n))),
+            Value::Expression(expr) => Ok((**expr).clone()),
+            Value::List(items) => Ok(Expr::List(items.iter().map(Value::to_expr).collect::<Result<_, _>>()?)),
+            Value::Polynomial(p) => Ok(polynomial_expr(p)),
+            _ => Err(ErrorWithMessage::new_box(&format!("expected an expression but found {}", self.describe()))),
+        }
+    }
+
+    /// The value of an expression that reduced to a number is that number.
+    pub fn from_expr(expr: Expr) -> Value {
+        match expr {
+            Expr::Literal(x) => Value::Real(x),
+            expr => Value::Expression(Rc::new(expr)),
+        }
+    }
+
+    pub fn is_symbolic(&self) -> bool {
+        matches!(self, Value::Expression(_) | Value::Polynomial(_))
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Value::Real(x) => x.to_string(),
+            Value::Expression(expr) => format!("the expression {}", expr),
+            Value::List(_) => format!("the list {}", self),
+            Value::Function(function) => format!("the function {}", function.name),
+            Value::Dual(x) => format!("the dual number {}", x),
+            Value::BigInt(n) => n.to_string(),
+            Value::Polynomial(_) => format!("the polynomial {}", self),
+        }
+    }
+}
+
+/// A polynomial written out highest power first.
+fn polynomial_expr(p: &Polynomial) -> Expr {
+    let variable = Expr::Variable(p.variable.clone());
+    let term = |power: usize, c: f64| {
+        let coefficient = match c.fract() == 0.0 && c.abs() < 1e15 {
+            true => Expr::Literal(Real::Int(c as i64)),
+            false => Expr::Literal(Real::Float(c)),
+        };
+        let x = match power {
+            0 => return coefficient,
+            1 => variable.clone(),
+            _ => Expr::binary(BinaryOp::Pow, variable.clone(), Expr::Literal(Real::Int(power as i64))),
+        };
+        match c == 1.0 {
+            true => x,
+            false => Expr::binary(BinaryOp::Mul, coefficient, x),
+        }
+    };
+    let mut terms = p.coefficients().iter().enumerate().rev().filter(|(_, c)| **c != 0.0);
+    let first = match terms.next() {
+        Some((power, c)) if *c < 0.0 && power > 0 => Expr::unary(UnaryOp::Neg, term(power, -c)),
+        Some((power, c)) => term(power, *c),
+        None => return Expr::Literal(Real::Int(0)),
+    };
+    terms.fold(first, |total, (power, c)| match *c < 0.0 {
+        true => Expr::binary(BinaryOp::Sub, total, term(power, -c)),
+        false => Expr::binary(BinaryOp::Add, total, term(power, *c)),
+    })
+}
+
+impl From<Real> for Value {
+    fn from(x: Real) -> Self {
+        Value::Real(x)
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Real(x) => write!(f, "{}", x.to_string()),
+            Value::Expression(expr) => write!(f, "{}", expr),
+            Value::List(items) => {
+                let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Value::Function(function) => write!(f, "{}({})", function.name, function.parameters[..function.arity()].join(", ")),
+            Value::Dual(x) => write!(f, "{}", x),
+            Value::BigInt(n) => write!(f, "{}", n),
+            Value::Polynomial(p) => write!(f, "{}", polynomial_expr(p)),
+        }
+    }
+}
 
 #[derive(Clone, Copy, Debug)]
 pub enum Real {
@@ -16,7 +205,7 @@ impl Add for Real {
             },
             Real::Int(x) => match other {
                 Real::Float(y) => Real::Float((x as f64) + y),
-                Real::Int(y) => Real::Int(x + y),
+                Real::Int(y) => x.checked_add(y).map_or(Real::Float(x as f64 + y as f64), Real::Int),
             },
         }
     }
@@ -32,7 +221,7 @@ impl Sub for Real {
             },
             Real::Int(x) => match other {
                 Real::Float(y) => Real::Float((x as f64) - y),
-                Real::Int(y) => Real::Int(x - y),
+                Real::Int(y) => x.checked_sub(y).map_or(Real::Float(x as f64 - y as f64), Real::Int),
             },
         }
     }
@@ -48,7 +237,7 @@ impl Mul for Real {
             },
             Real::Int(x) => match other {
                 Real::Float(y) => Real::Float((x as f64) * y),
-                Real::Int(y) => Real::Int(x * y),
+                Real::Int(y) => x.checked_mul(y).map_or(Real::Float(x as f64 * y as f64), Real::Int),
             },
         }
     }
@@ -64,7 +253,11 @@ impl Div for Real {
             },
             Real::Int(x) => match other {
                 Real::Float(y) => Real::Float((x as f64) / y),
-                Real::Int(y) => Real::Int(x / y),
+                // Whole numbers only stay whole when they divide exactly.
+                Real::Int(y) => match x.checked_rem(y) {
+                    Some(0) => Real::Int(x / y),
+                    _ => Real::Float(x as f64 / y as f64),
+                },
             },
         }
     }
@@ -80,7 +273,10 @@ impl Rem for Real {
             },
             Real::Int(x) => match other {
                 Real::Float(y) => Real::Float((x as f64) % y),
-                Real::Int(y) => Real::Int(x % y),
+                Real::Int(y) => match x.checked_rem(y) {
+                    Some(remainder) => Real::Int(remainder),
+                    None => Real::Float(x as f64 % y as f64),
+                },
             },
         }
     }
@@ -186,7 +382,7 @@ impl Neg for Real {
     fn neg(self) -> Self::Output {
         match self {
             Real::Float(x) => Real::Float(-x),
-            Real::Int(x) => Real::Int(-x),
+            Real::Int(x) => x.checked_neg().map_or(Real::Float(-(x as f64)), Real::Int),
         }
     }
 }
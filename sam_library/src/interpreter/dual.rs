@@ -0,0 +1,237 @@
+use std::f64::consts::FRAC_2_SQRT_PI;
+use std::fmt::Display;
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+
+use crate::{
+    algorithms::special,
+    ast::operators::{BinaryOp, UnaryOp},
+};
+
+use super::data_types::{Real, Value};
+
+/// A dual number `value + derivative·ε` where `ε² = 0`. Running a formula on
+/// dual numbers carries the derivative along with the value, which is
+/// forward-mode automatic differentiation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dual {
+    pub value: f64,
+    pub derivative: f64,
+}
+
+impl Dual {
+    pub fn new(value: f64, derivative: f64) -> Dual {
+        Dual { value, derivative }
+    }
+
+    pub fn constant(x: Real) -> Dual {
+        Dual::new(x.into(), 0.0)
+    }
+
+    /// Applies the chain rule for a function with value `fx` and slope `dfx`
+    /// at `self.value`.
+    fn chain(self, fx: f64, dfx: f64) -> Dual {
+        Dual::new(fx, dfx * self.derivative)
+    }
+
+    pub fn sin(self) -> Dual {
+        self.chain(self.value.sin(), self.value.cos())
+    }
+
+    pub fn asin(self) -> Dual {
+        self.chain(self.value.asin(), 1.0 / (1.0 - self.value * self.value).sqrt())
+    }
+
+    pub fn cos(self) -> Dual {
+        self.chain(self.value.cos(), -self.value.sin())
+    }
+
+    pub fn acos(self) -> Dual {
+        self.chain(self.value.acos(), -1.0 / (1.0 - self.value * self.value).sqrt())
+    }
+
+    pub fn tan(self) -> Dual {
+        self.chain(self.value.tan(), 1.0 / self.value.cos().powi(2))
+    }
+
+    pub fn atan(self) -> Dual {
+        self.chain(self.value.atan(), 1.0 / (1.0 + self.value * self.value))
+    }
+
+    pub fn atan2(self, x: Dual) -> Dual {
+        let radius = self.value * self.value + x.value * x.value;
+        Dual::new(self.value.atan2(x.value), (x.value * self.derivative - self.value * x.derivative) / radius)
+    }
+
+    pub fn sinh(self) -> Dual {
+        self.chain(self.value.sinh(), self.value.cosh())
+    }
+
+    pub fn asinh(self) -> Dual {
+        self.chain(self.value.asinh(), 1.0 / (self.value * self.value + 1.0).sqrt())
+    }
+
+    pub fn cosh(self) -> Dual {
+        self.chain(self.value.cosh(), self.value.sinh())
+    }
+
+    pub fn acosh(self) -> Dual {
+        self.chain(self.value.acosh(), 1.0 / (self.value * self.value - 1.0).sqrt())
+    }
+
+    pub fn tanh(self) -> Dual {
+        self.chain(self.value.tanh(), 1.0 / self.value.cosh().powi(2))
+    }
+
+    pub fn atanh(self) -> Dual {
+        self.chain(self.value.atanh(), 1.0 / (1.0 - self.value * self.value))
+    }
+
+    pub fn exp(self) -> Dual {
+        let value = self.value.exp();
+        self.chain(value, value)
+    }
+
+    pub fn ln(self) -> Dual {
+        self.chain(self.value.ln(), 1.0 / self.value)
+    }
+
+    pub fn log(self, base: Dual) -> Dual {
+        self.ln() / base.ln()
+    }
+
+    pub fn sqrt(self) -> Dual {
+        let root = self.value.sqrt();
+        self.chain(root, 0.5 / root)
+    }
+
+    pub fn cbrt(self) -> Dual {
+        let root = self.value.cbrt();
+        self.chain(root, 1.0 / (3.0 * root * root))
+    }
+
+    pub fn abs(self) -> Dual {
+        self.chain(self.value.abs(), self.value.signum())
+    }
+
+    pub fn gamma(self) -> Dual {
+        let value = special::gamma(self.value);
+        self.chain(value, value * special::digamma(self.value))
+    }
+
+    pub fn ln_gamma(self) -> Dual {
+        self.chain(special::ln_gamma(self.value), special::digamma(self.value))
+    }
+
+    pub fn erf(self) -> Dual {
+        self.chain(special::erf(self.value), FRAC_2_SQRT_PI * (-self.value * self.value).exp())
+    }
+
+    pub fn pow(self, exponent: Dual) -> Dual {
+        let value = self.value.powf(exponent.value);
+        // d(u^v) = v·u^(v-1)·du + u^v·ln(u)·dv, leaving out whichever term
+        // is zero so negative bases still work with constant exponents.
+        let mut derivative = 0.0;
+        if self.derivative != 0.0 {
+            derivative += exponent.value * self.value.powf(exponent.value - 1.0) * self.derivative;
+        }
+        if exponent.derivative != 0.0 {
+            derivative += value * self.value.ln() * exponent.derivative;
+        }
+        Dual::new(value, derivative)
+    }
+}
+
+impl Add for Dual {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Dual::new(self.value + other.value, self.derivative + other.derivative)
+    }
+}
+
+impl Sub for Dual {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Dual::new(self.value - other.value, self.derivative - other.derivative)
+    }
+}
+
+impl Mul for Dual {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        Dual::new(
+            self.value * other.value,
+            self.derivative * other.value + self.value * other.derivative,
+        )
+    }
+}
+
+impl Div for Dual {
+    type Output = Self;
+    fn div(self, other: Self) -> Self {
+        Dual::new(
+            self.value / other.value,
+            (self.derivative * other.value - self.value * other.derivative) / (other.value * other.value),
+        )
+    }
+}
+
+impl Rem for Dual {
+    type Output = Self;
+    fn rem(self, other: Self) -> Self {
+        // a % b = a - trunc(a / b)·b, where the quotient is piecewise constant.
+        let quotient = (self.value / other.value).trunc();
+        Dual::new(self.value % other.value, self.derivative - quotient * other.derivative)
+    }
+}
+
+impl Neg for Dual {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Dual::new(-self.value, -self.derivative)
+    }
+}
+
+impl Display for Dual {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} + {}ε", self.value, self.derivative)
+    }
+}
+
+/// Applies a binary operator to dual numbers. Comparisons, logic and bit
+/// operations are piecewise constant, so they produce plain numbers.
+pub fn apply_binary(op: BinaryOp, a: Dual, b: Dual) -> Value {
+    let result = match op {
+        BinaryOp::Add => a + b,
+        BinaryOp::Sub => a - b,
+        BinaryOp::Mul => a * b,
+        BinaryOp::Div => a / b,
+        BinaryOp::LeftDiv => b / a,
+        BinaryOp::Pow => a.pow(b),
+        BinaryOp::Mod => a % b,
+        _ => return Value::Real(op.apply(Real::Float(a.value), Real::Float(b.value))),
+    };
+    Value::Dual(result)
+}
+
+pub fn apply_unary(op: UnaryOp, x: Dual) -> Value {
+    match op {
+        UnaryOp::Neg => Value::Dual(-x),
+        _ => Value::Real(op.apply(Real::Float(x.value))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Dual;
+
+    #[test]
+    fn test_dual_rules() {
+        let x = Dual::new(2.0, 1.0);
+        assert!((x * x * x).derivative == 12.0);
+        assert!((Dual::new(1.0, 0.0) / x).derivative == -0.25);
+        assert!(x.pow(Dual::new(3.0, 0.0)).derivative == 12.0);
+        assert!(Dual::new(0.0, 1.0).sin().derivative == 1.0);
+        assert!(x.ln().derivative == 0.5);
+        assert!((Dual::new(-2.0, 1.0).pow(Dual::new(2.0, 0.0))).derivative == -4.0);
+    }
+}
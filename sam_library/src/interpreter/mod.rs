@@ -1,4 +1,5 @@
 pub mod virtual_machine;
-mod constants;
-mod builtin_functions;
+pub mod constants;
+pub(crate) mod builtin_functions;
 pub mod data_types;
+pub mod dual;
@@ -1,208 +1,1142 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
 
 use crate::{
-    algorithms::logarithms::pow,
-    ast::{operations::Operation, user_functions::UserFunctionDefinition},
-    errors::{ErrorWithMessage, SamError},
+    algorithms::{
+        big_integer::BigInt,
+        polynomial::Polynomial,
+        trigonometry::AngleMode,
+        linalg::{self, Matrix},
+        random::Rng,
+    },
+    ast::{
+        expression::Expr,
+        operators::{BinaryOp, UnaryOp},
+    },
+    compiler::{
+        compile, compile_function,
+        optimizer::{can_fold, Optimizer},
+        Chunk, Instruction,
+    },
+    errors::{ErrorWithMessage, RuntimeError, SamError},
+    symbolic::{
+        algebra::simplify,
+        call,
+        derivative::{differentiate_in_angle_mode, takes_angle},
+        var,
+    },
+    util::hash_str::hash_str,
 };
 
 use super::{
-    builtin_functions::{setup_builtins, Func},
-    constants::generate_constants,
-    data_types::Real,
+    builtin_functions::{evaluate_polynomial, exact_integer_builtin, setup_builtins, setup_dual_builtins, variadic_arguments, DualFunc, Func},
+    constants::{generate_constants, Constant},
+    data_types::{Real, Value},
+    dual::{self, Dual},
 };
 
+const MAX_CALL_DEPTH: usize = 1_000_000;
+/// How many of the innermost, and of the outermost, calls an error shows.
+const BACKTRACE_FRAMES: usize = 10;
+/// Exact integer powers beyond this many bits become floats instead.
+const MAX_INTEGER_BITS: u64 = 1 << 20;
+/// Powers of polynomials beyond this degree are left as expressions.
+const MAX_POLYNOMIAL_DEGREE: u64 = 10_000;
+
+/// An activation record for a running chunk. The caller's frame keeps its
+/// own program counter, which is where execution resumes once this frame
+/// returns. Parameters live in the value stack starting at `base`.
+struct CallFrame {
+    chunk: Rc<Chunk>,
+    pc: usize,
+    base: usize,
+}
+
 pub struct SamVM {
-    stacks: Vec<Vec<Real>>,
-    current_stack: usize,
-    current_scope: usize,
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
     constants: HashMap<u64, Real>,
-    user_vars: Vec<HashMap<u64, Real>>,
+    /// The constants again, by name and with their units and descriptions.
+    constant_table: BTreeMap<String, Constant>,
+    user_vars: HashMap<u64, Value>,
     builtin_functions: HashMap<u64, Func>,
-    user_functions: HashMap<u64, UserFunctionDefinition>,
+    dual_functions: HashMap<u64, DualFunc>,
+    user_functions: HashMap<u64, Rc<Chunk>>,
+    derivatives: HashMap<(u64, u8), Rc<Chunk>>,
+    rng: Rng,
+    angle_mode: AngleMode,
+}
+
+impl Default for SamVM {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SamVM {
+    /// A VM whose random numbers differ from run to run.
     pub fn new() -> SamVM {
-        return SamVM {
-            stacks: vec![vec![]],
-            current_stack: 0,
-            current_scope: 0,
-            constants: generate_constants(),
+        SamVM::with_rng(Rng::from_entropy())
+    }
+
+    /// A VM whose random numbers are repeatable, the same as after calling
+    /// `seed(seed)`.
+    pub fn with_seed(seed: u64) -> SamVM {
+        SamVM::with_rng(Rng::new(seed))
+    }
+
+    fn with_rng(rng: Rng) -> SamVM {
+        let mut vm = SamVM {
+            stack: vec![],
+            frames: vec![],
+            constants: HashMap::new(),
+            constant_table: BTreeMap::new(),
             user_functions: HashMap::new(),
-            user_vars: vec![HashMap::new()],
+            user_vars: HashMap::new(),
             builtin_functions: setup_builtins(),
+            dual_functions: setup_dual_builtins(),
+            derivatives: HashMap::new(),
+            rng,
+            angle_mode: AngleMode::Radians,
         };
+        for constant in generate_constants() {
+            vm.insert_constant(constant);
+        }
+        vm
+    }
+
+    /// Adds a constant, or replaces the one with the same name. Constants are
+    /// looked up before variables, so a namespaced name such as `units.ft`
+    /// keeps short names free.
+    pub fn add_constant(&mut self, constant: Constant) -> Result<(), SamError> {
+        if !Constant::is_valid_name(&constant.name) {
+            return Err(ErrorWithMessage::new_box(&format!("{} is not a valid constant name", constant.name)));
+        }
+        self.insert_constant(constant);
+        Ok(())
+    }
+
+    fn insert_constant(&mut self, constant: Constant) {
+        self.constants.insert(hash_str(&constant.name), constant.value);
+        self.constant_table.insert(constant.name.clone(), constant);
+    }
+
+    pub fn constant(&self, name: &str) -> Option<&Constant> {
+        self.constant_table.get(name)
+    }
+
+    /// Every constant, in order of name.
+    pub fn constants(&self) -> impl Iterator<Item = &Constant> {
+        self.constant_table.values()
+    }
+
+    pub fn angle_mode(&self) -> AngleMode {
+        self.angle_mode
+    }
+
+    /// Sets the unit that trigonometric functions take angles in and that
+    /// inverse trigonometric functions give them in.
+    pub fn set_angle_mode(&mut self, mode: AngleMode) {
+        self.angle_mode = mode;
+        // Derivatives of trigonometric functions depend on the mode.
+        self.derivatives.clear();
+    }
+
+    /// Restarts the random number generator from `seed`.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    pub(crate) fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    pub fn interpret(&mut self, expr: Expr) -> Result<Value, SamError> {
+        let chunk = self.compile(&expr)?;
+        self.execute(chunk)
+    }
+
+    /// Folds constant subexpressions, including calls to builtins with
+    /// literal arguments, and removes arithmetic identities.
+    pub fn optimize(&self, expr: &Expr) -> Expr {
+        Optimizer::new(&self.builtin_functions, &self.constants).optimize(expr)
     }
 
-    pub fn interpret(&mut self, commands: Vec<Operation>) -> Result<Real, SamError> {
-        for command in commands {
-            self.match_command(command)?;
+    pub fn compile(&self, expr: &Expr) -> Result<Rc<Chunk>, SamError> {
+        Ok(Rc::new(compile(&self.optimize(expr))?))
+    }
+
+    /// Runs an already compiled chunk, so a formula can be compiled once and
+    /// evaluated many times.
+    pub fn execute(&mut self, chunk: Rc<Chunk>) -> Result<Value, SamError> {
+        let depth = self.frames.len();
+        let stack_height = self.stack.len();
+        self.frames.push(CallFrame {
+            chunk,
+            pc: 0,
+            base: stack_height,
+        });
+
+        if let Err(e) = self.run(depth) {
+            let (backtrace, hidden_frames) = self.backtrace(depth);
+            self.frames.truncate(depth);
+            self.stack.truncate(stack_height);
+            return Err(Box::new(RuntimeError::new(e.to_string(), backtrace, hidden_frames)));
         }
-        let last_value = self.stacks[self.current_stack]
+
+        let last_value = self
+            .stack
             .last()
-            .ok_or(ErrorWithMessage::new_box("stack empty!"))?;
-        return Ok(last_value.clone());
-    }
-
-    fn match_command(&mut self, command: Operation) -> Result<(), SamError> {
-        match command {
-            Operation::Float(x) => self.push_stack(Real::Float(x)),
-            Operation::Int(x) => self.push_stack(Real::Int(x)),
-            Operation::Add => self.diadic_op(|a, b| a + b)?,
-            Operation::Sub => self.diadic_op(|a, b| a - b)?,
-            Operation::Mul => self.diadic_op(|a, b| a * b)?,
-            Operation::Div => self.diadic_op(|a, b| a / b)?,
-            Operation::Pow => self.diadic_op(|a, b| pow(a, b))?,
-            Operation::Mod => self.diadic_op(|a, b| a % b)?,
-            Operation::Gt => self.diadic_op(|a, b| Real::Int((a > b) as i64))?,
-            Operation::Lt => self.diadic_op(|a, b| Real::Int((a < b) as i64))?,
-            Operation::Lte => self.diadic_op(|a, b| Real::Int((a <= b) as i64))?,
-            Operation::Gte => self.diadic_op(|a, b| Real::Int((a >= b) as i64))?,
-            Operation::Eq => self.diadic_op(|a, b| Real::Int((a == b) as i64))?,
-            Operation::Neq => self.diadic_op(|a, b| Real::Int((a != b) as i64))?,
-            Operation::BitAnd => self.diadic_op(|a, b| a & b)?,
-            Operation::BoolAnd => self.diadic_op(|a, b| {
-                if a != Real::Int(0) && b != Real::Int(0) {
-                    Real::Int(1)
-                } else {
-                    Real::Int(0)
-                }
-            })?,
-            Operation::BoolOr => self.diadic_op(|a, b| {
-                if a != Real::Int(0) || b != Real::Int(0) {
-                    Real::Int(1)
-                } else {
-                    Real::Int(0)
+            .ok_or_else(|| ErrorWithMessage::new_box("stack empty!"))?;
+        Ok(last_value.clone())
+    }
+
+    /// Evaluates an expression from inside a running program, such as a
+    /// builtin, and returns its value without leaving it on the stack.
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, SamError> {
+        let chunk = self.compile(expr)?;
+        self.call_chunk(chunk, vec![])
+    }
+
+    /// Calls a function value with the given arguments. The name of a
+    /// builtin, such as `cos`, can be called the same way.
+    pub fn call(&mut self, function: &Value, args: Vec<Value>) -> Result<Value, SamError> {
+        match function {
+            Value::Function(chunk) => self.call_chunk(Rc::clone(chunk), args),
+            Value::Polynomial(p) => match args.as_slice() {
+                [x] => evaluate_polynomial(p, x),
+                _ => Err(ErrorWithMessage::new_box("a polynomial takes one argument")),
+            },
+            Value::Expression(expr) => match &**expr {
+                Expr::Variable(name) if self.builtin_functions.contains_key(&hash_str(name)) => {
+                    let stack_height = self.stack.len();
+                    let arg_count = args.len();
+                    self.stack.extend(args);
+                    let result = self
+                        .call_builtin(hash_str(name), name, arg_count)
+                        .and_then(|_| self.pop_stack());
+                    self.stack.truncate(stack_height);
+                    result
                 }
-            })?,
-            Operation::BitOr => self.diadic_op(|a, b| a | b)?,
-            Operation::BitXor => self.diadic_op(|a, b| a ^ b)?,
-            Operation::RightShift => self.diadic_op(|a, b| a >> b)?,
-            Operation::LeftShift => self.diadic_op(|a, b| a << b)?,
-            Operation::Neg => self.monadic_op(|x| -x)?,
-            Operation::BitCompliment => self.monadic_op(|x| !x)?,
-            Operation::Not => self.monadic_op(|x| if x == Real::Int(0) { Real::Int(1) } else { Real::Int(0) })?,
-            Operation::PeekStack => {
+                _ => Err(ErrorWithMessage::new_box(&format!("{} is not a function", function))),
+            },
+            _ => Err(ErrorWithMessage::new_box(&format!("{} is not a function", function))),
+        }
+    }
+
+    /// Applies a binary operator to two values, as the instruction for it
+    /// would.
+    pub fn binary(&mut self, op: BinaryOp, a: Value, b: Value) -> Result<Value, SamError> {
+        self.push_stack(a);
+        self.push_stack(b);
+        self.binary_op(op)?;
+        self.pop_stack()
+    }
+
+    /// The partial derivatives of `function` at `point`, found by calling it
+    /// once per parameter with that parameter as a dual number.
+    pub fn gradient(&mut self, function: &Value, point: &[f64]) -> Result<Vec<f64>, SamError> {
+        (0..point.len())
+            .map(|i| {
+                let args = point
+                    .iter()
+                    .enumerate()
+                    .map(|(j, x)| Value::Dual(Dual::new(*x, if i == j { 1.0 } else { 0.0 })))
+                    .collect();
+                Ok(self.call(function, args)?.as_dual()?.derivative)
+            })
+            .collect()
+    }
+
+    fn call_chunk(&mut self, chunk: Rc<Chunk>, args: Vec<Value>) -> Result<Value, SamError> {
+        let depth = self.frames.len();
+        let stack_height = self.stack.len();
+        let arg_count = args.len();
+        self.stack.extend(args);
+
+        let result = self
+            .push_frame(chunk, arg_count)
+            .and_then(|_| self.run(depth))
+            .and_then(|_| self.pop_stack());
+        self.frames.truncate(depth);
+        self.stack.truncate(stack_height);
+        result
+    }
+
+    fn run(&mut self, depth: usize) -> Result<(), SamError> {
+        while self.frames.len() > depth {
+            let frame = self.frames.last_mut().unwrap();
+            let chunk = Rc::clone(&frame.chunk);
+            let instruction = *chunk
+                .code
+                .get(frame.pc)
+                .ok_or_else(|| ErrorWithMessage::new_box("program counter out of bounds"))?;
+            frame.pc += 1;
+            self.execute_instruction(&chunk, instruction)?;
+        }
+        Ok(())
+    }
+
+    fn execute_instruction(&mut self, chunk: &Chunk, instruction: Instruction) -> Result<(), SamError> {
+        match instruction {
+            Instruction::Constant(index) => self.push_stack(chunk.constants[index as usize].clone()),
+            Instruction::Add => self.binary_op(BinaryOp::Add)?,
+            Instruction::Sub => self.binary_op(BinaryOp::Sub)?,
+            Instruction::Mul => self.binary_op(BinaryOp::Mul)?,
+            Instruction::Div => self.binary_op(BinaryOp::Div)?,
+            Instruction::LeftDiv => self.left_divide()?,
+            Instruction::Pow => self.binary_op(BinaryOp::Pow)?,
+            Instruction::Mod => self.binary_op(BinaryOp::Mod)?,
+            Instruction::Gt => self.binary_op(BinaryOp::Gt)?,
+            Instruction::Lt => self.binary_op(BinaryOp::Lt)?,
+            Instruction::Lte => self.binary_op(BinaryOp::Lte)?,
+            Instruction::Gte => self.binary_op(BinaryOp::Gte)?,
+            Instruction::Eq => self.binary_op(BinaryOp::Eq)?,
+            Instruction::Equation => self.equation()?,
+            Instruction::Neq => self.binary_op(BinaryOp::Neq)?,
+            Instruction::BitAnd => self.binary_op(BinaryOp::BitAnd)?,
+            Instruction::BitOr => self.binary_op(BinaryOp::BitOr)?,
+            Instruction::RightShift => self.binary_op(BinaryOp::RightShift)?,
+            Instruction::LeftShift => self.binary_op(BinaryOp::LeftShift)?,
+            Instruction::Neg => self.unary_op(UnaryOp::Neg)?,
+            Instruction::BitCompliment => self.unary_op(UnaryOp::BitCompliment)?,
+            Instruction::Not => self.unary_op(UnaryOp::Not)?,
+            Instruction::PeekStack => {
                 let value = self.pop_stack()?;
                 self.push_stack(value);
             }
-            Operation::LoadVar(key) => {
-                let val = self.get_var(key);
+            Instruction::LoadGlobal(name) => {
+                let val = self.get_var(chunk.names[name as usize], &chunk.symbols[name as usize]);
                 self.push_stack(val);
             }
-            Operation::StoreVar(key) => {
+            Instruction::LoadLocal(slot) => {
+                let base = self.current_frame().base;
+                let val = self
+                    .stack
+                    .get(base + slot as usize)
+                    .cloned()
+                    .ok_or_else(|| ErrorWithMessage::new_box("missing function argument"))?;
+                self.push_stack(val);
+            }
+            Instruction::StoreGlobal(name) => {
                 let value = self.pop_stack()?;
-                self.set_var(key, value);
+                let key = chunk.names[name as usize];
+                // The variable may have held a function whose derivatives are cached.
+                self.derivatives.retain(|(function, _), _| *function != key);
+                self.set_var(key, value.clone());
                 self.push_stack(value);
             }
-            Operation::CallFunc(key) => {
-                if let Some(func) = self.builtin_functions.get(&key) {
-                    match func {
-                        &Func::Monad(f) => {
-                            let x = self.pop_stack()?;
-                            self.push_stack(f(x));
-                        }
-                        &Func::Diad(f) => {
-                            let (b, a) = self.pop_two()?;
-                            self.push_stack(f(a, b))
-                        }
+            Instruction::Jump(target) => self.current_frame_mut().pc = target as usize,
+            Instruction::MakeList(length) => {
+                let start = self
+                    .stack
+                    .len()
+                    .checked_sub(length as usize)
+                    .ok_or_else(|| ErrorWithMessage::new_box("stack empty!"))?;
+                let items = self.stack.split_off(start);
+                self.push_stack(Value::List(Rc::new(items)));
+            }
+            Instruction::JumpIfFalse(target) => {
+                let condition = match self.pop_stack()? {
+                    Value::Dual(x) => Real::Float(x.value),
+                    value => value.as_real()?,
+                };
+                if condition == Real::Int(0) {
+                    self.current_frame_mut().pc = target as usize;
+                }
+            }
+            Instruction::Call(name, arg_count) => {
+                self.call_function(chunk.names[name as usize], &chunk.symbols[name as usize], arg_count as usize)?
+            }
+            Instruction::TailCall(name, arg_count) => {
+                self.tail_call_function(chunk.names[name as usize], &chunk.symbols[name as usize], arg_count as usize)?
+            }
+            Instruction::CallDerivative(name, order, arg_count) => {
+                let function = self.derivative_function(chunk.names[name as usize], &chunk.symbols[name as usize], order)?;
+                self.push_frame(function, arg_count as usize)?;
+            }
+            Instruction::DefineFunction(name, function) => {
+                self.user_functions.insert(
+                    chunk.names[name as usize],
+                    Rc::clone(&chunk.functions[function as usize]),
+                );
+                self.derivatives.clear();
+                self.push_stack(Value::Real(Real::Int(0)));
+            }
+            Instruction::MakeClosure(count) => {
+                let captured = self.pop_arguments(count as usize)?;
+                let function = match self.pop_stack()? {
+                    Value::Function(function) => function,
+                    value => return Err(ErrorWithMessage::new_box(&format!("cannot capture values in {}", value))),
+                };
+                let closure = Chunk { captured, ..(*function).clone() };
+                self.push_stack(Value::Function(Rc::new(closure)));
+            }
+            Instruction::Return => self.return_from_frame()?,
+        }
+        Ok(())
+    }
+
+    fn call_function(&mut self, key: u64, name: &str, arg_count: usize) -> Result<(), SamError> {
+        if self.call_builtin(key, name, arg_count)? || self.call_polynomial(key, arg_count)? {
+            return Ok(());
+        }
+        let function = self.get_callee(key, name)?;
+        self.push_frame(function, arg_count)
+    }
+
+    fn push_frame(&mut self, function: Rc<Chunk>, arg_count: usize) -> Result<(), SamError> {
+        check_arity(&function, arg_count)?;
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err(ErrorWithMessage::new_box("maximum call depth exceeded"));
+        }
+        let base = self
+            .stack
+            .len()
+            .checked_sub(arg_count)
+            .ok_or_else(|| ErrorWithMessage::new_box("stack empty!"))?;
+        self.stack.extend(function.captured.iter().cloned());
+        self.frames.push(CallFrame {
+            chunk: function,
+            pc: 0,
+            base,
+        });
+        Ok(())
+    }
+
+    /// Calls a function whose result is immediately returned by the current
+    /// frame: the arguments replace the current locals and the frame is
+    /// restarted on the callee, so tail recursion runs in constant space.
+    fn tail_call_function(&mut self, key: u64, name: &str, arg_count: usize) -> Result<(), SamError> {
+        if self.call_builtin(key, name, arg_count)? || self.call_polynomial(key, arg_count)? {
+            return Ok(());
+        }
+
+        let function = self.get_callee(key, name)?;
+        check_arity(&function, arg_count)?;
+        let args_start = self
+            .stack
+            .len()
+            .checked_sub(arg_count)
+            .ok_or_else(|| ErrorWithMessage::new_box("stack empty!"))?;
+        let base = self.current_frame().base;
+        self.stack.drain(base..args_start);
+        self.stack.extend(function.captured.iter().cloned());
+
+        let frame = self.current_frame_mut();
+        frame.chunk = function;
+        frame.pc = 0;
+        Ok(())
+    }
+
+    fn call_builtin(&mut self, key: u64, name: &str, arg_count: usize) -> Result<bool, SamError> {
+        let func = match self.builtin_functions.get(&key) {
+            Some(func) => *func,
+            None => return Ok(false),
+        };
+        match (func, arg_count) {
+            (Func::Monad(f), 1) => {
+                let result = match self.pop_stack()? {
+                    Value::Dual(x) => self.call_dual_builtin(key, &[x])?,
+                    x if x.is_symbolic() => symbolic_call(name, vec![x.to_expr()?]),
+                    x => match exact_integer_builtin(name, std::slice::from_ref(&x)) {
+                        Some(exact) => exact,
+                        None => Value::Real(f(x.as_real()?)),
+                    },
+                };
+                self.push_stack(result);
+            }
+            (Func::Trig(f), 1) => {
+                let result = match self.pop_stack()? {
+                    Value::Dual(x) => {
+                        let scale = Dual::constant(Real::Float(self.angle_mode.radians_per_unit()));
+                        self.call_dual_builtin(key, &[x * scale])?
                     }
-                } else {
-                    if let Some(func) = self.user_functions.get(&key) {
-                        let func = func.clone();
-                        self.user_vars.push(HashMap::new());
-                        self.current_scope += 1;
-                        let params = &func.parameters.clone();
-                        for param in params {
-                            let val = self.pop_stack()?;
-                            self.set_var(param.clone(), val);
-                        }
-
-                        let mut ops = func.operations.clone().into_iter();
-                        while let Some(op) = ops.next() {
-                            let op = op.clone();
-                            self.match_command(op)?;
-                        }
-                        self.current_scope -= 1;
-                        self.user_vars.pop();
+                    x if x.is_symbolic() => symbolic_call(name, vec![x.to_expr()?]),
+                    x => Value::Real(self.angle_mode.apply(f, x.as_real()?)),
+                };
+                self.push_stack(result);
+            }
+            (Func::InverseTrig(f), 1) => {
+                let result = match self.pop_stack()? {
+                    Value::Dual(x) => {
+                        let scale = Dual::constant(Real::Float(1.0 / self.angle_mode.radians_per_unit()));
+                        self.call_dual_builtin(key, &[x])?.as_dual().map(|angle| Value::Dual(angle * scale))?
                     }
-                }
+                    x if x.is_symbolic() => symbolic_call(name, vec![x.to_expr()?]),
+                    x => Value::Real(self.angle_mode.apply_inverse(f, x.as_real()?)),
+                };
+                self.push_stack(result);
             }
-            Operation::Conditional => {
-                let (c, b, a) = self.pop_three()?;
-                if a != Real::Float(0.0) && c != Real::Int(0) {
-                    self.push_stack(b);
-                } else {
-                    self.push_stack(c);
-                }
+            (Func::Diad(f), 2) => {
+                let result = match self.pop_two()? {
+                    (b, a) if a.is_symbolic() || b.is_symbolic() => symbolic_call(name, vec![a.to_expr()?, b.to_expr()?]),
+                    (b, a) if matches!(a, Value::Dual(_)) || matches!(b, Value::Dual(_)) => {
+                        self.call_dual_builtin(key, &[a.as_dual()?, b.as_dual()?])?
+                    }
+                    (b, a) => match exact_integer_builtin(name, &[a.clone(), b.clone()]) {
+                        Some(exact) => exact,
+                        None => Value::Real(f(a.as_real()?, b.as_real()?)),
+                    },
+                };
+                self.push_stack(result);
             }
-            Operation::StoreFunc(key, func) => {
-                self.user_functions.insert(key, func);
-                self.push_stack(Real::Int(0));
+            (Func::Native(f), _) => {
+                let args = self.pop_arguments(arg_count)?;
+                let result = f(self, args)?;
+                self.push_stack(result);
             }
-            _ => return Err(ErrorWithMessage::new_box("stack empty!")),
+            (Func::Variadic(f), _) => {
+                let args = self.pop_arguments(arg_count)?;
+                let result = match exact_integer_builtin(name, &args) {
+                    Some(exact) => exact,
+                    None => Value::Real(f(&variadic_arguments(&args)?)?),
+                };
+                self.push_stack(result);
+            }
+            _ => return Err(ErrorWithMessage::new_box("wrong number of arguments")),
         }
-        Ok({})
+        Ok(true)
+    }
+
+    fn pop_arguments(&mut self, arg_count: usize) -> Result<Vec<Value>, SamError> {
+        let args_start = self
+            .stack
+            .len()
+            .checked_sub(arg_count)
+            .ok_or_else(|| ErrorWithMessage::new_box("stack empty!"))?;
+        Ok(self.stack.split_off(args_start))
     }
 
-    fn set_var(&mut self, key: u64, value: Real) {
-        self.user_vars[self.current_scope].insert(key, value);
+    fn call_dual_builtin(&self, key: u64, args: &[Dual]) -> Result<Value, SamError> {
+        let result = match (self.dual_functions.get(&key), args) {
+            (Some(DualFunc::Monad(f)), [x]) => f(*x),
+            (Some(DualFunc::Diad(f)), [x, y]) => f(*x, *y),
+            _ => return Err(ErrorWithMessage::new_box("this function cannot be used with dual numbers")),
+        };
+        Ok(Value::Dual(result))
     }
 
-    fn pop_stack(&mut self) -> Result<Real, SamError> {
-        return self.stacks[self.current_stack]
+    /// A variable holding a polynomial can be called like a function.
+    fn call_polynomial(&mut self, key: u64, arg_count: usize) -> Result<bool, SamError> {
+        let polynomial = match self.user_vars.get(&key) {
+            Some(polynomial @ Value::Polynomial(_)) => polynomial.clone(),
+            _ => return Ok(false),
+        };
+        let args = self.pop_arguments(arg_count)?;
+        let result = self.call(&polynomial, args)?;
+        self.push_stack(result);
+        Ok(true)
+    }
+
+    /// A declared function, or a variable holding a function such as a
+    /// lambda.
+    /// A parameter of the current function holding a function, as in
+    /// `apply(f, x) = f(x)`, is called before any global of the same name.
+    fn get_callee(&self, key: u64, name: &str) -> Result<Rc<Chunk>, SamError> {
+        let frame = self.current_frame();
+        let slot = frame.chunk.parameters.iter().position(|param| param == name);
+        match slot.and_then(|slot| self.stack.get(frame.base + slot)) {
+            Some(Value::Function(function)) => Ok(Rc::clone(function)),
+            _ => self.get_user_function(key, name),
+        }
+    }
+
+    fn get_user_function(&self, key: u64, name: &str) -> Result<Rc<Chunk>, SamError> {
+        if let Some(function) = self.user_functions.get(&key) {
+            return Ok(Rc::clone(function));
+        }
+        match self.user_vars.get(&key) {
+            Some(Value::Function(function)) => Ok(Rc::clone(function)),
+            _ => Err(ErrorWithMessage::new_box(&format!("unknown function {}", name))),
+        }
+    }
+
+    /// Compiles (and caches) the `order`-th derivative of a user function
+    /// with respect to its first parameter, which is what `f'(x)` calls.
+    /// Trigonometric builtins come here too, as their derivatives depend on
+    /// the angle mode.
+    fn derivative_function(&mut self, key: u64, name: &str, order: u8) -> Result<Rc<Chunk>, SamError> {
+        if let Some(function) = self.derivatives.get(&(key, order)) {
+            return Ok(Rc::clone(function));
+        }
+        let function = match self.builtin_functions.contains_key(&key) && takes_angle(name) {
+            true => Rc::new(compile_function(name, &["x".to_owned()], &call(name, vec![var("x")]))?),
+            false => self.get_user_function(key, name)?,
+        };
+        let (body, variable) = match (&function.body, function.parameters.first()) {
+            (Some(body), Some(variable)) => (body, variable),
+            _ => {
+                return Err(ErrorWithMessage::new_box(&format!(
+                    "cannot differentiate {}",
+                    function.name
+                )))
+            }
+        };
+        let mut derivative = (**body).clone();
+        for _ in 0..order {
+            derivative = differentiate_in_angle_mode(&derivative, variable, self.angle_mode.radians_per_unit())?;
+        }
+        let name = format!("{}{}", function.name, "'".repeat(order as usize));
+        let derivative = self.optimize(&derivative);
+        let chunk = Chunk {
+            captured: function.captured.clone(),
+            ..compile_function(&name, &function.parameters, &derivative)?
+        };
+        let chunk = Rc::new(chunk);
+        self.derivatives.insert((key, order), Rc::clone(&chunk));
+        Ok(chunk)
+    }
+
+    fn return_from_frame(&mut self) -> Result<(), SamError> {
+        let result = self.pop_stack()?;
+        let frame = self.frames.pop().unwrap();
+        self.stack.truncate(frame.base);
+        self.push_stack(result);
+        Ok(())
+    }
+
+    /// The calls above `depth`, innermost first. Deep recursion keeps only the
+    /// innermost and outermost `BACKTRACE_FRAMES` and says how many it left out.
+    fn backtrace(&self, depth: usize) -> (Vec<String>, usize) {
+        let calls = self.frames.get(depth + 1..).unwrap_or_default();
+        let hidden_frames = calls.len().saturating_sub(2 * BACKTRACE_FRAMES);
+        let shown = match hidden_frames {
+            0 => calls.iter().rev().collect::<Vec<_>>(),
+            _ => calls[calls.len() - BACKTRACE_FRAMES..].iter().rev().chain(calls[..BACKTRACE_FRAMES].iter().rev()).collect(),
+        };
+        let backtrace = shown
+            .into_iter()
+            .map(|frame| {
+                let args: Vec<String> = self
+                    .stack
+                    .iter()
+                    .skip(frame.base)
+                    .take(frame.chunk.arity())
+                    .map(|arg| arg.to_string())
+                    .collect();
+                format!("{}({})", frame.chunk.name, args.join(", "))
+            })
+            .collect();
+        (backtrace, hidden_frames)
+    }
+
+    fn current_frame(&self) -> &CallFrame {
+        self.frames.last().unwrap()
+    }
+
+    fn current_frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn set_var(&mut self, key: u64, value: Value) {
+        self.user_vars.insert(key, value);
+    }
+
+    fn pop_stack(&mut self) -> Result<Value, SamError> {
+        self.stack
             .pop()
-            .ok_or(ErrorWithMessage::new_box("stack empty!"));
+            .ok_or_else(|| ErrorWithMessage::new_box("stack empty!"))
     }
 
-    fn pop_two(&mut self) -> Result<(Real, Real), SamError> {
+    fn pop_two(&mut self) -> Result<(Value, Value), SamError> {
         let b = self.pop_stack()?;
         let a = self.pop_stack()?;
-        return Ok((b, a));
+        Ok((b, a))
     }
 
-    fn pop_three(&mut self) -> Result<(Real, Real, Real), SamError> {
-        let (c, b) = self.pop_two()?;
-        let a = self.pop_stack()?;
-        return Ok((c, b, a));
+    fn binary_op(&mut self, op: BinaryOp) -> Result<(), SamError> {
+        let (b, a) = self.pop_two()?;
+        let exact = match exact_integer_op(op, &a, &b) {
+            Some(exact) => Some(exact),
+            None => polynomial_op(op, &a, &b)?,
+        };
+        if let Some(exact) = exact {
+            self.push_stack(exact);
+            return Ok(());
+        }
+        let result = match (b, a) {
+            (Value::Real(b), Value::Real(a)) => Value::Real(op.apply(a, b)),
+            (b, a) if a.is_symbolic() || b.is_symbolic() => {
+                Value::from_expr(simplify(&Expr::binary(op, a.to_expr()?, b.to_expr()?)))
+            }
+            (b, a) if matches!(a, Value::BigInt(_)) || matches!(b, Value::BigInt(_)) => {
+                Value::Real(op.apply(a.as_real()?, b.as_real()?))
+            }
+            (b, a) => dual::apply_binary(op, a.as_dual()?, b.as_dual()?),
+        };
+        self.push_stack(result);
+        Ok(())
     }
 
-    fn diadic_op(&mut self, op: fn(Real, Real) -> Real) -> Result<(), SamError> {
+    /// `a \ b` solves `a x = b` when `a` is a matrix, where `b` is a vector or
+    /// a matrix. Otherwise it is `b / a`.
+    fn left_divide(&mut self) -> Result<(), SamError> {
         let (b, a) = self.pop_two()?;
-        self.push_stack(op(a, b));
-        Ok({})
+        if !matches!(a, Value::List(_)) {
+            self.push_stack(b);
+            self.push_stack(a);
+            return self.binary_op(BinaryOp::Div);
+        }
+        let is_vector = b.as_list()?.iter().all(|x| matches!(x, Value::Real(_)));
+        let rhs = match is_vector {
+            true => b.as_list()?.iter().map(|x| Ok(vec![x.as_real()?.into()])).collect::<Result<Matrix, SamError>>()?,
+            false => b.as_matrix()?,
+        };
+        let solution = linalg::solve(&a.as_matrix()?, &rhs)?;
+        self.push_stack(match is_vector {
+            true => Value::List(Rc::new(solution.into_iter().map(|row| Value::Real(Real::Float(row[0]))).collect())),
+            false => Value::from_matrix(solution),
+        });
+        Ok(())
     }
 
-    fn monadic_op(&mut self, op: fn(Real) -> Real) -> Result<(), SamError> {
-        let a = self.pop_stack()?;
-        self.push_stack(op(a));
-        Ok({})
+    /// Equations are always kept as expressions, even between numbers.
+    fn equation(&mut self) -> Result<(), SamError> {
+        let (b, a) = self.pop_two()?;
+        let equation = Expr::binary(BinaryOp::Equation, simplify(&a.to_expr()?), simplify(&b.to_expr()?));
+        self.push_stack(Value::Expression(Rc::new(equation)));
+        Ok(())
     }
 
-    fn push_stack(&mut self, val: Real) {
-        self.stacks[self.current_stack].push(val);
+    fn unary_op(&mut self, op: UnaryOp) -> Result<(), SamError> {
+        let result = match self.pop_stack()? {
+            Value::Real(Real::Int(i64::MIN)) if op == UnaryOp::Neg => Value::from_big_integer(-&BigInt::from(i64::MIN)),
+            Value::Real(a) => Value::Real(op.apply(a)),
+            Value::BigInt(a) if op == UnaryOp::Neg => Value::from_big_integer(-&*a),
+            Value::BigInt(a) => Value::Real(op.apply(Real::Float(a.to_f64()))),
+            Value::Polynomial(p) if op == UnaryOp::Neg => Value::Polynomial(Rc::new(-&*p)),
+            Value::Expression(a) => Value::from_expr(simplify(&Expr::unary(op, (*a).clone()))),
+            a => dual::apply_unary(op, a.as_dual()?),
+        };
+        self.push_stack(result);
+        Ok(())
+    }
+
+    fn push_stack(&mut self, val: Value) {
+        self.stack.push(val);
     }
 
-    fn get_var(&mut self, key: u64) -> Real {
-        let global = self.constants.get(&key);
-        if let Some(val) = global {
+    /// Names that are not bound to anything stay symbolic.
+    fn get_var(&self, key: u64, name: &str) -> Value {
+        if let Some(val) = self.constants.get(&key) {
+            return Value::Real(*val);
+        }
+        if let Some(val) = self.user_vars.get(&key) {
             return val.clone();
-        } else {
-            let mut scope = self.current_scope;
-            loop {
-                let local = self.user_vars[scope].get(&key);
-                if let Some(val) = local {
-                    return val.clone();
-                }
-                if scope != 0 {
-                    scope -= 1;
-                }
-                if scope == 0 {
-                    break;
+        }
+        match self.user_functions.get(&key) {
+            Some(function) => Value::Function(Rc::clone(function)),
+            None => Value::Expression(Rc::new(Expr::Variable(name.to_owned()))),
+        }
+    }
+}
+
+/// Integer arithmetic that would overflow `i64`, or that involves a big
+/// integer, is done exactly. Division gives an integer when it is exact;
+/// powers and shifts too large to hold are left to floats.
+fn exact_integer_op(op: BinaryOp, a: &Value, b: &Value) -> Option<Value> {
+    let needed = match (a, b) {
+        (Value::Real(x @ Real::Int(_)), Value::Real(y @ Real::Int(_))) => !can_fold(op, *x, *y),
+        _ => matches!(a, Value::BigInt(_)) || matches!(b, Value::BigInt(_)),
+    };
+    if !needed {
+        return None;
+    }
+    // Whole floats, such as literals, join in exactly.
+    let exact = |value: &Value| value.as_big_integer().or_else(|| value.as_integer().ok().map(BigInt::from));
+    let (x, y) = (exact(a)?, exact(b)?);
+    let result = match op {
+        BinaryOp::Add => &x + &y,
+        BinaryOp::Sub => &x - &y,
+        BinaryOp::Mul => &x * &y,
+        BinaryOp::Pow => {
+            let exponent = u32::try_from(y.to_i64()?).ok()?;
+            if x.bits().saturating_mul(exponent as u64) > MAX_INTEGER_BITS {
+                return None;
+            }
+            x.pow(exponent)
+        }
+        BinaryOp::Div if !y.is_zero() => match x.div_rem(&y) {
+            (quotient, remainder) if remainder.is_zero() => quotient,
+            _ => return Some(Value::Real(Real::Float(x.div_f64(&y)))),
+        },
+        BinaryOp::Mod if !y.is_zero() => x.div_rem(&y).1,
+        BinaryOp::BitAnd => &x & &y,
+        BinaryOp::BitOr => &x | &y,
+        // A negative count shifts the other way.
+        BinaryOp::LeftShift | BinaryOp::RightShift => {
+            let count = y.to_i64()?;
+            let bits = count.unsigned_abs().min(u32::MAX as u64) as u32;
+            match (op == BinaryOp::LeftShift) == (count >= 0) {
+                true if !x.is_zero() && x.bits().saturating_add(bits as u64) > MAX_INTEGER_BITS => {
+                    return Some(Value::Real(Real::Float(x.to_f64() * 2_f64.powf(bits as f64))))
                 }
+                true => &x << bits,
+                false => &x >> bits,
+            }
+        }
+        BinaryOp::Lt => return Some(Value::Real(Real::Int((x < y) as i64))),
+        BinaryOp::Gt => return Some(Value::Real(Real::Int((x > y) as i64))),
+        BinaryOp::Lte => return Some(Value::Real(Real::Int((x <= y) as i64))),
+        BinaryOp::Gte => return Some(Value::Real(Real::Int((x >= y) as i64))),
+        BinaryOp::Neq => return Some(Value::Real(Real::Int((x != y) as i64))),
+        BinaryOp::Eq => return Some(Value::Real(Real::Int((x == y) as i64))),
+        _ => return None,
+    };
+    Some(Value::from_big_integer(result))
+}
+
+/// Polynomials in the same variable combine with each other and with
+/// numbers into polynomials. Division that leaves a remainder, like
+/// anything else, is left to the symbolic path; division by zero is an error.
+fn polynomial_op(op: BinaryOp, a: &Value, b: &Value) -> Result<Option<Value>, SamError> {
+    let variable = match (a, b) {
+        (Value::Polynomial(p), _) | (_, Value::Polynomial(p)) => p.variable.clone(),
+        _ => return Ok(None),
+    };
+    let polynomial = |value: &Value| match value {
+        Value::Polynomial(p) if p.variable == variable => Some((**p).clone()),
+        Value::Real(_) | Value::BigInt(_) => Some(Polynomial::constant(value.as_real().ok()?.into(), &variable)),
+        _ => None,
+    };
+    let (p, q) = match (polynomial(a), polynomial(b)) {
+        (Some(p), Some(q)) => (p, q),
+        _ => return Ok(None),
+    };
+    let result = match op {
+        BinaryOp::Add => &p + &q,
+        BinaryOp::Sub => &p - &q,
+        BinaryOp::Mul => &p * &q,
+        BinaryOp::Div | BinaryOp::Mod => {
+            let (quotient, remainder) = p.div_rem(&q)?;
+            match op {
+                BinaryOp::Div if remainder.is_zero() => quotient,
+                BinaryOp::Div => return Ok(None),
+                _ => remainder,
             }
-            return Real::Int(0);
         }
+        BinaryOp::Pow => match b.as_integer().ok().and_then(|n| u32::try_from(n).ok()) {
+            Some(exponent) if p.degree() as u64 * exponent as u64 <= MAX_POLYNOMIAL_DEGREE => p.pow(exponent),
+            _ => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(Value::Polynomial(Rc::new(result))))
+}
+
+fn symbolic_call(name: &str, args: Vec<Expr>) -> Value {
+    Value::from_expr(simplify(&Expr::Call(name.to_owned(), args)))
+}
+
+fn check_arity(function: &Chunk, arg_count: usize) -> Result<(), SamError> {
+    if function.arity() != arg_count {
+        return Err(Box::new(ErrorWithMessage::new(format!(
+            "{} expects {} arguments but was given {}",
+            function.name,
+            function.arity(),
+            arg_count
+        ))));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::parse_input;
+
+    use super::SamVM;
+    use crate::{
+        interpreter::data_types::{Real, Value},
+        AngleMode, Constant,
+    };
+
+    fn run(vm: &mut SamVM, input: &str) -> Real {
+        run_value(vm, input).as_real().unwrap()
+    }
+
+    fn run_value(vm: &mut SamVM, input: &str) -> Value {
+        vm.interpret(parse_input(input).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        let mut vm = SamVM::new();
+        run(&mut vm, "count(n) = n == 0 ? 0 : 1 + count(n - 1)");
+        assert!(run(&mut vm, "count(100000)") == Real::Int(100000));
+        run(&mut vm, "sub(a, b) = a - b");
+        assert!(run(&mut vm, "sub(10, 4)") == Real::Int(6));
+    }
+
+    #[test]
+    fn test_tail_calls_run_in_constant_space() {
+        let mut vm = SamVM::new();
+        run(&mut vm, "gcd(a, b) = b == 0 ? a : gcd(b, a % b)");
+        assert!(run(&mut vm, "gcd(48, 18)") == Real::Int(6));
+        run(&mut vm, "loop(n, acc) = n == 0 ? acc : loop(n - 1, acc + 1)");
+        assert!(run(&mut vm, "loop(2000000, 0)") == Real::Int(2000000));
+        assert!(vm.frames.is_empty());
+    }
+
+    #[test]
+    fn test_power_and_xor() {
+        let mut vm = SamVM::new();
+        assert!(run(&mut vm, "5 ^ 3") == Real::Int(125) && run(&mut vm, "5 ** 3") == Real::Int(125));
+        assert!(run(&mut vm, "xor(5, 3)") == Real::Int(6) && run(&mut vm, "xor(12, 10) | 1") == Real::Int(7));
+    }
+
+    #[test]
+    fn test_derivatives() {
+        let mut vm = SamVM::new();
+        let output = vm.interpret(parse_input("diff(x^2 * sin(x), x)").unwrap()).unwrap();
+        assert!(output.to_string() == "2*x*sin(x) + x^2*cos(x)");
+        run(&mut vm, "f(x) = x^3 + 2*x");
+        assert!(run(&mut vm, "f'(2)") == Real::Int(14));
+        assert!(run(&mut vm, "f''(2)") == Real::Int(12));
+        assert!(run(&mut vm, "sin'(0)") == Real::Int(1));
+        run(&mut vm, "g(x) = diff(x^2, x)");
+        assert!(run(&mut vm, "g(5)") == Real::Int(10));
+        vm.interpret(parse_input("d = diff(3 * y^2, y)").unwrap()).unwrap();
+        run(&mut vm, "y = 2");
+        assert!(run(&mut vm, "eval(d)") == Real::Int(12));
+        vm.interpret(parse_input("a = x^2 + s*x").unwrap()).unwrap();
+        run(&mut vm, "s = 5");
+        assert!(vm.interpret(parse_input("diff(a, x)").unwrap()).unwrap().to_string() == "s + 2*x");
+        assert!(vm.interpret(parse_input("diff(s*x, x)").unwrap()).unwrap().to_string() == "5");
+        vm.interpret(parse_input("p = poly([1, -3, 2])").unwrap()).unwrap();
+        assert!(vm.interpret(parse_input("diff(p, x)").unwrap()).unwrap().to_string() == "2*x - 3");
+        run(&mut vm, "k(c) = diff(c*z^2, z)");
+        assert!(vm.interpret(parse_input("k(3)").unwrap()).unwrap().to_string() == "6*z");
+
+        vm.interpret(parse_input("h = x -> x^2").unwrap()).unwrap();
+        assert!(run(&mut vm, "h'(2)") == Real::Int(4));
+        vm.interpret(parse_input("h = x -> x^3").unwrap()).unwrap();
+        assert!(run(&mut vm, "h'(2)") == Real::Int(12));
+    }
+
+    #[test]
+    fn test_gradient() {
+        let mut vm = SamVM::new();
+        run(&mut vm, "f(x, y) = x^2 * y + sin(x * y)");
+        let output = vm.interpret(parse_input("grad(f, [1, 2])").unwrap()).unwrap();
+        let expected = [4.0 + 2.0 * 2_f64.cos(), 1.0 + 2_f64.cos()];
+        let gradient = output.as_list().unwrap();
+        for (partial, expected) in gradient.iter().zip(expected) {
+            let partial: f64 = partial.as_real().unwrap().into();
+            assert!((partial - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_symbolic_values() {
+        let mut vm = SamVM::new();
+        let mut eval = |input: &str| vm.interpret(parse_input(input).unwrap()).unwrap().to_string();
+        assert!(eval("p = (x + 1) * (x - 1)") == "(x + 1)*(x - 1)");
+        assert!(eval("expand(p)") == "x^2 - 1");
+        assert!(eval("substitute(p, x, 3)") == "8");
+        assert!(eval("y + y") == "2*y");
+        assert!(eval("sin(y) + 1") == "sin(y) + 1");
+    }
+
+    #[test]
+    fn test_callable_arguments() {
+        let mut vm = SamVM::new();
+        vm.interpret(parse_input("square = (a) -> a * a").unwrap()).unwrap();
+        let mut eval = |input: &str| -> f64 { run(&mut vm, input).into() };
+        assert!((eval("root(x -> x^2 - 2, 0, 2)") - 2_f64.sqrt()).abs() < 1e-14);
+        assert!((eval("newton(cos, 1)") - std::f64::consts::FRAC_PI_2).abs() < 1e-14);
+        assert!(eval("square(7)") == 49.0);
+
+        // Lambdas capture the parameters of the function they appear in.
+        vm.interpret(parse_input("f(a) = root(x -> x^2 - a, 0, 10)").unwrap()).unwrap();
+        vm.interpret(parse_input("g(a) = newton(x -> x^2 - a, 1)").unwrap()).unwrap();
+        vm.interpret(parse_input("scale(a) = x -> a * x").unwrap()).unwrap();
+        vm.interpret(parse_input("triple = scale(3)").unwrap()).unwrap();
+        let mut eval = |input: &str| -> f64 { run(&mut vm, input).into() };
+        assert!((eval("f(2)") - 2_f64.sqrt()).abs() < 1e-14 && (eval("g(9)") - 3.0).abs() < 1e-14);
+        assert!(eval("triple(5)") == 15.0 && eval("triple'(1)") == 3.0);
+        assert!(run_value(&mut vm, "triple").to_string() == "lambda(x)");
+        assert!(vm.interpret(parse_input("fit((x, a) -> a*x, [], [], [1])").unwrap()).is_err());
+
+        // Functions passed as arguments can be called by their parameter name.
+        run(&mut vm, "apply(f, x) = f(x)");
+        run(&mut vm, "twice(f, x) = f(f(x))");
+        assert!(run(&mut vm, "apply(square, 4)") == Real::Int(16) && run(&mut vm, "twice(triple, 2)") == Real::Int(18));
+        assert!(run(&mut vm, "apply(x -> x + 1, 4)") == Real::Int(5));
+    }
+
+    #[test]
+    fn test_seeded_random_numbers() {
+        let (mut a, mut b) = (SamVM::with_seed(7), SamVM::new());
+        run(&mut b, "seed(7)");
+        for _ in 0..10 {
+            assert!(run(&mut a, "rand()") == run(&mut b, "rand()"));
+        }
+        let roll = run(&mut a, "randint(1, 6)");
+        assert!(roll >= Real::Int(1) && roll <= Real::Int(6));
+    }
+
+    #[test]
+    fn test_angle_modes() {
+        let mut vm = SamVM::new();
+        assert!(run(&mut vm, "sin(pi / 2)") == Real::Int(1));
+        assert!(run(&mut vm, "cos(180°)") == Real::Int(-1));
+        vm.set_angle_mode(AngleMode::Degrees);
+        assert!(run(&mut vm, "sin(30)") == Real::Float(0.5));
+        assert!(run(&mut vm, "tan(-45)") == Real::Int(-1));
+        assert!(run(&mut vm, "asin(1)") == Real::Int(90));
+        assert!(run(&mut vm, "atan2(1, -1)") == Real::Int(135));
+        assert!(run(&mut vm, "rad(pi)") == Real::Int(180));
+        let sine = run_value(&mut vm, "x -> sin(x)");
+        let slope = vm.gradient(&sine, &[0.0]).unwrap()[0];
+        assert!((slope - std::f64::consts::PI / 180.0).abs() < 1e-15);
+        // Primes agree with `grad`, for builtins and user functions alike.
+        run(&mut vm, "f(x) = sin(x)");
+        run(&mut vm, "g(x) = asin(x)");
+        for input in ["sin'(0)", "f'(0)", "1 / g'(0)", "1 / asin'(0)"] {
+            assert!((Into::<f64>::into(run(&mut vm, input)) - slope).abs() < 1e-15);
+        }
+        vm.set_angle_mode(AngleMode::Gradians);
+        assert!(run(&mut vm, "cos(200)") == Real::Int(-1));
+        assert!(run(&mut vm, "sin(90°)") == Real::Int(1));
+        vm.set_angle_mode(AngleMode::Radians);
+        assert!(run(&mut vm, "f'(0)") == Real::Int(1) && run(&mut vm, "sin'(0)") == Real::Int(1));
+    }
+
+    #[test]
+    fn test_constants() {
+        let mut vm = SamVM::new();
+        assert!(run(&mut vm, "phys.c") == Real::Int(299792458));
+        assert!(run(&mut vm, "G / phys.G") == Real::Int(1));
+        run(&mut vm, "c = 3");
+        assert!(run(&mut vm, "c * 2") == Real::Int(6));
+
+        vm.add_constant(Constant::new("units.ft", 0.3048, "m", 0.0, "international foot")).unwrap();
+        assert!(run(&mut vm, "10 * units.ft") == Real::Float(3.048));
+        assert!(vm.constant("units.ft").unwrap().unit == "m");
+        assert!(vm.add_constant(Constant::new("units.2ft", 0.6096, "m", 0.0, "")).is_err());
+        assert!(vm.constants().any(|constant| constant.name == "phys.k_B"));
+    }
+
+    #[test]
+    fn test_special_function_domains() {
+        let mut vm = SamVM::new();
+        assert!((Into::<f64>::into(run(&mut vm, "besselj(0, 1)")) - 0.7651976865579666).abs() < 1e-15);
+        assert!((Into::<f64>::into(run(&mut vm, "(-0.5)!")) - std::f64::consts::PI.sqrt()).abs() < 1e-14);
+        for input in ["besselj(0.5, 1)", "bessely(1.5, 2)", "fact(-1)", "(-1)!"] {
+            assert!(vm.interpret(parse_input(input).unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_exact_integer_literals() {
+        let mut vm = SamVM::new();
+        let mut eval = |input: &str| vm.interpret(parse_input(input).unwrap()).unwrap().to_string();
+        assert!(eval("2^62") == "4611686018427387904" && eval("2^64") == "18446744073709551616");
+        assert!(eval("isprime(2^61 - 1)") == "1" && eval("isprime(2305843009213693951)") == "1");
+        assert!(eval("123456789012345678901234567890 + 1") == "123456789012345678901234567891");
+        assert!(eval("7/2") == "3.5" && eval("6/3") == "2" && eval("7 % 0") == "NaN");
+        assert!(eval("diff(100000000000000000000 * x^2, x)") == "2*100000000000000000000*x");
+        assert!(eval("2^64 * y") == "18446744073709551616*y");
+    }
+
+    #[test]
+    fn test_big_integers() {
+        let mut vm = SamVM::new();
+        let mut eval = |input: &str| vm.interpret(parse_input(input).unwrap()).unwrap().to_string();
+        assert!(eval("25!") == "15511210043330985984000000");
+        assert!(eval("25! - 1") == "15511210043330985983999999");
+        assert!(eval("-(21!) < -(20!)") == "1");
+        assert!(eval("nCr(60, 30) * nCr(60, 30)") == "13986511252711760583915116323307776");
+        assert!(eval("fib(92) + fib(91)") == "12200160415121876738");
+        assert!(eval("25!/24!") == "25");
+        assert!(eval("2^64") == "18446744073709551616" && eval("2^64 * 2^64") == "340282366920938463463374607431768211456");
+        assert!(eval("9223372036854775807 + 1") == "9223372036854775808");
+        assert!(eval("-9223372036854775807 - 2") == "-9223372036854775809");
+
+        // Division, remainders and bitwise operations stay exact too.
+        assert!(eval("(2^64 + 1) % 7") == "3" && eval("(10^20 + 1) % 10") == "1" && eval("-(2^64 + 1) % 7") == "-3");
+        assert!(eval("fact(1000) / fact(999)") == "1000" && eval("(2^64 + 2) / 2") == "9223372036854775809");
+        assert!(eval("(2^64 + 1) / 2") == "9223372036854776000" && eval("fact(30) / fact(32)") == "0.0010080645161290322");
+        assert!(eval("(-9223372036854775807 - 1) / -1") == "9223372036854775808");
+        assert!(eval("2^64 & 1") == "0" && eval("(2^64 + 1) & 3") == "1" && eval("2^64 | 1") == "18446744073709551617");
+        assert!(eval("xor(2^64, 1)") == "18446744073709551617" && eval("xor(-1, 2^64)") == "-18446744073709551617");
+        assert!(eval("1 << 64") == "18446744073709551616" && eval("2^70 >> 68") == "4" && eval("-(2^70) >> 69") == "-2");
+        assert!(eval("8 << -2") == "2" && eval("-5 >> 100") == "-1" && eval("0 << 2^40") == "0");
+
+        // Rounding integers stays exact past 64 bits.
+        assert!(eval("abs(-9223372036854775807 - 1)") == "9223372036854775808" && eval("abs(-(2^70))") == "1180591620717411303424");
+        assert!(eval("round(9223372036854775807, -1)") == "9223372036854775810" && eval("round(-1250, -2)") == "-1300");
+        assert!(eval("round(2^64 + 5, -1)") == "18446744073709551620" && eval("sign(-(2^70))") == "-1" && eval("frac(2^70)") == "0");
+
+        // Number theory on big integers is exact or an error, never a float.
+        assert!(eval("factor(2^64)") == "[[2, 64]]" && eval("factor(2^64 + 1)") == "[[274177, 1], [67280421310721, 1]]");
+        assert!(eval("gcd(2^64, 3 * 2^70)") == "18446744073709551616" && eval("lcm(2^64, 3)") == "55340232221128654848");
+        assert!(eval("isqrt(10^30 + 5)") == "1000000000000000");
+        assert!(vm.interpret(parse_input("isprime(2^64 + 1)").unwrap()).is_err());
+        assert!(vm.interpret(parse_input("factor(2^89 - 1)").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_sums_and_products() {
+        let mut vm = SamVM::new();
+        assert!(run(&mut vm, "sum(k, 1, 100, k^2)") == Real::Int(338350));
+        assert!(run(&mut vm, "sum(1, 2, 3)") == Real::Int(6) && run(&mut vm, "prod([2, 3, 4])") == Real::Int(24));
+        assert!(run(&mut vm, "sum(k, 1, 0, k)") == Real::Int(0) && run(&mut vm, "prod(k, 1, 0, k)") == Real::Int(1));
+        assert!(run_value(&mut vm, "prod(k, 1, 30, k) == 30!").to_string() == "1");
+        assert!(run_value(&mut vm, "sum(k, 1, 3, x^k)").to_string() == "x^3 + x^2 + x");
+
+        // The index is local, and the body can use the enclosing function's parameters.
+        run(&mut vm, "k = 10");
+        run(&mut vm, "taylor(x, n) = sum(k, 0, n, x^k / k!)");
+        let close = |x: Real, y: f64| (Into::<f64>::into(x) - y).abs() < 1e-8 * y;
+        assert!(close(run(&mut vm, "taylor(1, 20)"), std::f64::consts::E));
+        assert!(run(&mut vm, "sum(j, 1, 3, sum(k, 1, j, k))") == Real::Int(10) && run(&mut vm, "k") == Real::Int(10));
+
+        assert!(close(run(&mut vm, "sum(k, 1, inf, 1/k^2)"), std::f64::consts::PI.powi(2) / 6.0));
+        assert!(close(run(&mut vm, "prod(k, 2, inf, 1 - 1/k^2)"), 0.5));
+        assert!(close(run(&mut vm, "sum(k, -inf, inf, 2^-abs(k))"), 3.0));
+        assert!(vm.interpret(parse_input("sum(k, 1, inf, 1/k)").unwrap()).is_err());
+        assert!(vm.interpret(parse_input("sum(k, 1.5, 3, k)").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_polynomials() {
+        let mut vm = SamVM::new();
+        run_value(&mut vm, "p = poly([1, -3, 2])");
+        assert!(run_value(&mut vm, "p").to_string() == "x^2 - 3*x + 2");
+        assert!(run_value(&mut vm, "p * poly([1, 1])").to_string() == "x^3 - 2*x^2 - x + 2");
+        assert!(run_value(&mut vm, "p / poly([1, -1])").to_string() == "x - 2");
+        assert!(run_value(&mut vm, "polydiv(p, [1, 1])").to_string() == "[x - 4, 6]");
+        assert!(run(&mut vm, "p(3)") == Real::Float(2.0) && run(&mut vm, "polyval([1, 2, 3], 2)") == Real::Float(11.0));
+        assert!(run_value(&mut vm, "p(z)").to_string() == "z^2 - 3*z + 2");
+        assert!(run_value(&mut vm, "poly((x - 1)^2 * (x + 2))").to_string() == "x^3 - 3*x + 2");
+        assert!(run_value(&mut vm, "roots(poly((x - 1)^2 * (x + 2)))").to_string() == "[-2, 1, 1]");
+        assert!(run_value(&mut vm, "roots(poly(y^2 + 1))").to_string() == "[-i, i]");
+        assert!(run_value(&mut vm, "polyder(p)").to_string() == "2*x - 3");
+        assert!(run_value(&mut vm, "polyint(polyder(p), 2)").to_string() == "x^2 - 3*x + 2");
+        assert!(run_value(&mut vm, "polyfit([0, 1, 2, 3], [1, 3, 5, 7], 1)").to_string() == "2*x + 1");
+        assert!(vm.interpret(parse_input("poly(a*x + b)").unwrap()).is_err());
+        assert!(run_value(&mut vm, "p / poly([1, 1])").to_string() == "(x^2 - 3*x + 2)/(x + 1)");
+        for input in ["p / poly([0])", "p / 0", "p % 0", "polydiv(p, [0])", "polydiv(p, poly([1, 1], t))"] {
+            assert!(vm.interpret(parse_input(input).unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_error_backtrace() {
+        let mut vm = SamVM::new();
+        run(&mut vm, "f(x) = g(x)");
+        let err = vm.interpret(parse_input("f(2)").unwrap()).unwrap_err();
+        assert!(err.to_string() == "unknown function g\n    in f(2)");
+
+        // Runaway recursion shows both ends of the call stack, not a million frames.
+        run(&mut vm, "h(n) = n == 0 ? 0 : 1 + h(n - 1)");
+        let err = vm.interpret(parse_input("h(-1)").unwrap()).unwrap_err().to_string();
+        let lines: Vec<&str> = err.lines().collect();
+        assert!(lines.len() == 22 && lines[1] == "    in h(-999999)" && lines[21] == "    in h(-1)");
+        assert!(lines[11] == "    ... 999979 more frames");
     }
 }
@@ -1,9 +1,11 @@
 pub mod parser;
 pub mod interpreter;
 pub mod errors;
-mod ast;
+pub mod ast;
 mod algorithms;
 mod util;
+pub mod compiler;
+pub mod symbolic;
 
 extern crate pest;
 #[macro_use]
@@ -11,4 +13,6 @@ extern crate pest_derive;
 
 pub use parser::parse_input;
 pub use interpreter::virtual_machine::SamVM;
-pub use errors::SamError;
\ No newline at end of file
+pub use errors::SamError;
+pub use algorithms::trigonometry::AngleMode;
+pub use interpreter::constants::Constant;
\ No newline at end of file
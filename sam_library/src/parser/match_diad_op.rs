@@ -1,29 +1,30 @@
-use crate::ast::operations::Operation;
+use crate::ast::operators::BinaryOp;
 
 use super::grammar::SamRule;
 
 
-pub fn match_diad_op(op: SamRule) -> Option<Operation> {
+pub fn match_diad_op(op: SamRule) -> Option<BinaryOp> {
     match op {
-        SamRule::Add => Some(Operation::Add),
-        SamRule::Subtract => Some(Operation::Sub),
-        SamRule::Multiply => Some(Operation::Mul),
-        SamRule::Divide => Some(Operation::Div),
-        SamRule::Power => Some(Operation::Pow),
-        SamRule::Modulus => Some(Operation::Mod),
-        SamRule::Gt => Some(Operation::Gt),
-        SamRule::Lt => Some(Operation::Lt),
-        SamRule::Gte => Some(Operation::Gte),
-        SamRule::Lte => Some(Operation::Lte),
-        SamRule::Eq => Some(Operation::Eq),
-        SamRule::Neq => Some(Operation::Neq),
-        SamRule::Xor => Some(Operation::BitXor),
-        SamRule::And => Some(Operation::BitAnd),
-        SamRule::Or => Some(Operation::BitOr),
-        SamRule::RightShift => Some(Operation::RightShift),
-        SamRule::LeftShift => Some(Operation::LeftShift),
-        SamRule::BoolOr => Some(Operation::BoolOr),
-        SamRule::BoolAnd => Some(Operation::BoolAnd),
+        SamRule::Add => Some(BinaryOp::Add),
+        SamRule::Subtract => Some(BinaryOp::Sub),
+        SamRule::Multiply => Some(BinaryOp::Mul),
+        SamRule::Divide => Some(BinaryOp::Div),
+        SamRule::LeftDivide => Some(BinaryOp::LeftDiv),
+        SamRule::Power => Some(BinaryOp::Pow),
+        SamRule::Modulus => Some(BinaryOp::Mod),
+        SamRule::Gt => Some(BinaryOp::Gt),
+        SamRule::Lt => Some(BinaryOp::Lt),
+        SamRule::Gte => Some(BinaryOp::Gte),
+        SamRule::Lte => Some(BinaryOp::Lte),
+        SamRule::Eq => Some(BinaryOp::Eq),
+        SamRule::Neq => Some(BinaryOp::Neq),
+        SamRule::And => Some(BinaryOp::BitAnd),
+        SamRule::Or => Some(BinaryOp::BitOr),
+        SamRule::RightShift => Some(BinaryOp::RightShift),
+        SamRule::LeftShift => Some(BinaryOp::LeftShift),
+        SamRule::BoolOr => Some(BinaryOp::BoolOr),
+        SamRule::BoolAnd => Some(BinaryOp::BoolAnd),
+        SamRule::Equation => Some(BinaryOp::Equation),
         _ => None
     }
 }
\ No newline at end of file
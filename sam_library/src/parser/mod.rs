@@ -1,34 +1,66 @@
 mod grammar;
 mod match_diad_op;
-mod evaluator;
+mod tree_builder;
 
 use pest::Parser;
 
-use crate::ast::ast_block::AstBlock;
+use crate::ast::expression::Expr;
 
-pub fn parse_input(input: &str) -> Result<AstBlock, crate::errors::SamError> {
-    let mut evaluator = evaluator::SamEvaluator::new();
+pub fn parse_input(input: &str) -> Result<Expr, crate::errors::SamError> {
     let mut pairs = grammar::SamParser::parse(grammar::SamRule::Calculation, input)?;
-    let output = evaluator.eval(&mut pairs)?;
-    return Ok(output);
+    let statement = pairs.next().unwrap();
+    tree_builder::build_statement(statement)
 }
 
 #[cfg(test)]
 mod test {
-    use crate::ast::operations::Operation;
+    use crate::ast::{expression::Expr, operators::{BinaryOp, UnaryOp}};
+    use crate::interpreter::data_types::Real;
+
+    fn num(x: f64) -> Expr {
+        Expr::Literal(Real::Float(x))
+    }
 
     #[test]
-    fn test_shunting_yard(){
+    fn test_operator_precedence(){
         let output = super::parse_input("1 + 2 * 2").unwrap();
         println!("{:?}", &output);
-        assert!(output == vec![Operation::Float(1.0), Operation::Float(2.0), Operation::Float(2.0), Operation::Mul, Operation::Add]);
+        assert!(output == Expr::binary(BinaryOp::Add, num(1.0), Expr::binary(BinaryOp::Mul, num(2.0), num(2.0))));
 
         let output = super::parse_input("2 ** 3 * 4 + 8 % 3 + (8 * 8)").unwrap();
         println!("{:?}", &output);
-        assert!(output == vec![
-            Operation::Float(2.0), Operation::Float(3.0), Operation::Pow, 
-            Operation::Float(4.0), Operation::Mul, 
-            Operation::Float(8.0), Operation::Float(3.0), Operation::Mod, Operation::Add, 
-            Operation::Float(8.0), Operation::Float(8.0), Operation::Mul, Operation::Add]);
+        assert!(output == Expr::binary(BinaryOp::Add,
+            Expr::binary(BinaryOp::Add,
+                Expr::binary(BinaryOp::Mul, Expr::binary(BinaryOp::Pow, num(2.0), num(3.0)), num(4.0)),
+                Expr::binary(BinaryOp::Mod, num(8.0), num(3.0))),
+            Expr::binary(BinaryOp::Mul, num(8.0), num(8.0))));
+
+        let output = super::parse_input("-2 ** 2").unwrap();
+        assert!(output == Expr::unary(UnaryOp::Neg, Expr::binary(BinaryOp::Pow, num(2.0), num(2.0))));
+
+        let output = super::parse_input("1 + 2 & 3").unwrap();
+        assert!(output.to_string() == "1 + 2 & 3");
+        assert!(matches!(output, Expr::Binary(BinaryOp::BitAnd, _, _)));
+
+        let output = super::parse_input("2 ** 3 ** 2 |> f(1)").unwrap();
+        assert!(output.to_string() == "f(2^3^2, 1)");
+
+        let output = super::parse_input("-3! + 2^n!").unwrap();
+        assert!(output.to_string() == "-fact(3) + 2^fact(n)");
+
+        let output = super::parse_input("sin(30 deg) + cos(x°)").unwrap();
+        assert!(output.to_string() == "sin(deg(30)) + cos(deg(x))");
+        assert!(super::parse_input("30degrees").is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_number_literals() {
+        assert!(matches!(super::parse_input("42").unwrap(), Expr::Literal(Real::Int(42))));
+        assert!(matches!(super::parse_input("9223372036854775807").unwrap(), Expr::Literal(Real::Int(i64::MAX))));
+        assert!(matches!(super::parse_input("4.0").unwrap(), Expr::Literal(Real::Float(_))));
+        assert!(matches!(super::parse_input("1e3").unwrap(), Expr::Literal(Real::Float(_))));
+        let output = super::parse_input("123456789012345678901234567890").unwrap();
+        assert!(matches!(&output, Expr::BigInteger(_)) && output.to_string() == "123456789012345678901234567890");
+        assert!(super::parse_input("0000000000000000000000000000012").unwrap().to_string() == "12");
+    }
+}
@@ -0,0 +1,211 @@
+use std::{iter::Peekable, rc::Rc, vec::IntoIter};
+
+use pest::iterators::Pair;
+
+use crate::{
+    algorithms::big_integer::BigInt,
+    ast::{
+        expression::Expr,
+        operators::{BinaryOp, UnaryOp, UNARY_PRECEDENCE},
+    },
+    errors::{ErrorWithMessage, SamError},
+    interpreter::data_types::Real,
+};
+
+use super::{grammar::SamRule, match_diad_op::match_diad_op};
+
+pub fn build_statement(pair: Pair<SamRule>) -> Result<Expr, SamError> {
+    match pair.as_rule() {
+        SamRule::Assignment => {
+            let mut inner = pair.into_inner();
+            let name = inner.next().unwrap().as_str().trim().to_owned();
+            let value = build_expression(inner.next().unwrap())?;
+            Ok(Expr::Assignment(name, Box::new(value)))
+        }
+        SamRule::FunctionDeclaration => {
+            let mut inner = pair.into_inner();
+            let name = inner.next().unwrap().as_str().trim().to_owned();
+            let params = inner
+                .next()
+                .unwrap()
+                .into_inner()
+                .map(|param| param.as_str().trim().to_owned())
+                .collect();
+            let body = build_expression(inner.next().unwrap())?;
+            Ok(Expr::FunctionDeclaration(name, params, Box::new(body)))
+        }
+        _ => build_expression(pair),
+    }
+}
+
+/// Turns the flat `Term (Operation Term)*` sequence of an `Expression` into a
+/// tree using precedence climbing. Pipes bind loosest of all and a trailing
+/// ternary applies to everything before it.
+fn build_expression(pair: Pair<SamRule>) -> Result<Expr, SamError> {
+    let mut segments = vec![OperatorSequence::new()];
+    let mut unary = None;
+    let mut ternary = None;
+
+    for pair in pair.into_inner() {
+        let sequence = segments.last_mut().unwrap();
+        match pair.as_rule() {
+            SamRule::Neg => unary = Some(UnaryOp::Neg),
+            SamRule::Not => unary = Some(UnaryOp::Not),
+            SamRule::BitCompliment => unary = Some(UnaryOp::BitCompliment),
+            SamRule::Factorial => {
+                let (_, term) = sequence.terms.last_mut().unwrap();
+                *term = Expr::Call("fact".to_owned(), vec![term.clone()]);
+            }
+            SamRule::Degrees => {
+                let (_, term) = sequence.terms.last_mut().unwrap();
+                *term = Expr::Call("deg".to_owned(), vec![term.clone()]);
+            }
+            SamRule::Pipe => segments.push(OperatorSequence::new()),
+            SamRule::ConditionalOperator => {
+                let mut inner = pair.into_inner();
+                let then = build_expression(inner.next().unwrap())?;
+                let otherwise = build_expression(inner.next().unwrap())?;
+                ternary = Some((then, otherwise));
+            }
+            rule => match match_diad_op(rule) {
+                Some(op) => sequence.operators.push(op),
+                None => sequence.terms.push((unary.take(), build_term(pair)?)),
+            },
+        }
+    }
+
+    let mut segments = segments.into_iter();
+    let mut expr = segments.next().unwrap().into_tree();
+    for segment in segments {
+        expr = match segment.into_tree() {
+            Expr::Variable(name) => Expr::Call(name, vec![expr]),
+            Expr::Call(name, mut args) => {
+                args.insert(0, expr);
+                Expr::Call(name, args)
+            }
+            _ => return Err(ErrorWithMessage::new_box("can only pipe into a function")),
+        }
+    }
+
+    Ok(match ternary {
+        Some((then, otherwise)) => Expr::Conditional(Box::new(expr), Box::new(then), Box::new(otherwise)),
+        None => expr,
+    })
+}
+
+/// A whole number too large for `i64` becomes a big integer literal, built
+/// up 18 digits at a time.
+fn integer_literal(digits: &str) -> Expr {
+    if let Ok(n) = digits.parse::<i64>() {
+        return Expr::Literal(Real::Int(n));
+    }
+    let n = digits.as_bytes().chunks(18).fold(BigInt::zero(), |n, chunk| {
+        let chunk = std::str::from_utf8(chunk).unwrap();
+        let scale = BigInt::from(10_u64).pow(chunk.len() as u32);
+        &(&n * &scale) + &BigInt::from(chunk.parse::<u64>().unwrap())
+    });
+    Expr::BigInteger(Rc::new(n))
+}
+
+fn build_term(pair: Pair<SamRule>) -> Result<Expr, SamError> {
+    let expr = match pair.as_rule() {
+        SamRule::Expression => build_expression(pair)?,
+        SamRule::Float => Expr::Literal(Real::Float(pair.as_str().parse::<f64>()?)),
+        SamRule::Integer => integer_literal(pair.as_str()),
+        SamRule::Hexadecimal => parse_radix(pair.as_str(), "0x", 16)?,
+        SamRule::Octal => parse_radix(pair.as_str(), "0o", 8)?,
+        SamRule::Binary => parse_radix(pair.as_str(), "0b", 2)?,
+        SamRule::PeekStack => Expr::PeekStack,
+        SamRule::List => Expr::List(pair.into_inner().map(build_expression).collect::<Result<Vec<Expr>, SamError>>()?),
+        SamRule::Variable => Expr::Variable(pair.as_str().trim().to_owned()),
+        SamRule::QualifiedName => Expr::Variable(pair.as_str().to_owned()),
+        SamRule::Lambda => {
+            let mut inner = pair.into_inner();
+            let params = inner.next().unwrap();
+            let params = match params.as_rule() {
+                SamRule::Parameters => params.into_inner().map(|param| param.as_str().trim().to_owned()).collect(),
+                _ => vec![params.as_str().trim().to_owned()],
+            };
+            let body = build_expression(inner.next().unwrap())?;
+            Expr::Lambda(params, Box::new(body))
+        }
+        SamRule::FunctionInvocation => {
+            let mut inner = pair.into_inner().peekable();
+            let mut name = inner.next().unwrap().as_str().trim().to_owned();
+            if let Some(primes) = inner.next_if(|pair| pair.as_rule() == SamRule::Derivative) {
+                name.push_str(primes.as_str());
+            }
+            let args = inner.map(build_expression).collect::<Result<Vec<Expr>, SamError>>()?;
+            Expr::Call(name, args)
+        }
+        _ => return Err(ErrorWithMessage::new_box("unexpected token")),
+    };
+    Ok(expr)
+}
+
+fn parse_radix(literal: &str, prefix: &str, radix: u32) -> Result<Expr, SamError> {
+    let digits = literal.trim_start_matches(prefix).replace('_', "");
+    Ok(Expr::Literal(Real::Int(i64::from_str_radix(&digits, radix)?)))
+}
+
+struct OperatorSequence {
+    terms: Vec<(Option<UnaryOp>, Expr)>,
+    operators: Vec<BinaryOp>,
+}
+
+impl OperatorSequence {
+    fn new() -> OperatorSequence {
+        OperatorSequence {
+            terms: vec![],
+            operators: vec![],
+        }
+    }
+
+    fn into_tree(self) -> Expr {
+        let mut climber = PrecedenceClimber {
+            terms: self.terms.into_iter(),
+            operators: self.operators.into_iter().peekable(),
+        };
+        let lhs = climber.operand();
+        climber.climb(lhs, 0)
+    }
+}
+
+struct PrecedenceClimber {
+    terms: IntoIter<(Option<UnaryOp>, Expr)>,
+    operators: Peekable<IntoIter<BinaryOp>>,
+}
+
+impl PrecedenceClimber {
+    fn operand(&mut self) -> Expr {
+        let (unary, term) = self.terms.next().unwrap();
+        match unary {
+            Some(op) => {
+                let operand = self.climb(term, UNARY_PRECEDENCE + 1);
+                Expr::unary(op, operand)
+            }
+            None => term,
+        }
+    }
+
+    fn climb(&mut self, mut lhs: Expr, min_precedence: u8) -> Expr {
+        while let Some(&op) = self.operators.peek() {
+            if op.precedence() < min_precedence {
+                break;
+            }
+            self.operators.next();
+            let mut rhs = self.operand();
+            while let Some(&next) = self.operators.peek() {
+                if next.precedence() > op.precedence() {
+                    rhs = self.climb(rhs, op.precedence() + 1);
+                } else if next.is_right_associative() && next.precedence() == op.precedence() {
+                    rhs = self.climb(rhs, op.precedence());
+                } else {
+                    break;
+                }
+            }
+            lhs = Expr::binary(op, lhs, rhs);
+        }
+        lhs
+    }
+}
@@ -0,0 +1,700 @@
+use std::cmp::{Ordering, Reverse};
+
+use crate::{
+    ast::{
+        expression::Expr,
+        operators::{BinaryOp, UnaryOp},
+    },
+    compiler::optimizer::can_fold,
+    interpreter::data_types::Real,
+};
+
+use super::{
+    coefficient::{gcd, Coefficient},
+    depends_on,
+    rules::rewrite,
+};
+
+/// Sums raised to a higher power than this are left unexpanded.
+const MAX_EXPANSION_POWER: i64 = 32;
+
+/// Rewrites `expr` as a canonical sum of products, combining like terms but
+/// leaving products of sums alone: `x*x + x - 1 + x` is `x^2 + 2*x - 1`.
+pub fn simplify(expr: &Expr) -> Expr {
+    canonical(expr, false)
+}
+
+/// Like `simplify`, but also multiplies out products and integer powers of
+/// sums: `(x + 1)^2` is `x^2 + 2*x + 1`.
+pub fn expand(expr: &Expr) -> Expr {
+    canonical(expr, true)
+}
+
+/// Expands `expr` and groups its terms by powers of `name`, highest first.
+pub fn collect(expr: &Expr, name: &str) -> Expr {
+    let sum = Sum::from_expr(&rewrite(expr), true);
+    let variable = Expr::Variable(name.to_owned());
+    let mut groups: Vec<(i64, Vec<Term>)> = vec![];
+    for term in sum.terms {
+        let power = term.exponent_of(&variable);
+        let rest = term.mul(&Term::power(variable.clone(), -power));
+        match groups.iter_mut().find(|(p, _)| *p == power) {
+            Some((_, terms)) => terms.push(rest),
+            None => groups.push((power, vec![rest])),
+        }
+    }
+    groups.sort_by_key(|(power, _)| Reverse(*power));
+
+    let terms = groups
+        .into_iter()
+        .map(|(power, rest)| {
+            let mut coefficient = Sum::from_terms(rest);
+            let coefficient = match coefficient.terms.len() {
+                1 => coefficient.terms.remove(0),
+                _ => Term::atom(coefficient.to_expr()),
+            };
+            coefficient.mul(&Term::power(variable.clone(), power))
+        })
+        .collect();
+    Sum { terms }.to_expr()
+}
+
+/// Factors a polynomial in one variable over the rationals by finding its
+/// rational roots. Anything else only has its common factors taken out.
+pub fn factor(expr: &Expr) -> Expr {
+    let sum = Sum::from_expr(&rewrite(expr), true);
+    match sum.univariate() {
+        Some((variable, coefficients)) => factor_polynomial(variable, coefficients).unwrap_or_else(|| sum.to_expr()),
+        None => factor_common(sum),
+    }
+}
+
+fn canonical(expr: &Expr, expand: bool) -> Expr {
+    let once = Sum::from_expr(&rewrite(expr), expand).to_expr();
+    let rewritten = rewrite(&once);
+    if rewritten == once {
+        once
+    } else {
+        Sum::from_expr(&rewritten, expand).to_expr()
+    }
+}
+
+/// `coefficient * base1^exponent1 * base2^exponent2 * ...` with the bases
+/// in canonical order and no base repeated.
+#[derive(Clone, Debug, PartialEq)]
+struct Term {
+    coefficient: Coefficient,
+    factors: Vec<(Expr, i64)>,
+}
+
+impl Term {
+    fn constant(coefficient: Coefficient) -> Term {
+        Term {
+            coefficient,
+            factors: vec![],
+        }
+    }
+
+    fn atom(expr: Expr) -> Term {
+        Term::power(expr, 1)
+    }
+
+    fn power(base: Expr, exponent: i64) -> Term {
+        let factors = if exponent == 0 { vec![] } else { vec![(base, exponent)] };
+        Term {
+            coefficient: Coefficient::int(1),
+            factors,
+        }
+    }
+
+    fn mul(&self, other: &Term) -> Term {
+        let mut factors = self.factors.clone();
+        for (base, exponent) in &other.factors {
+            match factors.iter_mut().find(|(b, _)| b == base) {
+                Some((_, e)) => *e += exponent,
+                None => factors.push((base.clone(), *exponent)),
+            }
+        }
+        factors.retain(|(_, exponent)| *exponent != 0);
+        factors.sort_by(compare_factors);
+        Term {
+            coefficient: self.coefficient.mul(other.coefficient),
+            factors,
+        }
+    }
+
+    fn pow(&self, exponent: i64) -> Term {
+        Term {
+            coefficient: self.coefficient.pow(exponent),
+            factors: self
+                .factors
+                .iter()
+                .map(|(base, e)| (base.clone(), e.saturating_mul(exponent)))
+                .collect(),
+        }
+    }
+
+    fn neg(&self) -> Term {
+        Term {
+            coefficient: self.coefficient.neg(),
+            factors: self.factors.clone(),
+        }
+    }
+
+    fn degree(&self) -> i64 {
+        self.factors.iter().map(|(_, exponent)| (*exponent).max(0)).sum()
+    }
+
+    fn exponent_of(&self, base: &Expr) -> i64 {
+        self.factors
+            .iter()
+            .find(|(b, _)| b == base)
+            .map_or(0, |(_, exponent)| *exponent)
+    }
+
+    /// Writes the term with positive powers over negative ones, e.g. `3*x/(2*y)`.
+    fn to_expr(&self) -> Expr {
+        let mut numerator = vec![];
+        let mut denominator = vec![];
+        for (base, exponent) in &self.factors {
+            match exponent.cmp(&0) {
+                Ordering::Greater => numerator.push(power_expr(base, *exponent)),
+                _ => denominator.push(power_expr(base, -exponent)),
+            }
+        }
+        let (scale, divisor) = match self.coefficient {
+            Coefficient::Ratio(n, d) => (Real::Int(n), d),
+            Coefficient::Float(x) => (Real::Float(x), 1),
+        };
+
+        let numerator = if numerator.is_empty() {
+            Expr::Literal(scale)
+        } else if scale == Real::Int(1) {
+            product(numerator)
+        } else if scale == Real::Int(-1) {
+            numerator[0] = Expr::unary(UnaryOp::Neg, numerator[0].clone());
+            product(numerator)
+        } else {
+            numerator.insert(0, Expr::Literal(scale));
+            product(numerator)
+        };
+        if divisor != 1 {
+            denominator.insert(0, Expr::Literal(Real::Int(divisor)));
+        }
+        match denominator.is_empty() {
+            true => numerator,
+            false => Expr::binary(BinaryOp::Div, numerator, product(denominator)),
+        }
+    }
+}
+
+/// A sum of unlike terms in canonical order: highest degree first, then by
+/// the order of the factors.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct Sum {
+    terms: Vec<Term>,
+}
+
+impl Sum {
+    fn constant(coefficient: Coefficient) -> Sum {
+        Sum::from_terms(vec![Term::constant(coefficient)])
+    }
+
+    fn atom(expr: Expr) -> Sum {
+        Sum {
+            terms: vec![Term::atom(expr)],
+        }
+    }
+
+    fn from_terms(terms: Vec<Term>) -> Sum {
+        let mut combined: Vec<Term> = vec![];
+        for term in terms {
+            match combined.iter_mut().find(|t| t.factors == term.factors) {
+                Some(t) => t.coefficient = t.coefficient.add(term.coefficient),
+                None => combined.push(term),
+            }
+        }
+        combined.retain(|term| !term.coefficient.is_zero());
+        let mut sum = Sum { terms: combined };
+        if sum.apply_pythagorean_identity() {
+            return Sum::from_terms(sum.terms);
+        }
+        sum.terms.sort_by(compare_terms);
+        sum
+    }
+
+    fn from_expr(expr: &Expr, expand: bool) -> Sum {
+        match expr {
+            Expr::Literal(x) => Sum::constant(Coefficient::from(*x)),
+            Expr::Unary(UnaryOp::Neg, x) => Sum::from_expr(x, expand).neg(),
+            Expr::Unary(op, x) => {
+                let x = Sum::from_expr(x, expand);
+                match x.as_constant() {
+                    Some(c) => Sum::constant(Coefficient::from(op.apply(c.to_real()))),
+                    None => Sum::atom(Expr::unary(*op, x.to_expr())),
+                }
+            }
+            Expr::Binary(op, a, b) => {
+                let a = Sum::from_expr(a, expand);
+                let b = Sum::from_expr(b, expand);
+                match op {
+                    BinaryOp::Add => a.add(b),
+                    BinaryOp::Sub => a.add(b.neg()),
+                    BinaryOp::Mul => a.mul(b, expand),
+                    BinaryOp::Div => a.mul(b.pow(&Sum::constant(Coefficient::int(-1)), false), expand),
+                    BinaryOp::LeftDiv => b.mul(a.pow(&Sum::constant(Coefficient::int(-1)), false), expand),
+                    BinaryOp::Pow => a.pow(&b, expand),
+                    _ => match (a.as_constant(), b.as_constant()) {
+                        (Some(x), Some(y)) if can_fold(*op, x.to_real(), y.to_real()) => {
+                            Sum::constant(Coefficient::from(op.apply(x.to_real(), y.to_real())))
+                        }
+                        _ => Sum::atom(Expr::binary(*op, a.to_expr(), b.to_expr())),
+                    },
+                }
+            }
+            Expr::Call(name, args) => Sum::atom(Expr::Call(
+                name.clone(),
+                args.iter().map(|arg| Sum::from_expr(arg, expand).to_expr()).collect(),
+            )),
+            Expr::List(items) => Sum::atom(Expr::List(
+                items.iter().map(|item| Sum::from_expr(item, expand).to_expr()).collect(),
+            )),
+            _ => Sum::atom(expr.clone()),
+        }
+    }
+
+    pub(super) fn as_constant(&self) -> Option<Coefficient> {
+        match self.terms.as_slice() {
+            [] => Some(Coefficient::int(0)),
+            [term] if term.factors.is_empty() => Some(term.coefficient),
+            _ => None,
+        }
+    }
+
+    /// The sum as a single term, wrapping it up as an opaque factor when it
+    /// has more than one.
+    fn into_term(self) -> Term {
+        match self.terms.len() {
+            0 => Term::constant(Coefficient::int(0)),
+            1 => self.terms.into_iter().next().unwrap(),
+            _ => Term::atom(self.to_expr()),
+        }
+    }
+
+    fn add(mut self, other: Sum) -> Sum {
+        self.terms.extend(other.terms);
+        Sum::from_terms(self.terms)
+    }
+
+    fn neg(self) -> Sum {
+        Sum {
+            terms: self.terms.iter().map(Term::neg).collect(),
+        }
+    }
+
+    fn mul(self, other: Sum, expand: bool) -> Sum {
+        let distribute = expand
+            || self.terms.len() <= 1 && other.terms.len() <= 1
+            || self.as_constant().is_some()
+            || other.as_constant().is_some();
+        if !distribute {
+            return Sum::from_terms(vec![self.into_term().mul(&other.into_term())]);
+        }
+        let mut terms = vec![];
+        for a in &self.terms {
+            for b in &other.terms {
+                terms.push(a.mul(b));
+            }
+        }
+        Sum::from_terms(terms)
+    }
+
+    fn pow(self, exponent: &Sum, expand: bool) -> Sum {
+        let integer = match exponent.as_constant() {
+            Some(Coefficient::Ratio(n, 1)) => Some(n),
+            _ => None,
+        };
+        match (integer, self.terms.len()) {
+            (Some(0), _) => Sum::constant(Coefficient::int(1)),
+            (Some(_), 0) => Sum::constant(Coefficient::int(0).pow(integer.unwrap())),
+            (Some(n), 1) => Sum::from_terms(vec![self.terms[0].pow(n)]),
+            (Some(n), _) if expand && (1..=MAX_EXPANSION_POWER).contains(&n) => {
+                let mut result = self.clone();
+                for _ in 1..n {
+                    result = result.mul(self.clone(), true);
+                }
+                result
+            }
+            (Some(n), _) => Sum::from_terms(vec![Term::power(self.to_expr(), n)]),
+            (None, _) => match (self.as_constant(), exponent.as_constant()) {
+                (Some(x), Some(y)) if matches!(x, Coefficient::Float(_)) || matches!(y, Coefficient::Float(_)) => {
+                    Sum::constant(Coefficient::Float(x.to_f64().powf(y.to_f64())))
+                }
+                _ => Sum::atom(Expr::binary(BinaryOp::Pow, self.to_expr(), exponent.to_expr())),
+            },
+        }
+    }
+
+    /// Replaces `c*sin(u)^2*r + c*cos(u)^2*r` with `c*r`.
+    fn apply_pythagorean_identity(&mut self) -> bool {
+        for i in 0..self.terms.len() {
+            for (k, (base, exponent)) in self.terms[i].factors.iter().enumerate() {
+                let argument = match base {
+                    Expr::Call(name, args) if name == "sin" && args.len() == 1 && *exponent == 2 => &args[0],
+                    _ => continue,
+                };
+                let mut rest = self.terms[i].clone();
+                rest.factors.remove(k);
+                let partner = rest.mul(&Term::power(Expr::Call("cos".to_owned(), vec![argument.clone()]), 2));
+                if let Some(j) = self.terms.iter().position(|term| *term == partner) {
+                    self.terms[i] = rest;
+                    self.terms.remove(j);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// The variable and coefficients, lowest power first, if this is a
+    /// polynomial in one variable with exact coefficients.
+    fn univariate(&self) -> Option<(Expr, Vec<Coefficient>)> {
+        let mut variable = None;
+        let mut coefficients = vec![];
+        for term in &self.terms {
+            if !matches!(term.coefficient, Coefficient::Ratio(_, _)) {
+                return None;
+            }
+            let power = match term.factors.as_slice() {
+                [] => 0,
+                [(base @ Expr::Variable(_), power)] if *power > 0 && *power <= MAX_EXPANSION_POWER => {
+                    if variable.get_or_insert_with(|| base.clone()) != base {
+                        return None;
+                    }
+                    *power as usize
+                }
+                _ => return None,
+            };
+            if coefficients.len() <= power {
+                coefficients.resize(power + 1, Coefficient::int(0));
+            }
+            coefficients[power] = term.coefficient;
+        }
+        Some((variable?, coefficients))
+    }
+
+    pub(super) fn to_expr(&self) -> Expr {
+        let mut terms = self.terms.iter();
+        let first = match terms.next() {
+            Some(term) => term.to_expr(),
+            None => return Expr::Literal(Real::Int(0)),
+        };
+        terms.fold(first, |sum, term| match term.coefficient.is_negative() {
+            true => Expr::binary(BinaryOp::Sub, sum, term.neg().to_expr()),
+            false => Expr::binary(BinaryOp::Add, sum, term.to_expr()),
+        })
+    }
+}
+
+/// Takes out the numeric content and the monomial common to every term.
+fn factor_common(sum: Sum) -> Expr {
+    if sum.terms.len() < 2 {
+        return sum.to_expr();
+    }
+    let mut common = sum.terms[0].clone();
+    if sum.terms.iter().all(|term| matches!(term.coefficient, Coefficient::Ratio(_, _))) {
+        let content = sum.terms.iter().fold((0, 1), |(n, d), term| match term.coefficient {
+            Coefficient::Ratio(a, b) => (gcd(n, a), lcm(d, b).unwrap_or(d)),
+            Coefficient::Float(_) => (n, d),
+        });
+        let sign = if sum.terms[0].coefficient.is_negative() { -1 } else { 1 };
+        common.coefficient = Coefficient::ratio(sign * content.0, content.1);
+    } else {
+        common.coefficient = Coefficient::int(1);
+    }
+    common.factors = common
+        .factors
+        .iter()
+        .filter(|(_, exponent)| *exponent > 0)
+        .map(|(base, _)| {
+            let smallest = sum.terms.iter().map(|term| term.exponent_of(base)).min().unwrap_or(0);
+            (base.clone(), smallest.max(0))
+        })
+        .filter(|(_, exponent)| *exponent > 0)
+        .collect();
+    if common == Term::constant(Coefficient::int(1)) {
+        return sum.to_expr();
+    }
+
+    let inverse = common.pow(-1);
+    let rest = Sum::from_terms(sum.terms.iter().map(|term| term.mul(&inverse)).collect());
+    common.mul(&Term::atom(rest.to_expr())).to_expr()
+}
+
+fn factor_polynomial(variable: Expr, coefficients: Vec<Coefficient>) -> Option<Expr> {
+    let split = rational_factors(&coefficients)?;
+    let mut factors = vec![];
+    if split.zeros > 0 {
+        factors.push((variable.clone(), split.zeros as i64));
+    }
+    for ((p, q), multiplicity) in split.roots {
+        let linear = Sum::from_terms(vec![
+            Term::atom(variable.clone()).mul(&Term::constant(Coefficient::int(q))),
+            Term::constant(Coefficient::int(-p)),
+        ]);
+        factors.push((linear.to_expr(), multiplicity));
+    }
+    if split.rest.len() > 1 {
+        let rest = split
+            .rest
+            .iter()
+            .enumerate()
+            .map(|(power, a)| Term::power(variable.clone(), power as i64).mul(&Term::constant(Coefficient::int(*a))))
+            .collect();
+        factors.push((Sum::from_terms(rest).to_expr(), 1));
+    }
+
+    let term = Term {
+        coefficient: split.content,
+        factors,
+    };
+    Some(term.to_expr())
+}
+
+/// A polynomial with exact coefficients written as
+/// `content * x^zeros * (q1*x - p1)^m1 * ... * rest(x)`.
+pub(super) struct RationalFactors {
+    pub content: Coefficient,
+    pub zeros: usize,
+    /// Each rational root `p/q` with its multiplicity, largest root first.
+    pub roots: Vec<((i64, i64), i64)>,
+    /// What is left once every rational root is divided out, lowest power
+    /// first, with integer coefficients.
+    pub rest: Vec<i64>,
+}
+
+/// Finds the rational roots of a polynomial given lowest power first.
+pub(super) fn rational_factors(coefficients: &[Coefficient]) -> Option<RationalFactors> {
+    // Clear denominators and take out the content so the polynomial has
+    // integer coefficients and a positive leading coefficient.
+    let denominator = coefficients.iter().try_fold(1, |d, c| match c {
+        Coefficient::Ratio(_, b) => lcm(d, *b),
+        Coefficient::Float(_) => None,
+    })?;
+    let mut polynomial = coefficients
+        .iter()
+        .map(|c| match c {
+            Coefficient::Ratio(a, b) => a.checked_mul(denominator / b),
+            Coefficient::Float(_) => None,
+        })
+        .collect::<Option<Vec<i64>>>()?;
+    let sign = if *polynomial.last()? < 0 { -1 } else { 1 };
+    let content = sign * polynomial.iter().fold(0, |g, a| gcd(g, *a));
+    for a in polynomial.iter_mut() {
+        *a /= content;
+    }
+
+    let zeros = polynomial.iter().take_while(|a| **a == 0).count();
+    polynomial.drain(..zeros);
+
+    let mut roots: Vec<((i64, i64), i64)> = vec![];
+    while polynomial.len() > 1 {
+        let root = rational_roots(&polynomial).find(|&(p, q)| evaluate(&polynomial, p, q) == Some(0));
+        let (p, q) = match root {
+            Some(root) => root,
+            None => break,
+        };
+        polynomial = deflate(&polynomial, p, q);
+        match roots.iter_mut().find(|(root, _)| *root == (p, q)) {
+            Some((_, multiplicity)) => *multiplicity += 1,
+            None => roots.push(((p, q), 1)),
+        }
+    }
+    roots.sort_by(|((a, b), _), ((c, d), _)| (c * b).cmp(&(a * d)));
+
+    Some(RationalFactors {
+        content: Coefficient::ratio(content, denominator),
+        zeros,
+        roots,
+        rest: polynomial,
+    })
+}
+
+/// The coefficients of `expr` as a polynomial in `name`, lowest power
+/// first, or `None` when `name` appears other than in whole powers.
+pub(super) fn polynomial_coefficients(expr: &Expr, name: &str) -> Option<Vec<Sum>> {
+    let sum = Sum::from_expr(&rewrite(expr), true);
+    let variable = Expr::Variable(name.to_owned());
+    let mut coefficients: Vec<Vec<Term>> = vec![];
+    for term in sum.terms {
+        let power = term.exponent_of(&variable);
+        let rest = term.mul(&Term::power(variable.clone(), -power));
+        if !(0..=MAX_EXPANSION_POWER).contains(&power) || rest.factors.iter().any(|(base, _)| depends_on(base, name)) {
+            return None;
+        }
+        let power = power as usize;
+        if coefficients.len() <= power {
+            coefficients.resize(power + 1, vec![]);
+        }
+        coefficients[power].push(rest);
+    }
+    Some(coefficients.into_iter().map(Sum::from_terms).collect())
+}
+
+/// The coefficients of `expr` as a polynomial in `name`, lowest power
+/// first, when they are all numbers.
+pub fn numeric_coefficients(expr: &Expr, name: &str) -> Option<Vec<f64>> {
+    polynomial_coefficients(expr, name)?
+        .iter()
+        .map(|coefficient| coefficient.as_constant().map(Coefficient::to_f64))
+        .collect()
+}
+
+/// Splits a linear expression in `names` into its numeric coefficients and
+/// constant term.
+pub(super) fn linear_coefficients(expr: &Expr, names: &[String]) -> Option<(Vec<Coefficient>, Coefficient)> {
+    let mut coefficients = vec![Coefficient::int(0); names.len()];
+    let mut constant = Coefficient::int(0);
+    for term in Sum::from_expr(&rewrite(expr), true).terms {
+        match term.factors.as_slice() {
+            [] => constant = term.coefficient,
+            [(Expr::Variable(name), 1)] => {
+                let index = names.iter().position(|n| n == name)?;
+                coefficients[index] = term.coefficient;
+            }
+            _ => return None,
+        }
+    }
+    Some((coefficients, constant))
+}
+
+/// Candidate roots `p/q` of an integer polynomial: `p` divides the constant
+/// term and `q` the leading coefficient.
+fn rational_roots(polynomial: &[i64]) -> impl Iterator<Item = (i64, i64)> {
+    let numerators = divisors(polynomial[0]);
+    let denominators = divisors(*polynomial.last().unwrap());
+    let mut candidates = vec![];
+    for q in &denominators {
+        for p in &numerators {
+            if gcd(*p, *q) == 1 {
+                candidates.push((*p, *q));
+                candidates.push((-*p, *q));
+            }
+        }
+    }
+    candidates.into_iter()
+}
+
+/// The positive divisors of `n`, or none at all when there are too many to
+/// try.
+fn divisors(n: i64) -> Vec<i64> {
+    let n = n.unsigned_abs();
+    if n > 1_000_000_000_000 {
+        return vec![];
+    }
+    let mut divisors = vec![];
+    let mut d = 1;
+    while d * d <= n {
+        if n.is_multiple_of(d) {
+            divisors.push(d as i64);
+            if d * d != n {
+                divisors.push((n / d) as i64);
+            }
+        }
+        d += 1;
+    }
+    divisors
+}
+
+/// `q^n * polynomial(p/q)`, which is zero exactly when `p/q` is a root.
+fn evaluate(polynomial: &[i64], p: i64, q: i64) -> Option<i128> {
+    let mut result: i128 = 0;
+    let mut scale: i128 = 1;
+    for a in polynomial.iter().rev() {
+        result = result.checked_mul(p as i128)?.checked_add((*a as i128).checked_mul(scale)?)?;
+        scale = scale.checked_mul(q as i128)?;
+    }
+    Some(result)
+}
+
+/// Divides out the factor `q*x - p`.
+fn deflate(polynomial: &[i64], p: i64, q: i64) -> Vec<i64> {
+    let n = polynomial.len() - 1;
+    let mut quotient = vec![0; n];
+    quotient[n - 1] = polynomial[n] / q;
+    for i in (1..n).rev() {
+        quotient[i - 1] = (polynomial[i] + p * quotient[i]) / q;
+    }
+    quotient
+}
+
+fn lcm(a: i64, b: i64) -> Option<i64> {
+    (a / gcd(a, b)).checked_mul(b).map(i64::abs)
+}
+
+fn power_expr(base: &Expr, exponent: i64) -> Expr {
+    match exponent {
+        1 => base.clone(),
+        _ => Expr::binary(BinaryOp::Pow, base.clone(), Expr::Literal(Real::Int(exponent))),
+    }
+}
+
+fn product(factors: Vec<Expr>) -> Expr {
+    factors
+        .into_iter()
+        .reduce(|product, factor| Expr::binary(BinaryOp::Mul, product, factor))
+        .unwrap()
+}
+
+/// Big integers come first like other numbers, then plain variables, then
+/// calls and then anything else, so products read as `2*x*sin(x)`. Higher
+/// powers of the same base go first.
+fn compare_factors(a: &(Expr, i64), b: &(Expr, i64)) -> Ordering {
+    fn rank(expr: &Expr) -> u8 {
+        match expr {
+            Expr::BigInteger(_) => 0,
+            Expr::Variable(_) => 1,
+            Expr::Call(_, _) => 2,
+            _ => 3,
+        }
+    }
+    rank(&a.0)
+        .cmp(&rank(&b.0))
+        .then_with(|| a.0.to_string().cmp(&b.0.to_string()))
+        .then_with(|| b.1.cmp(&a.1))
+}
+
+fn compare_terms(a: &Term, b: &Term) -> Ordering {
+    b.degree().cmp(&a.degree()).then_with(|| {
+        a.factors
+            .iter()
+            .zip(&b.factors)
+            .map(|(x, y)| compare_factors(x, y))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| b.factors.len().cmp(&a.factors.len()))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ast::expression::Expr, parser::parse_input};
+
+    fn apply(rewrite: fn(&Expr) -> Expr, input: &str) -> String {
+        rewrite(&parse_input(input).unwrap()).to_string()
+    }
+
+    #[test]
+    fn test_canonical_forms() {
+        assert!(apply(super::expand, "(x + 1)^2") == "x^2 + 2*x + 1");
+        assert!(apply(super::expand, "(x - y)^3") == "x^3 - 3*x^2*y + 3*x*y^2 - y^3");
+        assert!(apply(super::simplify, "x*x + x - 1 + x") == "x^2 + 2*x - 1");
+        assert!(apply(super::simplify, "(x + 1)*(1 + x)") == "(x + 1)^2");
+        assert!(apply(super::simplify, "x/3 + x/3") == "2*x/3");
+        assert!(apply(super::simplify, "sin(y)^2 + cos(y)^2") == "1");
+        assert!(apply(super::factor, "x^2 + 2*x + 1") == "(x + 1)^2");
+        assert!(apply(super::factor, "2*x^3 - 3*x^2 + x") == "x*(x - 1)*(2*x - 1)");
+        assert!(apply(super::factor, "2*x*y + 4*x") == "2*x*(y + 2)");
+        assert!(super::collect(&parse_input("a*x^2 + b*x^2 + c*x + x + 5").unwrap(), "x").to_string()
+            == "x^2*(a + b) + x*(c + 1) + 5");
+    }
+}
@@ -0,0 +1,139 @@
+use std::cmp::Ordering;
+
+use crate::interpreter::data_types::Real;
+
+/// Floats above this may not be whole numbers that an `i64` can hold.
+const MAX_EXACT_FLOAT: f64 = 9_007_199_254_740_992.0;
+
+/// A numeric coefficient in a canonical sum. Integers and their quotients
+/// stay exact, so `x/3 + x/3` is `2*x/3`; anything involving a float, or an
+/// exact result that would overflow, becomes a float.
+#[derive(Clone, Copy, Debug)]
+pub enum Coefficient {
+    Ratio(i64, i64),
+    Float(f64),
+}
+
+impl Coefficient {
+    pub fn int(n: i64) -> Coefficient {
+        Coefficient::Ratio(n, 1)
+    }
+
+    /// Builds a normalised ratio: lowest terms with a positive denominator.
+    pub fn ratio(numerator: i64, denominator: i64) -> Coefficient {
+        if denominator == 0 {
+            return Coefficient::Float(numerator as f64 / 0.0);
+        }
+        let divisor = gcd(numerator, denominator);
+        let sign = if denominator < 0 { -1 } else { 1 };
+        match ((numerator / divisor).checked_mul(sign), (denominator / divisor).checked_mul(sign)) {
+            (Some(n), Some(d)) => Coefficient::Ratio(n, d),
+            _ => Coefficient::Float(numerator as f64 / denominator as f64),
+        }
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.to_f64() == 0.0
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.to_f64() < 0.0
+    }
+
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Coefficient::Ratio(n, d) => n as f64 / d as f64,
+            Coefficient::Float(x) => x,
+        }
+    }
+
+    pub fn to_real(self) -> Real {
+        match self {
+            Coefficient::Ratio(n, 1) => Real::Int(n),
+            _ => Real::Float(self.to_f64()),
+        }
+    }
+
+    pub fn add(self, other: Coefficient) -> Coefficient {
+        match (self, other) {
+            (Coefficient::Ratio(a, b), Coefficient::Ratio(c, d)) => {
+                let exact = a
+                    .checked_mul(d)
+                    .zip(c.checked_mul(b))
+                    .and_then(|(ad, cb)| ad.checked_add(cb))
+                    .zip(b.checked_mul(d));
+                match exact {
+                    Some((numerator, denominator)) => Coefficient::ratio(numerator, denominator),
+                    None => Coefficient::Float(self.to_f64() + other.to_f64()),
+                }
+            }
+            _ => Coefficient::Float(self.to_f64() + other.to_f64()),
+        }
+    }
+
+    pub fn mul(self, other: Coefficient) -> Coefficient {
+        match (self, other) {
+            (Coefficient::Ratio(a, b), Coefficient::Ratio(c, d)) => match (a.checked_mul(c), b.checked_mul(d)) {
+                (Some(numerator), Some(denominator)) => Coefficient::ratio(numerator, denominator),
+                _ => Coefficient::Float(self.to_f64() * other.to_f64()),
+            },
+            _ => Coefficient::Float(self.to_f64() * other.to_f64()),
+        }
+    }
+
+    pub fn neg(self) -> Coefficient {
+        self.mul(Coefficient::int(-1))
+    }
+
+    pub fn recip(self) -> Coefficient {
+        match self {
+            Coefficient::Ratio(n, d) => Coefficient::ratio(d, n),
+            Coefficient::Float(x) => Coefficient::Float(1.0 / x),
+        }
+    }
+
+    pub fn pow(self, exponent: i64) -> Coefficient {
+        let base = if exponent < 0 { self.recip() } else { self };
+        match (base, u32::try_from(exponent.unsigned_abs())) {
+            (Coefficient::Ratio(n, d), Ok(e)) => match (n.checked_pow(e), d.checked_pow(e)) {
+                (Some(n), Some(d)) => Coefficient::Ratio(n, d),
+                _ => Coefficient::Float(base.to_f64().powf(e as f64)),
+            },
+            _ => Coefficient::Float(self.to_f64().powf(exponent as f64)),
+        }
+    }
+}
+
+/// Whole floats are exact, since the parser reads `2` as a float.
+impl From<Real> for Coefficient {
+    fn from(x: Real) -> Self {
+        match x {
+            Real::Int(n) => Coefficient::int(n),
+            Real::Float(x) if x.fract() == 0.0 && x.abs() < MAX_EXACT_FLOAT => Coefficient::int(x as i64),
+            Real::Float(x) => Coefficient::Float(x),
+        }
+    }
+}
+
+impl PartialEq for Coefficient {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Coefficient::Ratio(a, b), Coefficient::Ratio(c, d)) => a == c && b == d,
+            _ => self.to_f64() == other.to_f64(),
+        }
+    }
+}
+
+impl PartialOrd for Coefficient {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.to_f64().partial_cmp(&other.to_f64())
+    }
+}
+
+pub fn gcd(a: i64, b: i64) -> i64 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    i64::try_from(a).unwrap_or(1).max(1)
+}
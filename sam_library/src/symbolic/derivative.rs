@@ -0,0 +1,265 @@
+use crate::{
+    ast::{
+        expression::Expr,
+        operators::{BinaryOp, UnaryOp},
+    },
+    errors::{ErrorWithMessage, SamError},
+    interpreter::data_types::Real,
+};
+
+use super::{add, call, depends_on, div, int, mul, neg, pow, simplify::simplify, sub, var};
+
+/// Differentiates `expr` with respect to the variable `name` and simplifies
+/// the result.
+pub fn differentiate(expr: &Expr, name: &str) -> Result<Expr, SamError> {
+    differentiate_in_angle_mode(expr, name, 1.0)
+}
+
+/// Like `differentiate`, for trigonometric functions that take and give
+/// angles in units of `radians_per_unit` radians.
+pub fn differentiate_in_angle_mode(expr: &Expr, name: &str, radians_per_unit: f64) -> Result<Expr, SamError> {
+    Ok(simplify(&derivative(expr, name, radians_per_unit)?))
+}
+
+/// Whether the builtin `function` depends on the angle mode.
+pub fn takes_angle(function: &str) -> bool {
+    is_trigonometric(function) || is_inverse_trigonometric(function) || matches!(function, "deg" | "rad")
+}
+
+/// Builtins of a number with no derivative rule: the derivatives of
+/// `digamma` and `zeta` are not builtins, and the rest only take whole
+/// numbers. Any other builtin of several arguments without a rule below
+/// cannot be differentiated either.
+const NO_DERIVATIVE: [&str; 8] = ["digamma", "zeta", "fib", "catalan", "isprime", "nextprime", "totient", "isqrt"];
+
+fn is_trigonometric(function: &str) -> bool {
+    matches!(function, "sin" | "cos" | "tan" | "sec" | "csc" | "cot")
+}
+
+fn is_inverse_trigonometric(function: &str) -> bool {
+    matches!(function, "asin" | "acos" | "atan")
+}
+
+/// Splits a call name such as `f''` into the function name and the order of
+/// the derivative being called.
+pub fn split_primes(name: &str) -> (&str, usize) {
+    let base = name.trim_end_matches('\'');
+    (base, name.len() - base.len())
+}
+
+fn derivative(expr: &Expr, name: &str, angle: f64) -> Result<Expr, SamError> {
+    if !depends_on(expr, name) {
+        return Ok(int(0));
+    }
+    let result = match expr {
+        Expr::Variable(_) => int(1),
+        Expr::Unary(UnaryOp::Neg, u) => neg(derivative(u, name, angle)?),
+        Expr::Binary(op, u, v) => binary_derivative(*op, u, v, name, angle)?,
+        Expr::Conditional(condition, then, otherwise) => Expr::Conditional(
+            condition.clone(),
+            Box::new(derivative(then, name, angle)?),
+            Box::new(derivative(otherwise, name, angle)?),
+        ),
+        Expr::Call(function, args) => call_derivative(function, args, name, angle)?,
+        Expr::List(items) => Expr::List(items.iter().map(|item| derivative(item, name, angle)).collect::<Result<_, _>>()?),
+        // `!` and `~` are piecewise constant.
+        Expr::Unary(_, _) => int(0),
+        Expr::Literal(_) | Expr::BigInteger(_) | Expr::PeekStack => int(0),
+        Expr::Assignment(_, _) | Expr::FunctionDeclaration(_, _, _) | Expr::Lambda(_, _) => {
+            return Err(ErrorWithMessage::new_box("can only differentiate expressions"))
+        }
+    };
+    Ok(result)
+}
+
+fn binary_derivative(op: BinaryOp, u: &Expr, v: &Expr, name: &str, angle: f64) -> Result<Expr, SamError> {
+    let du = derivative(u, name, angle)?;
+    let dv = derivative(v, name, angle)?;
+    let (u, v) = (u.clone(), v.clone());
+    let result = match op {
+        BinaryOp::Add => add(du, dv),
+        BinaryOp::Sub => sub(du, dv),
+        BinaryOp::Mul => add(mul(du, v), mul(u, dv)),
+        BinaryOp::Div => div(sub(mul(du, v.clone()), mul(u, dv)), pow(v, int(2))),
+        BinaryOp::LeftDiv => derivative(&div(v, u), name, angle)?,
+        BinaryOp::Pow if !depends_on(&v, name) => mul(mul(v.clone(), pow(u, sub(v, int(1)))), du),
+        BinaryOp::Pow if !depends_on(&u, name) => mul(mul(pow(u.clone(), v), ln(u)), dv),
+        BinaryOp::Pow => mul(pow(u.clone(), v.clone()), add(mul(dv, ln(u.clone())), div(mul(v, du), u))),
+        BinaryOp::Equation => Expr::binary(op, du, dv),
+        BinaryOp::Mod if !depends_on(&v, name) => du,
+        BinaryOp::Mod => {
+            return Err(ErrorWithMessage::new_box("cannot differentiate % with a variable divisor"))
+        }
+        // Comparisons, logic and bit operations are piecewise constant.
+        _ => int(0),
+    };
+    Ok(result)
+}
+
+/// Applies the chain rule to a call. Builtins use the table below; any other
+/// function of one argument is differentiated through its `f'` form.
+fn call_derivative(function: &str, args: &[Expr], name: &str, angle: f64) -> Result<Expr, SamError> {
+    if function == "diff" {
+        return derivative(&expand_diff(args)?, name, angle);
+    }
+    if function == "xor" || function == "round" {
+        return Ok(int(0));
+    }
+    if NO_DERIVATIVE.contains(&function) {
+        return Err(ErrorWithMessage::new_box(&format!("cannot differentiate {}, which has no derivative rule", function)));
+    }
+    match (function, args) {
+        ("log_base", [x, base]) => {
+            return derivative(&div(call("ln", vec![x.clone()]), call("ln", vec![base.clone()])), name, angle)
+        }
+        ("atan2", [y, x]) => {
+            let (dy, dx) = (derivative(y, name, angle)?, derivative(x, name, angle)?);
+            let numerator = sub(mul(x.clone(), dy), mul(y.clone(), dx));
+            let result = div(numerator, add(pow(x.clone(), int(2)), pow(y.clone(), int(2))));
+            return Ok(in_angle_units(result, angle));
+        }
+        ("hypot", [a, b]) => {
+            let numerator = add(mul(a.clone(), derivative(a, name, angle)?), mul(b.clone(), derivative(b, name, angle)?));
+            return Ok(div(numerator, call("hypot", vec![a.clone(), b.clone()])));
+        }
+        ("nthroot", [x, n]) if !depends_on(n, name) => {
+            let outer = div(call("nthroot", vec![x.clone(), n.clone()]), mul(n.clone(), x.clone()));
+            return Ok(mul(outer, derivative(x, name, angle)?));
+        }
+        ("beta", [a, b]) => {
+            let digamma_sum = call("digamma", vec![add(a.clone(), b.clone())]);
+            let da = mul(sub(call("digamma", vec![a.clone()]), digamma_sum.clone()), derivative(a, name, angle)?);
+            let db = mul(sub(call("digamma", vec![b.clone()]), digamma_sum), derivative(b, name, angle)?);
+            return Ok(mul(call("beta", vec![a.clone(), b.clone()]), add(da, db)));
+        }
+        // J_n' = (J_{n-1} - J_{n+1}) / 2, and the same for Y_n.
+        ("besselj" | "bessely", [n, x]) if !depends_on(n, name) => {
+            let lower = call(function, vec![sub(n.clone(), int(1)), x.clone()]);
+            let upper = call(function, vec![add(n.clone(), int(1)), x.clone()]);
+            return Ok(mul(div(sub(lower, upper), int(2)), derivative(x, name, angle)?));
+        }
+        // `deg(u)` is `u` degrees and `rad(u)` is `u` radians in units of `angle`.
+        ("deg", [u]) => {
+            let scale = div(var("pi"), mul(int(180), Expr::Literal(Real::Float(angle))));
+            return Ok(mul(scale, derivative(u, name, angle)?));
+        }
+        ("rad", [u]) => return Ok(in_angle_units(derivative(u, name, angle)?, angle)),
+        _ => {}
+    }
+    let u = match args {
+        [u] => u.clone(),
+        _ => {
+            return Err(ErrorWithMessage::new_box(&format!(
+                "cannot differentiate {} because it takes more than one argument",
+                function
+            )))
+        }
+    };
+    let outer = match builtin_derivative(function, &u) {
+        // With angles in units of `angle` radians, `sin(u)` is the radian
+        // sine of `angle * u` and `asin(u)` the radian arcsine over `angle`.
+        Some(outer) if angle != 1.0 && is_trigonometric(function) => mul(Expr::Literal(Real::Float(angle)), outer),
+        Some(outer) if is_inverse_trigonometric(function) => in_angle_units(outer, angle),
+        Some(outer) => outer,
+        None => call(&format!("{}'", function), vec![u.clone()]),
+    };
+    Ok(mul(outer, derivative(&u, name, angle)?))
+}
+
+/// The derivative of a one-argument builtin evaluated at `u`, or `None` when
+/// `function` has no rule.
+pub fn builtin_derivative(function: &str, u: &Expr) -> Option<Expr> {
+    let u = u.clone();
+    let result = match function {
+        "sin" => call("cos", vec![u]),
+        "cos" => neg(call("sin", vec![u])),
+        "tan" => div(int(1), pow(call("cos", vec![u]), int(2))),
+        "asin" => div(int(1), call("sqrt", vec![sub(int(1), pow(u, int(2)))])),
+        "acos" => neg(div(int(1), call("sqrt", vec![sub(int(1), pow(u, int(2)))]))),
+        "atan" => div(int(1), add(int(1), pow(u, int(2)))),
+        "sec" => mul(call("sec", vec![u.clone()]), call("tan", vec![u])),
+        "csc" => neg(mul(call("csc", vec![u.clone()]), call("cot", vec![u]))),
+        "cot" => neg(div(int(1), pow(call("sin", vec![u]), int(2)))),
+        "sinh" => call("cosh", vec![u]),
+        "cosh" => call("sinh", vec![u]),
+        "tanh" => div(int(1), pow(call("cosh", vec![u]), int(2))),
+        "asinh" => div(int(1), call("sqrt", vec![add(pow(u, int(2)), int(1))])),
+        "acosh" => div(int(1), call("sqrt", vec![sub(pow(u, int(2)), int(1))])),
+        "atanh" => div(int(1), sub(int(1), pow(u, int(2)))),
+        "exp" => call("exp", vec![u]),
+        "ln" => div(int(1), u),
+        "log2" => div(int(1), mul(u, call("ln", vec![int(2)]))),
+        "cbrt" => div(int(1), mul(int(3), pow(call("cbrt", vec![u]), int(2)))),
+        "abs" => call("sign", vec![u]),
+        "sign" | "floor" | "ceil" | "trunc" => int(0),
+        "frac" => int(1),
+        "log" => div(int(1), mul(u, call("ln", vec![int(10)]))),
+        "sqrt" => div(int(1), mul(int(2), call("sqrt", vec![u]))),
+        "gamma" => mul(call("gamma", vec![u.clone()]), call("digamma", vec![u])),
+        "lgamma" => call("digamma", vec![u]),
+        "fact" => mul(call("fact", vec![u.clone()]), call("digamma", vec![add(u, int(1))])),
+        "erf" => mul(div(int(2), call("sqrt", vec![var("pi")])), pow(var("e"), neg(pow(u, int(2))))),
+        "erfc" => neg(mul(div(int(2), call("sqrt", vec![var("pi")])), pow(var("e"), neg(pow(u, int(2)))))),
+        "erfinv" => mul(div(call("sqrt", vec![var("pi")]), int(2)), pow(var("e"), pow(call("erfinv", vec![u]), int(2)))),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// An angle in radians converted to units of `angle` radians.
+fn in_angle_units(radians: Expr, angle: f64) -> Expr {
+    match angle == 1.0 {
+        true => radians,
+        false => div(radians, Expr::Literal(Real::Float(angle))),
+    }
+}
+
+/// `ln(u)`, with `ln(e)` already simplified to 1.
+fn ln(u: Expr) -> Expr {
+    match &u {
+        Expr::Variable(name) if name == "e" => int(1),
+        _ => call("ln", vec![u]),
+    }
+}
+
+/// Replaces a nested `diff(expr, x)` or `diff(expr, x, n)` with the
+/// derivative it stands for.
+pub fn expand_diff(args: &[Expr]) -> Result<Expr, SamError> {
+    let (expr, name, order) = match args {
+        [expr, Expr::Variable(name)] => (expr, name, 1),
+        [expr, Expr::Variable(name), Expr::Literal(order)] => (expr, name, Into::<i64>::into(*order)),
+        _ => return Err(ErrorWithMessage::new_box("usage: diff(expression, variable[, order])")),
+    };
+    let mut result = expr.clone();
+    for _ in 0..order {
+        result = differentiate(&result, name)?;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::parse_input;
+
+    use super::differentiate;
+
+    fn diff(input: &str) -> String {
+        differentiate(&parse_input(input).unwrap(), "x").unwrap().to_string()
+    }
+
+    #[test]
+    fn test_derivative_rules() {
+        assert!(diff("x^2 * sin(x)") == "2*x*sin(x) + x^2*cos(x)");
+        assert!(diff("3 * x + 1") == "3");
+        assert!(diff("ln(x) / x") == "(1 - ln(x))/x^2");
+        assert!(diff("2^x") == "2^x*ln(2)");
+        assert!(diff("cos(x^2)") == "-2*sin(x^2)*x");
+        assert!(diff("f(x^2)") == "2*f'(x^2)*x");
+        assert!(diff("exp(2 * x)") == "2*exp(2*x)");
+        assert!(diff("tanh(x)") == "1/cosh(x)^2");
+        assert!(diff("e^x") == "e^x" && diff("x^x") == "x^x*(ln(x) + 1)");
+        assert!(diff("atan2(y, x)") == "-(y/(x^2 + y^2))");
+        assert!(diff("besselj(0, x)") == "(besselj(-1, x) - besselj(1, x))/2");
+        assert!(differentiate(&parse_input("zeta(x)").unwrap(), "x").is_err());
+    }
+}
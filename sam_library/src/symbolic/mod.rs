@@ -0,0 +1,100 @@
+pub mod algebra;
+mod coefficient;
+pub mod derivative;
+mod rules;
+pub mod simplify;
+pub mod solve;
+
+use crate::{
+    ast::{
+        expression::Expr,
+        operators::{BinaryOp, UnaryOp},
+    },
+    interpreter::data_types::Real,
+};
+
+pub(crate) fn int(x: i64) -> Expr {
+    Expr::Literal(Real::Int(x))
+}
+
+pub(crate) fn var(name: &str) -> Expr {
+    Expr::Variable(name.to_owned())
+}
+
+pub(crate) fn call(name: &str, args: Vec<Expr>) -> Expr {
+    Expr::Call(name.to_owned(), args)
+}
+
+pub(crate) fn neg(x: Expr) -> Expr {
+    Expr::unary(UnaryOp::Neg, x)
+}
+
+pub(crate) fn add(a: Expr, b: Expr) -> Expr {
+    Expr::binary(BinaryOp::Add, a, b)
+}
+
+pub(crate) fn sub(a: Expr, b: Expr) -> Expr {
+    Expr::binary(BinaryOp::Sub, a, b)
+}
+
+pub(crate) fn mul(a: Expr, b: Expr) -> Expr {
+    Expr::binary(BinaryOp::Mul, a, b)
+}
+
+pub(crate) fn div(a: Expr, b: Expr) -> Expr {
+    Expr::binary(BinaryOp::Div, a, b)
+}
+
+pub(crate) fn pow(a: Expr, b: Expr) -> Expr {
+    Expr::binary(BinaryOp::Pow, a, b)
+}
+
+/// Whether `expr` mentions the variable `name` anywhere.
+pub fn depends_on(expr: &Expr, name: &str) -> bool {
+    match expr {
+        Expr::Variable(var) => var == name,
+        Expr::Literal(_) | Expr::BigInteger(_) | Expr::PeekStack => false,
+        Expr::Unary(_, x) => depends_on(x, name),
+        Expr::Binary(_, a, b) => depends_on(a, name) || depends_on(b, name),
+        Expr::Conditional(c, t, e) => depends_on(c, name) || depends_on(t, name) || depends_on(e, name),
+        Expr::Call(_, args) | Expr::List(args) => args.iter().any(|arg| depends_on(arg, name)),
+        Expr::Assignment(_, value) => depends_on(value, name),
+        Expr::FunctionDeclaration(_, params, body) | Expr::Lambda(params, body) => {
+            !params.iter().any(|p| p == name) && depends_on(body, name)
+        }
+    }
+}
+
+/// The variables `expr` mentions, in the order they first appear.
+pub fn variables(expr: &Expr) -> Vec<String> {
+    fn visit(expr: &Expr, found: &mut Vec<String>) {
+        match expr {
+            Expr::Variable(name) if !found.contains(name) => found.push(name.clone()),
+            Expr::Unary(_, x) => visit(x, found),
+            Expr::Binary(_, a, b) => {
+                visit(a, found);
+                visit(b, found);
+            }
+            Expr::Conditional(c, t, e) => [c, t, e].into_iter().for_each(|x| visit(x, found)),
+            Expr::Call(_, args) | Expr::List(args) => args.iter().for_each(|arg| visit(arg, found)),
+            _ => {}
+        }
+    }
+    let mut found = vec![];
+    visit(expr, &mut found);
+    found
+}
+
+/// Replaces every occurrence of the variable `name` with `value`.
+pub fn substitute(expr: &Expr, name: &str, value: &Expr) -> Expr {
+    let recurse = |x: &Expr| substitute(x, name, value);
+    match expr {
+        Expr::Variable(var) if var == name => value.clone(),
+        Expr::Unary(op, x) => Expr::unary(*op, recurse(x)),
+        Expr::Binary(op, a, b) => Expr::binary(*op, recurse(a), recurse(b)),
+        Expr::Conditional(c, t, e) => Expr::Conditional(Box::new(recurse(c)), Box::new(recurse(t)), Box::new(recurse(e))),
+        Expr::Call(function, args) => Expr::Call(function.clone(), args.iter().map(recurse).collect()),
+        Expr::List(items) => Expr::List(items.iter().map(recurse).collect()),
+        _ => expr.clone(),
+    }
+}
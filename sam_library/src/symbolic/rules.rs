@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+
+use crate::{ast::expression::Expr, parser::parse_input};
+
+/// Rewrite rules as `pattern => replacement`. Variables starting with `_`
+/// match any subexpression, and must match the same one wherever they are
+/// repeated.
+const RULES: [(&str, &str); 7] = [
+    ("sin(asin(_x))", "_x"),
+    ("cos(acos(_x))", "_x"),
+    ("tan(atan(_x))", "_x"),
+    ("sqrt(_x)^2", "_x"),
+    ("sin(_x)/cos(_x)", "tan(_x)"),
+    ("log_base(_b^_x, _b)", "_x"),
+    ("ln(_x^_n)", "_n*ln(_x)"),
+];
+
+/// Applies the rewrite rules everywhere in `expr`, innermost first.
+pub fn rewrite(expr: &Expr) -> Expr {
+    let rules: Vec<(Expr, Expr)> = RULES
+        .iter()
+        .map(|(pattern, replacement)| (parse_input(pattern).unwrap(), parse_input(replacement).unwrap()))
+        .collect();
+    rewrite_with(expr, &rules)
+}
+
+fn rewrite_with(expr: &Expr, rules: &[(Expr, Expr)]) -> Expr {
+    let expr = match expr {
+        Expr::Unary(op, x) => Expr::unary(*op, rewrite_with(x, rules)),
+        Expr::Binary(op, a, b) => Expr::binary(*op, rewrite_with(a, rules), rewrite_with(b, rules)),
+        Expr::Call(name, args) => Expr::Call(name.clone(), args.iter().map(|arg| rewrite_with(arg, rules)).collect()),
+        Expr::List(items) => Expr::List(items.iter().map(|item| rewrite_with(item, rules)).collect()),
+        _ => expr.clone(),
+    };
+    for (pattern, replacement) in rules {
+        let mut bindings = HashMap::new();
+        if matches(pattern, &expr, &mut bindings) {
+            return rewrite_with(&instantiate(replacement, &bindings), rules);
+        }
+    }
+    expr
+}
+
+fn matches(pattern: &Expr, expr: &Expr, bindings: &mut HashMap<String, Expr>) -> bool {
+    match (pattern, expr) {
+        (Expr::Variable(name), _) if name.starts_with('_') => match bindings.get(name) {
+            Some(bound) => bound == expr,
+            None => {
+                bindings.insert(name.clone(), expr.clone());
+                true
+            }
+        },
+        (Expr::Unary(p, x), Expr::Unary(q, y)) => p == q && matches(x, y, bindings),
+        (Expr::Binary(p, a, b), Expr::Binary(q, c, d)) => p == q && matches(a, c, bindings) && matches(b, d, bindings),
+        (Expr::Call(f, xs), Expr::Call(g, ys)) => {
+            f == g && xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| matches(x, y, bindings))
+        }
+        _ => pattern == expr,
+    }
+}
+
+fn instantiate(replacement: &Expr, bindings: &HashMap<String, Expr>) -> Expr {
+    match replacement {
+        Expr::Variable(name) => bindings.get(name).cloned().unwrap_or_else(|| replacement.clone()),
+        Expr::Unary(op, x) => Expr::unary(*op, instantiate(x, bindings)),
+        Expr::Binary(op, a, b) => Expr::binary(*op, instantiate(a, bindings), instantiate(b, bindings)),
+        Expr::Call(name, args) => Expr::Call(name.clone(), args.iter().map(|arg| instantiate(arg, bindings)).collect()),
+        _ => replacement.clone(),
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{
+    ast::{
+        expression::Expr,
+        operators::{BinaryOp, UnaryOp},
+    },
+    compiler::optimizer::can_fold,
+    interpreter::data_types::Real,
+};
+
+use super::{int, mul, neg};
+
+/// Applies algebraic identities bottom-up. Unlike the optimizer this never
+/// substitutes constants such as `pi`, so results stay readable, and it
+/// treats `0 * x` as `0`, which is fine for symbolic work.
+pub fn simplify(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Unary(op, operand) => simplify_unary(*op, simplify(operand)),
+        Expr::Binary(op, lhs, rhs) => simplify_binary(*op, simplify(lhs), simplify(rhs)),
+        Expr::Conditional(condition, then, otherwise) => match simplify(condition) {
+            Expr::Literal(x) if is(x, 0) => simplify(otherwise),
+            Expr::Literal(_) => simplify(then),
+            condition => Expr::Conditional(
+                Box::new(condition),
+                Box::new(simplify(then)),
+                Box::new(simplify(otherwise)),
+            ),
+        },
+        Expr::Call(name, args) => Expr::Call(name.clone(), args.iter().map(simplify).collect()),
+        Expr::List(items) => Expr::List(items.iter().map(simplify).collect()),
+        _ => expr.clone(),
+    }
+}
+
+fn simplify_unary(op: UnaryOp, operand: Expr) -> Expr {
+    match (op, operand) {
+        (_, Expr::Literal(x)) => Expr::Literal(op.apply(x)),
+        (UnaryOp::Neg, Expr::Unary(UnaryOp::Neg, inner)) => *inner,
+        // Fold the sign into a leading coefficient: `-(2*x)` is `-2*x`.
+        (UnaryOp::Neg, Expr::Binary(BinaryOp::Mul, lhs, rhs)) if has_coefficient(&lhs) => {
+            mul(simplify_unary(op, *lhs), *rhs)
+        }
+        (_, operand) => Expr::unary(op, operand),
+    }
+}
+
+fn simplify_binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+    let a = literal(&lhs);
+    let b = literal(&rhs);
+    if let (Some(a), Some(b)) = (a, b) {
+        if can_fold(op, a, b) {
+            return Expr::Literal(op.apply(a, b));
+        }
+    }
+
+    match op {
+        BinaryOp::Add => match (a, b) {
+            (Some(a), _) if is(a, 0) => rhs,
+            (_, Some(b)) if is(b, 0) => lhs,
+            _ => match rhs {
+                Expr::Unary(UnaryOp::Neg, rhs) => simplify_binary(BinaryOp::Sub, lhs, *rhs),
+                _ => Expr::binary(op, lhs, rhs),
+            },
+        },
+        BinaryOp::Sub => match (a, b) {
+            (_, Some(b)) if is(b, 0) => lhs,
+            (Some(a), _) if is(a, 0) => simplify_unary(UnaryOp::Neg, rhs),
+            _ if lhs == rhs => int(0),
+            _ => match rhs {
+                Expr::Unary(UnaryOp::Neg, rhs) => Expr::binary(BinaryOp::Add, lhs, *rhs),
+                _ => Expr::binary(op, lhs, rhs),
+            },
+        },
+        BinaryOp::Mul => match (a, b) {
+            (Some(a), _) if is(a, 0) => int(0),
+            (_, Some(b)) if is(b, 0) => int(0),
+            (Some(a), _) if is(a, 1) => rhs,
+            (_, Some(b)) if is(b, 1) => lhs,
+            (Some(a), _) if is(a, -1) => simplify_unary(UnaryOp::Neg, rhs),
+            (_, Some(b)) if is(b, -1) => simplify_unary(UnaryOp::Neg, lhs),
+            // Keep numeric factors in front: `x * 2` becomes `2*x`.
+            (None, Some(_)) => simplify_binary(op, rhs, lhs),
+            _ => match (lhs, rhs) {
+                (Expr::Unary(UnaryOp::Neg, lhs), rhs) => simplify_unary(UnaryOp::Neg, simplify_binary(op, *lhs, rhs)),
+                (lhs, Expr::Unary(UnaryOp::Neg, rhs)) => simplify_unary(UnaryOp::Neg, simplify_binary(op, lhs, *rhs)),
+                // `a/b * b` and `b * a/b` cancel to `a`.
+                (Expr::Binary(BinaryOp::Div, a, b), c) | (c, Expr::Binary(BinaryOp::Div, a, b)) if *b == c => *a,
+                // Keep products left-associative: `a * (b * c)` becomes `a*b*c`.
+                (lhs, Expr::Binary(BinaryOp::Mul, inner_lhs, inner_rhs)) => {
+                    let lhs = simplify_binary(op, lhs, *inner_lhs);
+                    simplify_binary(op, lhs, *inner_rhs)
+                }
+                // Move a numeric factor out of the left operand: `(2*x) * y`
+                // stays as is, but `(x*2) * y` is never built.
+                (lhs, rhs) => match (lhs, literal(&rhs)) {
+                    (Expr::Binary(BinaryOp::Mul, inner_lhs, inner_rhs), _) if literal(&inner_rhs).is_some() => {
+                        simplify_binary(op, simplify_binary(op, *inner_rhs, *inner_lhs), rhs)
+                    }
+                    (lhs, _) => mul(lhs, rhs),
+                },
+            },
+        },
+        BinaryOp::Div => match (a, b) {
+            (Some(a), _) if is(a, 0) => int(0),
+            (_, Some(b)) if is(b, 1) => lhs,
+            _ if lhs == rhs => int(1),
+            _ => match (lhs, rhs) {
+                (Expr::Unary(UnaryOp::Neg, lhs), rhs) => neg(simplify_binary(op, *lhs, rhs)),
+                (lhs, rhs) => Expr::binary(op, lhs, rhs),
+            },
+        },
+        BinaryOp::Pow => match (a, b) {
+            (_, Some(b)) if is(b, 0) => int(1),
+            (_, Some(b)) if is(b, 1) => lhs,
+            (Some(a), _) if is(a, 1) => int(1),
+            _ => match lhs {
+                // (u^a)^b is u^(a*b) when both exponents are numbers.
+                Expr::Binary(BinaryOp::Pow, base, exponent) if literal(&exponent).is_some() && b.is_some() => {
+                    let exponent = simplify_binary(BinaryOp::Mul, *exponent, rhs);
+                    simplify_binary(BinaryOp::Pow, *base, exponent)
+                }
+                lhs => Expr::binary(op, lhs, rhs),
+            },
+        },
+        _ => Expr::binary(op, lhs, rhs),
+    }
+}
+
+fn literal(expr: &Expr) -> Option<Real> {
+    match expr {
+        Expr::Literal(x) => Some(*x),
+        _ => None,
+    }
+}
+
+/// Whether a product starts with a numeric factor.
+fn has_coefficient(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(_) => true,
+        Expr::Binary(BinaryOp::Mul, lhs, _) => has_coefficient(lhs),
+        _ => false,
+    }
+}
+
+/// Compares numerically, so `1` and `1.0` are the same.
+fn is(x: Real, n: i64) -> bool {
+    x == Real::Int(n)
+}
@@ -0,0 +1,177 @@
+use crate::{
+    algorithms::roots::polynomial_roots,
+    ast::{expression::Expr, operators::BinaryOp},
+    errors::{ErrorWithMessage, SamError},
+    interpreter::data_types::Real,
+};
+
+use super::{
+    add,
+    algebra::{linear_coefficients, polynomial_coefficients, rational_factors, simplify, Sum},
+    call,
+    coefficient::Coefficient,
+    div, int, mul, neg, pow, sub, var,
+};
+
+/// Moves everything to one side, so `a = b` becomes `a - b`. Anything that
+/// is not an equation is taken to equal zero.
+pub fn zero_form(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Binary(BinaryOp::Equation, a, b) => sub((**a).clone(), (**b).clone()),
+        _ => expr.clone(),
+    }
+}
+
+/// Solves a polynomial equation in `name`. Rational roots are exact and the
+/// rest are found numerically; complex roots are written in terms of `i`
+/// and only included when asked for. Linear and quadratic equations with
+/// symbolic coefficients are solved by formula. Gives `None` for anything
+/// else.
+pub fn solve_polynomial(equation: &Expr, name: &str, complex: bool) -> Result<Option<Vec<Expr>>, SamError> {
+    let coefficients = match polynomial_coefficients(&zero_form(equation), name) {
+        Some(coefficients) => coefficients,
+        None => return Ok(None),
+    };
+    if coefficients.is_empty() {
+        return Err(ErrorWithMessage::new_box(&format!("every value of {} is a solution", name)));
+    }
+    match coefficients.iter().map(Sum::as_constant).collect::<Option<Vec<Coefficient>>>() {
+        Some(numbers) => Ok(Some(numeric_roots(&numbers, complex))),
+        None => Ok(formula_roots(&coefficients)),
+    }
+}
+
+/// Solves a square system of linear equations by Gaussian elimination,
+/// exactly when every coefficient is rational.
+pub fn solve_linear_system(equations: &[Expr], names: &[String]) -> Result<Vec<Real>, SamError> {
+    if equations.len() != names.len() {
+        return Err(ErrorWithMessage::new_box("solve needs as many equations as unknowns"));
+    }
+    let mut rows = equations
+        .iter()
+        .map(|equation| {
+            let (mut row, constant) = linear_coefficients(&zero_form(equation), names)
+                .ok_or_else(|| ErrorWithMessage::new_box(&format!("{} is not linear in {}", equation, names.join(", "))))?;
+            row.push(constant.neg());
+            Ok(row)
+        })
+        .collect::<Result<Vec<Vec<Coefficient>>, SamError>>()?;
+
+    let n = names.len();
+    for column in 0..n {
+        let pivot = (column..n)
+            .filter(|row| !rows[*row][column].is_zero())
+            .max_by(|a, b| {
+                let (a, b) = (rows[*a][column].to_f64().abs(), rows[*b][column].to_f64().abs());
+                a.total_cmp(&b)
+            })
+            .ok_or_else(|| ErrorWithMessage::new_box("the system does not have a unique solution"))?;
+        rows.swap(column, pivot);
+        let pivot_row = rows[column].clone();
+        let inverse = pivot_row[column].recip();
+        for (index, row) in rows.iter_mut().enumerate() {
+            if index == column || row[column].is_zero() {
+                continue;
+            }
+            let factor = row[column].mul(inverse).neg();
+            for (entry, pivot_entry) in row.iter_mut().zip(&pivot_row).skip(column) {
+                *entry = entry.add(factor.mul(*pivot_entry));
+            }
+        }
+    }
+    Ok((0..n).map(|i| rows[i][n].mul(rows[i][i].recip()).to_real()).collect())
+}
+
+fn numeric_roots(coefficients: &[Coefficient], complex: bool) -> Vec<Expr> {
+    let mut real = vec![];
+    let remaining: Vec<f64> = match rational_factors(coefficients) {
+        Some(split) => {
+            if split.zeros > 0 {
+                real.push(0.0);
+            }
+            real.extend(split.roots.iter().map(|((p, q), _)| *p as f64 / *q as f64));
+            split.rest.iter().map(|a| *a as f64).collect()
+        }
+        None => coefficients.iter().map(|c| c.to_f64()).collect(),
+    };
+
+    let mut imaginary = vec![];
+    for (re, im) in polynomial_roots(&remaining) {
+        let scale = re.hypot(im).max(1.0);
+        let (re, im) = (tidy(re, scale), tidy(im, scale));
+        if im.abs() <= 1e-9 * re.abs().max(1.0) {
+            real.push(re);
+        } else if complex {
+            imaginary.push((re, im));
+        }
+    }
+    real.sort_by(f64::total_cmp);
+    real.dedup_by(|a, b| (*a - *b).abs() <= 1e-9 * a.abs().max(1.0));
+    imaginary.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+    let real = real.into_iter().map(|x| Expr::Literal(number(x)));
+    let imaginary = imaginary
+        .into_iter()
+        .map(|(re, im)| simplify(&add(Expr::Literal(number(re)), mul(Expr::Literal(number(im)), var("i")))));
+    real.chain(imaginary).collect()
+}
+
+fn formula_roots(coefficients: &[Sum]) -> Option<Vec<Expr>> {
+    let c: Vec<Expr> = coefficients.iter().map(Sum::to_expr).collect();
+    let roots = match c.as_slice() {
+        [c0, c1] => vec![neg(div(c0.clone(), c1.clone()))],
+        [c0, c1, c2] => {
+            let discriminant = sub(pow(c1.clone(), int(2)), mul(mul(int(4), c2.clone()), c0.clone()));
+            let root = call("sqrt", vec![discriminant]);
+            vec![
+                div(sub(neg(c1.clone()), root.clone()), mul(int(2), c2.clone())),
+                div(add(neg(c1.clone()), root), mul(int(2), c2.clone())),
+            ]
+        }
+        _ => return None,
+    };
+    Some(roots.iter().map(simplify).collect())
+}
+
+/// Numeric roots carry rounding error, so snap values that are within it
+/// of zero or of a whole number.
+fn tidy(x: f64, scale: f64) -> f64 {
+    if x.abs() <= 1e-12 * scale {
+        0.0
+    } else if (x - x.round()).abs() <= 1e-12 * scale {
+        x.round()
+    } else {
+        x
+    }
+}
+
+/// Whole numbers are shown as integers.
+fn number(x: f64) -> Real {
+    match x.fract() == 0.0 && x.abs() < 1e15 {
+        true => Real::Int(x as i64),
+        false => Real::Float(x),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::parse_input;
+
+    use super::solve_polynomial;
+
+    fn solve(input: &str, complex: bool) -> Vec<String> {
+        let roots = solve_polynomial(&parse_input(input).unwrap(), "x", complex).unwrap().unwrap();
+        roots.iter().map(|root| root.to_string()).collect()
+    }
+
+    #[test]
+    fn test_polynomial_roots() {
+        assert!(solve("x^2 - 4 = 0", false) == ["-2", "2"]);
+        assert!(solve("2*x^3 - 3*x^2 + x", false) == ["0", "0.5", "1"]);
+        assert!(solve("x^2 + 1 = 0", false).is_empty());
+        assert!(solve("x^2 + 1 = 0", true) == ["-i", "i"]);
+        assert!(solve("a*x + b = 0", false) == ["-b/a"]);
+        let roots = solve("x^2 - 2 = 0", false);
+        assert!(roots.len() == 2 && roots[1].starts_with("1.41421356237309"));
+    }
+}